@@ -0,0 +1,32 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use prodio::token::lexer::Lexer;
+
+/// Generate a program which has `n` functions.
+fn gen_program(n: usize) -> String {
+    (0..n)
+        .map(|i| {
+            format!(
+                "func f{}(a: u64, b: u64) {{\n    let c: u64 = a * {} + b;\n    if c >= 10 {{\n        return c / 2;\n    }}\n    return c - 1;\n}}\n",
+                i, i
+            )
+        })
+        .collect()
+}
+
+fn bench_lexer(c: &mut Criterion) {
+    let source_code = gen_program(1000);
+    c.bench_function("lex", |b| {
+        b.iter(|| Lexer::new(black_box(&source_code)).lex().unwrap().len())
+    });
+    c.bench_function("lex_presized", |b| {
+        b.iter(|| {
+            Lexer::new(black_box(&source_code))
+                .lex_presized()
+                .unwrap()
+                .len()
+        })
+    });
+}
+
+criterion_group!(benches, bench_lexer);
+criterion_main!(benches);
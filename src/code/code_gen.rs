@@ -1,14 +1,23 @@
+use crate::code::AsmSyntax;
 use crate::ir::gen_ir::{IRGenerator, IROp, IR};
-use crate::ARG_REGISTER_COUNT;
+use crate::parse::Type;
+use crate::target::{CallConv, Target};
 use crate::REGISTER_COUNT;
 
 const REGISTERS: [&str; REGISTER_COUNT] = ["rbx", "r10", "r11", "r12", "r13", "r14", "r15"];
-const ARG_REGISTERS: [&str; ARG_REGISTER_COUNT] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
+const REGISTERS32: [&str; REGISTER_COUNT] = ["ebx", "r10d", "r11d", "r12d", "r13d", "r14d", "r15d"];
+const REGISTERS8: [&str; REGISTER_COUNT] = ["bl", "r10b", "r11b", "r12b", "r13b", "r14b", "r15b"];
 
 /// Struct for retain generated code.
 #[derive(Debug, Default, Clone)]
 pub struct Generator {
     pub code: Vec<String>,
+    // Name of the function whose code is being generated.
+    func_name: String,
+    // Mangled names of static variables of the function.
+    statics: Vec<String>,
+    target: Target,
+    syntax: AsmSyntax,
 }
 
 impl Generator {
@@ -16,14 +25,69 @@ impl Generator {
         Default::default()
     }
 
+    pub fn with_target(target: Target) -> Self {
+        assert!(
+            target.register_count <= REGISTERS.len(),
+            "x86-64 has only {} registers for allocation",
+            REGISTERS.len()
+        );
+        Generator {
+            target,
+            ..Default::default()
+        }
+    }
+
+    /// Generate assembly written in `syntax`.
+    pub fn with_syntax(self, syntax: AsmSyntax) -> Self {
+        Generator { syntax, ..self }
+    }
+
+    /// Name of a register holding `size`-byte operands of an IR.
+    /// Addresses always use the full 64-bit name of `REGISTERS`.
+    fn sized_reg(reg: usize, size: usize) -> &'static str {
+        match size {
+            1 => REGISTERS8[reg],
+            4 => REGISTERS32[reg],
+            8 => REGISTERS[reg],
+            size => panic!("{}-byte operand is not supported", size),
+        }
+    }
+
+    /// Register which carries the `index`-th argument.
+    fn arg_register(&self, index: usize) -> &'static str {
+        let registers = self.target.call_conv.arg_registers();
+        registers.get(index).unwrap_or_else(|| {
+            panic!(
+                "More than {} arguments are not supported in {:?}",
+                registers.len(),
+                self.target.call_conv
+            )
+        })
+    }
+
     /// Entry point of code generation.
+    /// Instructions are built in Intel syntax and translated afterwards if needed.
     pub fn code_gen(&mut self, ir_generator: &IRGenerator) {
-        self.code.push(".intel_syntax noprefix\n".to_string());
+        self.code.push(match self.syntax {
+            AsmSyntax::Intel => ".intel_syntax noprefix\n".to_string(),
+            AsmSyntax::Att => ".att_syntax\n".to_string(),
+        });
+        self.gen_rodata(ir_generator);
+        self.gen_data(ir_generator);
+        self.code.push(".text\n".to_string());
         for func in &ir_generator.funcs {
-            self.code.push(format!(
-                ".global {}\n{}:\n  push rbp\n  mov rbp, rsp",
-                func.name, func.name
-            ));
+            self.func_name = func.name.clone();
+            self.statics = func
+                .statics
+                .iter()
+                .map(|(symbol, _)| symbol.clone())
+                .collect();
+            // Only the entry point is visible to the linker.
+            if func.name == "main" {
+                self.code.push(".global main".to_string());
+            }
+            self.code
+                .push(format!("{}:\n  push rbp\n  mov rbp, rsp", func.name));
             self.code.push(format!("  sub rsp, {}", func.stack_size));
             for ir in &func.ir_vec {
                 self.gen(ir);
@@ -33,24 +97,80 @@ impl Generator {
                 func.name
             ));
         }
+        if self.syntax == AsmSyntax::Att {
+            for code in self.code.iter_mut().skip(1) {
+                *code = code.lines().map(to_att).collect::<Vec<_>>().join("\n");
+            }
+        }
+    }
+
+    /// Generate read-only data section which contains string literals.
+    fn gen_rodata(&mut self, ir_generator: &IRGenerator) {
+        self.code.push(".section .rodata".to_string());
+        for func in &ir_generator.funcs {
+            for (i, string) in func.strings.iter().enumerate() {
+                self.code.push(format!(
+                    "{}:\n  .string \"{}\"",
+                    str_label(&func.name, i),
+                    escape_str(string)
+                ));
+            }
+        }
+    }
+
+    /// Generate data section which contains static variables, if any.
+    fn gen_data(&mut self, ir_generator: &IRGenerator) {
+        let statics: Vec<_> = ir_generator
+            .funcs
+            .iter()
+            .flat_map(|func| &func.statics)
+            .collect();
+        if statics.is_empty() {
+            return;
+        }
+        self.code.push(".data".to_string());
+        for (symbol, init) in statics {
+            self.code.push(format!(".L{}:\n  .quad {}", symbol, init));
+        }
     }
 
     /// Generate assembly code for an IR.
     fn gen(&mut self, ir: &IR) {
         match &ir.op {
             IROp::Imm => self.gen_immidiate(ir),
-            IROp::Add | IROp::Sub | IROp::Mul | IROp::Div => self.gen_binary_operator(ir),
-            IROp::Plus | IROp::Minus => self.gen_unary_operator(ir),
+            IROp::Mov => self.gen_mov(ir),
+            IROp::Add
+            | IROp::Sub
+            | IROp::Mul
+            | IROp::Div
+            | IROp::Rem
+            | IROp::BitAnd
+            | IROp::BitOr
+            | IROp::BitXor
+            | IROp::Shl
+            | IROp::Shr => self.gen_binary_operator(ir),
+            IROp::Eq | IROp::Ne | IROp::Lt | IROp::Le | IROp::Gt | IROp::Ge => {
+                self.gen_comparison(ir)
+            }
+            IROp::Min | IROp::Max => self.gen_select(ir),
+            IROp::Neg => self.gen_unary_operator(ir),
+            IROp::Cast(ty) | IROp::Zext(ty) => self.gen_cast(ir, ty),
             IROp::BpOffset => self.gen_bprel(ir),
-            IROp::FuncCall(name) => self.gen_func_call(ir, name.to_string()),
+            IROp::StrAddr => self.gen_str_addr(ir),
+            IROp::GlobalAddr => self.gen_global_addr(ir),
+            // A tail call is not lowered to `jmp` yet.
+            IROp::FuncCall(name) | IROp::TailCall(name) => self.gen_func_call(ir, name.to_string()),
             IROp::Load => self.gen_load(ir),
             IROp::LoadParam => self.gen_load_param(ir),
             IROp::Store => self.gen_store(ir),
             IROp::StoreArg => self.gen_store_arg(ir),
             IROp::Cond => self.gen_cond(ir),
+            IROp::Branch(label_name) => self.gen_branch(ir, label_name),
+            IROp::BranchOverflow(label_name) => self.code.push(format!("  jo .L{}", label_name)),
             IROp::Label(label_name) => self.gen_label(ir, label_name.to_string()),
             IROp::Jmp(label_name) => self.gen_jmp(label_name.to_string()),
             IROp::Return => self.gen_return(ir),
+            IROp::Trap => self.code.push("  ud2".to_string()),
             IROp::Kill => (),
         }
     }
@@ -67,46 +187,118 @@ impl Generator {
 
     /// Generate code for binary operator.
     fn gen_binary_operator(&mut self, ir: &IR) {
-        let lhs_reg_count = ir.lhs.unwrap();
-        let rhs_reg_count = ir.rhs.unwrap();
+        let lhs = Generator::sized_reg(ir.lhs.unwrap(), ir.size);
+        let rhs = Generator::sized_reg(ir.rhs.unwrap(), ir.size);
+        // Dividend is sign-extended into `rdx:rax`, or `edx:eax` for 32-bit int.
+        let (quotient, remainder, sign_extend) = match ir.size {
+            4 => ("eax", "edx", "cdq"),
+            _ => ("rax", "rdx", "cqo"),
+        };
         match ir.op {
-            IROp::Add => self.code.push(format!(
-                "  add {}, {}",
-                REGISTERS[lhs_reg_count], REGISTERS[rhs_reg_count]
-            )),
-            IROp::Sub => self.code.push(format!(
-                "  sub {}, {}",
-                REGISTERS[lhs_reg_count], REGISTERS[rhs_reg_count]
-            )),
-            IROp::Mul => self.code.push(format!(
-                "  imul {}, {}",
-                REGISTERS[lhs_reg_count], REGISTERS[rhs_reg_count]
-            )),
+            IROp::Add => self.code.push(format!("  add {}, {}", lhs, rhs)),
+            IROp::Sub => self.code.push(format!("  sub {}, {}", lhs, rhs)),
+            IROp::Mul => self.code.push(format!("  imul {}, {}", lhs, rhs)),
             IROp::Div => {
+                self.code.push(format!("  mov {}, {}", quotient, lhs));
+                self.code.push(format!("  {}", sign_extend));
+                self.code.push(format!("  idiv {}", rhs));
+                self.code.push(format!("  mov {}, {}", lhs, quotient));
+            }
+            IROp::Rem => {
+                self.code.push(format!("  mov {}, {}", quotient, lhs));
+                self.code.push(format!("  {}", sign_extend));
+                self.code.push(format!("  idiv {}", rhs));
+                self.code.push(format!("  mov {}, {}", lhs, remainder));
+            }
+            IROp::BitAnd => self.code.push(format!("  and {}, {}", lhs, rhs)),
+            IROp::BitOr => self.code.push(format!("  or {}, {}", lhs, rhs)),
+            IROp::BitXor => self.code.push(format!("  xor {}, {}", lhs, rhs)),
+            // Shift count must be in `cl`.
+            IROp::Shl => {
                 self.code
-                    .push(format!("  mov rax, {}", REGISTERS[lhs_reg_count]));
-                self.code.push("  cqo".to_string());
+                    .push(format!("  mov rcx, {}", REGISTERS[ir.rhs.unwrap()]));
+                self.code.push(format!("  shl {}, cl", lhs));
+            }
+            IROp::Shr => {
                 self.code
-                    .push(format!("  idiv {}", REGISTERS[rhs_reg_count]));
+                    .push(format!("  mov rcx, {}", REGISTERS[ir.rhs.unwrap()]));
+                self.code.push(format!("  sar {}, cl", lhs));
             }
             _ => unreachable!(),
         }
     }
 
+    /// Generate code to copy a register into another.
+    /// The full width is copied as the value may be a pointer.
+    fn gen_mov(&mut self, ir: &IR) {
+        self.code.push(format!(
+            "  mov {}, {}",
+            REGISTERS[ir.lhs.unwrap()],
+            REGISTERS[ir.rhs.unwrap()]
+        ));
+    }
+
+    /// Generate code for comparison operator.
+    /// The result is zero-extended so that the register holds exactly 0 or 1.
+    fn gen_comparison(&mut self, ir: &IR) {
+        let lhs = Generator::sized_reg(ir.lhs.unwrap(), ir.size);
+        let rhs = Generator::sized_reg(ir.rhs.unwrap(), ir.size);
+        let set_instruction = match ir.op {
+            IROp::Eq => "sete",
+            IROp::Ne => "setne",
+            IROp::Lt => "setl",
+            IROp::Le => "setle",
+            IROp::Gt => "setg",
+            IROp::Ge => "setge",
+            _ => unreachable!(),
+        };
+        self.code.push(format!("  cmp {}, {}", lhs, rhs));
+        self.code.push(format!("  {} al", set_instruction));
+        self.code.push(format!("  movzx {}, al", lhs));
+    }
+
+    /// Generate code for `Min` and `Max`, which compare and conditionally move
+    /// `rhs` into `lhs` instead of branching.
+    fn gen_select(&mut self, ir: &IR) {
+        let lhs = Generator::sized_reg(ir.lhs.unwrap(), ir.size);
+        let rhs = Generator::sized_reg(ir.rhs.unwrap(), ir.size);
+        let cmov_instruction = match ir.op {
+            IROp::Min => "cmovg",
+            IROp::Max => "cmovl",
+            _ => unreachable!(),
+        };
+        self.code.push(format!("  cmp {}, {}", lhs, rhs));
+        self.code
+            .push(format!("  {} {}, {}", cmov_instruction, lhs, rhs));
+    }
+
     /// Generate code for unary operator.
     fn gen_unary_operator(&mut self, ir: &IR) {
         let reg_count = ir.lhs.unwrap();
         match ir.op {
-            IROp::Plus => {
-                self.code.push("  pop rax".to_string());
-            }
-            IROp::Minus => {
-                self.code.push(format!("  neg {}", REGISTERS[reg_count]));
+            IROp::Neg => {
+                self.code.push(format!(
+                    "  neg {}",
+                    Generator::sized_reg(reg_count, ir.size)
+                ));
             }
             _ => unreachable!(),
         }
     }
 
+    /// Generate code to truncate a value in the register into `ty`,
+    /// or to zero-extend a value of `ty`; both of them are done by `movzx`.
+    fn gen_cast(&mut self, ir: &IR, ty: &Type) {
+        let reg_count = ir.lhs.unwrap();
+        match ty {
+            Type::U8 => self.code.push(format!(
+                "  movzx {}, {}",
+                REGISTERS[reg_count], REGISTERS8[reg_count]
+            )),
+            Type::U64 | Type::Ptr(_) | Type::Array(..) => (),
+        }
+    }
+
     /// Generate code to store an address into the register.
     fn gen_bprel(&mut self, ir: &IR) {
         let offset = ir.rhs.expect("Offset from $rbp is not specified.");
@@ -115,10 +307,41 @@ impl Generator {
             .push(format!("  lea {}, [rbp-{}]", REGISTERS[reg_count], offset));
     }
 
+    /// Generate code to store an address of a string literal into the register.
+    fn gen_str_addr(&mut self, ir: &IR) {
+        let reg_count = ir.lhs.unwrap();
+        self.code.push(format!(
+            "  lea {}, [rip+{}]",
+            REGISTERS[reg_count],
+            str_label(&self.func_name, ir.rhs.unwrap())
+        ));
+    }
+
+    /// Generate code to store an address of a static variable into the register.
+    fn gen_global_addr(&mut self, ir: &IR) {
+        let reg_count = ir.lhs.unwrap();
+        self.code.push(format!(
+            "  lea {}, [rip+.L{}]",
+            REGISTERS[reg_count],
+            self.statics[ir.rhs.unwrap()]
+        ));
+    }
+
     /// Generate code to call a function.
     fn gen_func_call(&mut self, ir: &IR, name: String) {
         self.save_registers(ir.lhs);
+        let shadow_space = self.target.call_conv.shadow_space();
+        if shadow_space > 0 {
+            self.code.push(format!("  sub rsp, {}", shadow_space));
+        }
+        // System V passes the number of vector registers used by a variadic call in `al`.
+        if let (Some(vector_args), CallConv::SysV) = (ir.rhs, self.target.call_conv) {
+            self.code.push(format!("  mov eax, {}", vector_args));
+        }
         self.code.push(format!("  call {}", name));
+        if shadow_space > 0 {
+            self.code.push(format!("  add rsp, {}", shadow_space));
+        }
         let ret_reg = ir.lhs.unwrap();
         self.code.push(format!("  mov {}, rax", REGISTERS[ret_reg]));
         self.restore_registers(ir.lhs);
@@ -145,12 +368,14 @@ impl Generator {
     }
 
     /// Make sure the destination register has an address.
+    /// A byte is zero-extended to the whole register.
     fn gen_load(&mut self, ir: &IR) {
-        self.code.push(format!(
-            "  mov {}, [{}]",
-            REGISTERS[ir.rhs.unwrap()],
-            REGISTERS[ir.lhs.unwrap()]
-        ));
+        let dst = REGISTERS[ir.rhs.unwrap()];
+        let addr = REGISTERS[ir.lhs.unwrap()];
+        self.code.push(match ir.size {
+            1 => format!("  movzx {}, byte ptr [{}]", dst, addr),
+            _ => format!("  mov {}, [{}]", dst, addr),
+        });
     }
 
     /// Source register: Register to pass an argument(lhs)
@@ -159,17 +384,19 @@ impl Generator {
         self.code.push(format!(
             "  mov [{}], {}",
             REGISTERS[ir.rhs.unwrap()],
-            ARG_REGISTERS[ir.lhs.unwrap()]
+            self.arg_register(ir.lhs.unwrap())
         ));
     }
 
     /// Make sure the source register has an address.
+    /// Only the low `size` bytes of the source register are written.
     fn gen_store(&mut self, ir: &IR) {
-        self.code.push(format!(
-            "  mov [{}], {}",
-            REGISTERS[ir.lhs.unwrap()],
-            REGISTERS[ir.rhs.unwrap()]
-        ));
+        let addr = REGISTERS[ir.lhs.unwrap()];
+        let src = Generator::sized_reg(ir.rhs.unwrap(), ir.size);
+        self.code.push(match ir.size {
+            1 => format!("  mov byte ptr [{}], {}", addr, src),
+            _ => format!("  mov [{}], {}", addr, src),
+        });
     }
 
     /// Source register: Register which contains a result of evaled an argument(rhs)
@@ -177,7 +404,7 @@ impl Generator {
     fn gen_store_arg(&mut self, ir: &IR) {
         self.code.push(format!(
             "  mov {}, {}",
-            ARG_REGISTERS[ir.lhs.unwrap()],
+            self.arg_register(ir.lhs.unwrap()),
             REGISTERS[ir.rhs.unwrap()]
         ));
     }
@@ -186,12 +413,21 @@ impl Generator {
         let reg_flag = ir.lhs.unwrap();
         let label_number = ir.rhs.unwrap();
         self.code.push(format!("  cmp {}, 0", REGISTERS[reg_flag]));
-        self.code.push(format!("  je .Lelse{}", label_number));
+        self.code
+            .push(format!("  je .L{}..else{}", self.func_name, label_number));
+    }
+
+    fn gen_branch(&mut self, ir: &IR, label_name: &str) {
+        self.code
+            .push(format!("  cmp {}, 0", REGISTERS[ir.lhs.unwrap()]));
+        self.code.push(format!("  jne .L{}", label_name));
     }
 
     fn gen_label(&mut self, ir: &IR, name: String) {
+        // Numbered labels are generated per function, so they are qualified by its name.
         if let Some(label_number) = ir.lhs {
-            self.code.push(format!(".L{}{}:", name, label_number));
+            self.code
+                .push(format!(".L{}..{}{}:", self.func_name, name, label_number));
         } else {
             self.code.push(format!(".L{}:", name));
         }
@@ -206,3 +442,246 @@ impl Generator {
             .push(format!("  mov rax, {}", REGISTERS[ir.lhs.unwrap()]));
     }
 }
+
+/// Label of `index`-th string literal in a function.
+fn str_label(func_name: &str, index: usize) -> String {
+    format!(".L{}..str{}", func_name, index)
+}
+
+/// Translate a line of Intel syntax into AT&T syntax.
+/// Labels and directives are left as they are.
+fn to_att(line: &str) -> String {
+    let instruction = line.trim_start();
+    if instruction.is_empty() || instruction.starts_with('.') || instruction.ends_with(':') {
+        return line.to_string();
+    }
+    let (mnemonic, operands) = match instruction.split_once(' ') {
+        Some((mnemonic, operands)) => (mnemonic, operands.split(", ").collect()),
+        None => (instruction, Vec::new()),
+    };
+    // Operands of jumps and calls are labels.
+    if mnemonic == "call" || mnemonic.starts_with('j') {
+        return line.to_string();
+    }
+    let mnemonic = match (mnemonic, operands.first()) {
+        ("cqo", _) => "cqto",
+        ("cdq", _) => "cltd",
+        // The source is always a byte register, and the destination decides the suffix.
+        ("movzx", Some(dst)) if dst.starts_with('e') || dst.ends_with('d') => "movzbl",
+        ("movzx", _) => "movzbq",
+        _ => mnemonic,
+    };
+    let operands: Vec<String> = operands.iter().rev().map(|op| att_operand(op)).collect();
+    if operands.is_empty() {
+        format!("  {}", mnemonic)
+    } else {
+        format!("  {} {}", mnemonic, operands.join(", "))
+    }
+}
+
+/// Translate an operand of Intel syntax, which is a register, an immediate,
+/// or a memory reference like `[rbp-8]` or `[rip+label]`, into AT&T syntax.
+fn att_operand(operand: &str) -> String {
+    // The size of memory is told by the register or the mnemonic in AT&T syntax.
+    let operand = operand.trim_start_matches("byte ptr ");
+    if let Some(address) = operand
+        .strip_prefix('[')
+        .and_then(|op| op.strip_suffix(']'))
+    {
+        return match address.split_once('-') {
+            Some((base, offset)) => format!("-{}(%{})", offset, base),
+            None => match address.split_once('+') {
+                Some((base, offset)) => format!("{}(%{})", offset, base),
+                None => format!("(%{})", address),
+            },
+        };
+    }
+    if operand.starts_with(|c: char| c.is_ascii_digit() || c == '-') {
+        format!("${}", operand)
+    } else {
+        format!("%{}", operand)
+    }
+}
+
+/// Escape a string so that the assembler reads it as it is.
+fn escape_str(string: &str) -> String {
+    let mut escaped = String::new();
+    for b in string.bytes() {
+        match b {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            b' '..=b'~' => escaped.push(b as char),
+            _ => escaped.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::code::code_gen::Generator;
+    use crate::code::AsmSyntax;
+    use crate::ir::gen_ir::IRGenerator;
+    use crate::ir::testutil::{gen_ir_of, gen_ir_with};
+    use crate::target::Target;
+
+    fn compile(source_code: &str) -> Vec<String> {
+        compile_for(source_code, Target::x86_64_sysv())
+    }
+
+    fn compile_for(source_code: &str, target: Target) -> Vec<String> {
+        let mut ir_generator = gen_ir_with(source_code, IRGenerator::with_target(target)).unwrap();
+        ir_generator.reg_alloc(&target);
+        let mut generator = Generator::with_target(target);
+        generator.code_gen(&ir_generator);
+        generator.code
+    }
+
+    #[test]
+    fn test_call_conv() {
+        let source_code = "func main() { return f(1); }";
+        let sysv = compile_for(source_code, Target::x86_64_sysv());
+        assert!(sysv.contains(&"  mov rdi, rbx".to_string()));
+        assert!(!sysv.iter().any(|line| line == "  sub rsp, 32"));
+
+        let win64 = compile_for(source_code, Target::x86_64_win64());
+        assert!(win64.contains(&"  mov rcx, rbx".to_string()));
+        let call = win64.iter().position(|line| line == "  call f").unwrap();
+        assert_eq!(win64[call - 1], "  sub rsp, 32");
+        assert_eq!(win64[call + 1], "  add rsp, 32");
+    }
+
+    #[test]
+    fn test_str_lit() {
+        let code = compile(r#"func main() { let s: u64 = "hi\n"; return 0; }"#);
+        assert_eq!(code[1], ".section .rodata");
+        assert_eq!(code[2], ".Lmain..str0:\n  .string \"hi\\012\"");
+        assert!(code.contains(&"  lea r10, [rip+.Lmain..str0]".to_string()));
+    }
+
+    #[test]
+    fn test_user_label_named_like_generated() {
+        let code =
+            compile("func main() { let a: u64 = 1; if (a) { goto else1; } else1: return a; }");
+        assert!(code.contains(&".Lmain.else1:".to_string()));
+        assert!(code.contains(&".Lmain..else1:".to_string()));
+        assert!(code.contains(&"  je .Lmain..else1".to_string()));
+        assert!(code.contains(&"  jmp .Lmain.else1".to_string()));
+    }
+
+    #[test]
+    fn test_signed_div() {
+        let code = compile("func f(a: u64, b: u64) { return a / b; }");
+        let idiv = code.iter().position(|line| line == "  idiv r10").unwrap();
+        assert_eq!(code[idiv - 2..idiv], ["  mov rax, rbx", "  cqo"]);
+        assert_eq!(code[idiv + 1], "  mov rbx, rax");
+    }
+
+    #[test]
+    fn test_variadic_call() {
+        let source_code =
+            "extern func printf(fmt: u8*, ...); func main() { return printf(\"hi\"); }";
+        let sysv = compile_for(source_code, Target::x86_64_sysv());
+        let call = sysv
+            .iter()
+            .position(|line| line == "  call printf")
+            .unwrap();
+        assert_eq!(sysv[call - 1], "  mov eax, 0");
+
+        let win64 = compile_for(source_code, Target::x86_64_win64());
+        assert!(!win64.contains(&"  mov eax, 0".to_string()));
+    }
+
+    #[test]
+    fn test_min_max() {
+        let code = compile("func f(a: u64, b: u64) { return min(a, b) + max(a, b); }");
+        let cmovs: Vec<usize> = code
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.starts_with("  cmov"))
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(cmovs.len(), 2);
+        assert!(code[cmovs[0]].starts_with("  cmovg "));
+        assert!(code[cmovs[1]].starts_with("  cmovl "));
+        assert!(cmovs.iter().all(|&i| code[i - 1].starts_with("  cmp ")));
+    }
+
+    #[test]
+    fn test_int_width() {
+        let int32 = Target {
+            int_size: 4,
+            ..Target::x86_64_sysv()
+        };
+        let code = compile_for("func main() { return -(2 + 3 * 4); }", int32);
+        assert!(code.contains(&"  imul r10d, r11d".to_string()));
+        assert!(code.contains(&"  add ebx, r10d".to_string()));
+        assert!(code.contains(&"  neg ebx".to_string()));
+
+        // Operations on `u64` and pointers stay 64-bit.
+        let code = compile_for("func f(a: u64, p: u8*) { return p + a - 1; }", int32);
+        assert!(code.contains(&"  add rbx, r10".to_string()));
+        assert!(code.contains(&"  sub rbx, r10".to_string()));
+        // Addresses of variables keep the 64-bit name.
+        assert!(code.contains(&"  lea rbx, [rbp-8]".to_string()));
+
+        let int64 = compile_for("func main() { return 2 + 3; }", Target::x86_64_sysv());
+        assert!(int64.contains(&"  add rbx, r10".to_string()));
+    }
+
+    #[test]
+    fn test_asm_syntax() {
+        let source_code = r#"func main() { let s: u64 = "hi"; let a: u64 = 7 / 2; return a; }"#;
+        let intel = compile(source_code);
+        assert_eq!(intel[0], ".intel_syntax noprefix\n");
+        assert!(intel.iter().all(|line| !line.contains('%')));
+        assert!(intel.contains(&"  mov rax, rbx".to_string()));
+
+        let mut ir_generator = gen_ir_of(source_code).unwrap();
+        ir_generator.reg_alloc(&Target::x86_64_sysv());
+        let mut generator = Generator::new().with_syntax(AsmSyntax::Att);
+        generator.code_gen(&ir_generator);
+        let att = generator.code;
+        assert_eq!(att[0], ".att_syntax\n");
+        assert_eq!(att.len(), intel.len());
+        assert!(att.contains(&"  mov %rbx, %rax".to_string()));
+        assert!(att.contains(&"main:\n  push %rbp\n  mov %rsp, %rbp".to_string()));
+        assert!(att.contains(&"  lea .Lmain..str0(%rip), %r10".to_string()));
+        assert!(att.contains(&"  lea -8(%rbp), %rbx".to_string()));
+        assert!(att.contains(&"  cqto".to_string()));
+    }
+
+    #[test]
+    fn test_byte_access() {
+        let source_code = "func f(p: u8*) { *p = 1; return *p; }";
+        let intel = compile(source_code);
+        assert!(intel.contains(&"  mov byte ptr [rbx], r10b".to_string()));
+        assert!(intel.contains(&"  movzx rbx, byte ptr [rbx]".to_string()));
+
+        let mut ir_generator = gen_ir_of(source_code).unwrap();
+        ir_generator.reg_alloc(&Target::x86_64_sysv());
+        let mut generator = Generator::new().with_syntax(AsmSyntax::Att);
+        generator.code_gen(&ir_generator);
+        assert!(generator.code.contains(&"  mov %r10b, (%rbx)".to_string()));
+        assert!(generator
+            .code
+            .contains(&"  movzbq (%rbx), %rbx".to_string()));
+    }
+
+    #[test]
+    fn test_return_comparison() {
+        let code = compile("func main() { return 3 == 3; }");
+        let body: Vec<&str> = code.iter().skip(6).map(|line| line.as_str()).collect();
+        assert_eq!(
+            body[..6],
+            [
+                "  mov rbx, 3",
+                "  mov r10, 3",
+                "  cmp rbx, r10",
+                "  sete al",
+                "  movzx rbx, al",
+                "  mov rax, rbx",
+            ]
+        );
+    }
+}
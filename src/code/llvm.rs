@@ -0,0 +1,557 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::ir::gen_ir::{Function, IRGenerator, IROp, IR};
+use crate::parse::Type;
+
+/// Print IR as textual LLVM IR, such as `%3 = add i64 %1, %2`.
+/// Each write to a virtual register defines a new numbered value and every value is `i64`.
+/// Addresses are converted from and to `ptr` by `inttoptr` and `ptrtoint` where they are used.
+/// A register written on several paths, such as the result of `?:`, is kept in a stack slot
+/// as `clang -O0` does instead of being merged by `phi`.
+/// Virtual registers must not be allocated yet.
+pub fn emit_llvm(ir_generator: &IRGenerator) -> String {
+    let mut text = String::new();
+    for func in &ir_generator.funcs {
+        for (i, string) in func.strings.iter().enumerate() {
+            text += &format!(
+                "@{}.str{} = private constant [{} x i8] c\"{}\\00\"\n",
+                func.name,
+                i,
+                string.len() + 1,
+                escape_str(string)
+            );
+        }
+        for (symbol, init) in &func.statics {
+            text += &format!("@{} = internal global i64 {}\n", symbol, init);
+        }
+    }
+    // Functions called but not defined here, mapped onto their declarations.
+    let mut declarations = BTreeMap::new();
+    for func in &ir_generator.funcs {
+        let emitter = FuncEmitter::new(func);
+        text += &emitter.emit(&mut declarations);
+    }
+    for func in &ir_generator.funcs {
+        declarations.remove(&func.name);
+    }
+    for declaration in declarations.values() {
+        text += declaration;
+        text += "\n";
+    }
+    text
+}
+
+/// State of printing a function.
+struct FuncEmitter<'a> {
+    func: &'a Function,
+    lines: Vec<String>,
+    // Mapping a virtual register onto the value it holds, either a name or a constant.
+    values: HashMap<usize, String>,
+    // Values made by `ptrtoint` mapped onto the `ptr` they were made from.
+    pointers: HashMap<String, String>,
+    // Virtual registers defined more than once, which are kept in stack slots.
+    slots: BTreeSet<usize>,
+    // Number of the last defined value.
+    value_count: usize,
+    // Number of the last block which follows a conditional branch.
+    block_count: usize,
+    // Arguments stored for the next call, in the order of their index.
+    args: BTreeMap<usize, String>,
+    // Flag of the last arithmetic with an overflow check.
+    overflow: Option<String>,
+    // Whether the current block has ended with a branch or `ret`.
+    is_terminated: bool,
+    // Whether any branch jumps to the common return block.
+    has_return_block: bool,
+    // Called functions mapped onto their declarations such as `declare i64 @f(i64)`.
+    callees: BTreeMap<String, String>,
+}
+
+impl<'a> FuncEmitter<'a> {
+    fn new(func: &'a Function) -> Self {
+        FuncEmitter {
+            func,
+            lines: Vec::new(),
+            values: HashMap::new(),
+            pointers: HashMap::new(),
+            slots: BTreeSet::new(),
+            value_count: 0,
+            block_count: 0,
+            args: BTreeMap::new(),
+            overflow: None,
+            is_terminated: false,
+            has_return_block: false,
+            callees: BTreeMap::new(),
+        }
+    }
+
+    fn emit(mut self, declarations: &mut BTreeMap<String, String>) -> String {
+        let func = self.func;
+        let param_count = func
+            .ir_vec
+            .iter()
+            .filter(|ir| ir.op == IROp::LoadParam)
+            .count();
+        let params: Vec<_> = (0..param_count).map(|i| format!("i64 %arg{}", i)).collect();
+        let mut text = format!("define i64 @{}({}) {{\n", func.name, params.join(", "));
+        // Basic blocks where each virtual register is defined.
+        let mut def_blocks: HashMap<usize, BTreeSet<usize>> = HashMap::new();
+        let mut block = 0;
+        for ir in &func.ir_vec {
+            match ir.op {
+                IROp::Imm
+                | IROp::Mov
+                | IROp::BpOffset
+                | IROp::StrAddr
+                | IROp::GlobalAddr
+                | IROp::Load
+                | IROp::FuncCall(_)
+                | IROp::TailCall(_) => {
+                    def_blocks.entry(ir.lhs.unwrap()).or_default().insert(block);
+                }
+                IROp::Label(_)
+                | IROp::Jmp(_)
+                | IROp::Cond
+                | IROp::Branch(_)
+                | IROp::BranchOverflow(_) => block += 1,
+                _ => (),
+            }
+        }
+        self.slots = def_blocks
+            .into_iter()
+            .filter(|(_, blocks)| blocks.len() > 1)
+            .map(|(reg, _)| reg)
+            .collect();
+        for reg in self.slots.clone() {
+            self.push(format!("%reg{} = alloca i64", reg));
+        }
+        // Local variables are addressed downward from the top of the frame like `rbp`.
+        if func.stack_size > 0 {
+            self.push(format!("%frame.base = alloca i8, i64 {}", func.stack_size));
+            self.push(format!(
+                "%frame = getelementptr i8, ptr %frame.base, i64 {}",
+                func.stack_size
+            ));
+        }
+        for (i, ir) in func.ir_vec.iter().enumerate() {
+            let next = func.ir_vec.get(i + 1);
+            self.emit_ir(ir, next);
+        }
+        if self.has_return_block {
+            self.push_label(&format!("return_{}", func.name));
+            self.push("ret i64 undef".to_string());
+        }
+        declarations.append(&mut self.callees);
+        for line in &self.lines {
+            text += line;
+            text += "\n";
+        }
+        text += "}\n";
+        text
+    }
+
+    fn emit_ir(&mut self, ir: &IR, next: Option<&IR>) {
+        // Code after a branch or `ret` is unreachable until the next label.
+        if self.is_terminated && !matches!(ir.op, IROp::Label(_)) {
+            return;
+        }
+        let lhs = ir.lhs.unwrap_or_default();
+        let rhs = ir.rhs.unwrap_or_default();
+        match &ir.op {
+            IROp::Imm => self.set(lhs, rhs.to_string()),
+            IROp::Mov => {
+                let value = self.value(rhs);
+                self.set(lhs, value);
+            }
+            IROp::Add | IROp::Sub | IROp::Mul
+                if next.is_some_and(|ir| matches!(ir.op, IROp::BranchOverflow(_))) =>
+            {
+                let intrinsic = match ir.op {
+                    IROp::Add => "sadd",
+                    IROp::Sub => "ssub",
+                    _ => "smul",
+                };
+                let name = format!("llvm.{}.with.overflow.i64", intrinsic);
+                self.declare(&name, "{i64, i1}", "i64, i64");
+                let (a, b) = (self.value(lhs), self.value(rhs));
+                let pair = format!("call {{i64, i1}} @{}(i64 {}, i64 {})", name, a, b);
+                let pair = self.define(pair);
+                self.assign(lhs, format!("extractvalue {{i64, i1}} {}, 0", pair));
+                self.overflow = Some(self.define(format!("extractvalue {{i64, i1}} {}, 1", pair)));
+            }
+            IROp::Add
+            | IROp::Sub
+            | IROp::Mul
+            | IROp::Div
+            | IROp::Rem
+            | IROp::BitAnd
+            | IROp::BitOr
+            | IROp::BitXor
+            | IROp::Shl
+            | IROp::Shr => {
+                let instruction = match ir.op {
+                    IROp::Add => "add",
+                    IROp::Sub => "sub",
+                    IROp::Mul => "mul",
+                    IROp::Div => "sdiv",
+                    IROp::Rem => "srem",
+                    IROp::BitAnd => "and",
+                    IROp::BitOr => "or",
+                    IROp::BitXor => "xor",
+                    IROp::Shl => "shl",
+                    _ => "ashr",
+                };
+                let (a, b) = (self.value(lhs), self.value(rhs));
+                self.assign(lhs, format!("{} i64 {}, {}", instruction, a, b));
+            }
+            IROp::Eq | IROp::Ne | IROp::Lt | IROp::Le | IROp::Gt | IROp::Ge => {
+                let predicate = match ir.op {
+                    IROp::Eq => "eq",
+                    IROp::Ne => "ne",
+                    IROp::Lt => "slt",
+                    IROp::Le => "sle",
+                    IROp::Gt => "sgt",
+                    _ => "sge",
+                };
+                let (a, b) = (self.value(lhs), self.value(rhs));
+                let flag = self.define(format!("icmp {} i64 {}, {}", predicate, a, b));
+                self.assign(lhs, format!("zext i1 {} to i64", flag));
+            }
+            IROp::Min | IROp::Max => {
+                let predicate = match ir.op {
+                    IROp::Min => "sgt",
+                    _ => "slt",
+                };
+                let (a, b) = (self.value(lhs), self.value(rhs));
+                let flag = self.define(format!("icmp {} i64 {}, {}", predicate, a, b));
+                self.assign(lhs, format!("select i1 {}, i64 {}, i64 {}", flag, b, a));
+            }
+            IROp::Neg => {
+                let value = self.value(lhs);
+                self.assign(lhs, format!("sub i64 0, {}", value));
+            }
+            IROp::Cast(ty) | IROp::Zext(ty) => {
+                if let Type::U8 = ty {
+                    let value = self.value(lhs);
+                    let narrow = self.define(format!("trunc i64 {} to i8", value));
+                    self.assign(lhs, format!("zext i8 {} to i64", narrow));
+                }
+            }
+            IROp::BpOffset => {
+                let addr = self.define(format!("getelementptr i8, ptr %frame, i64 -{}", rhs));
+                self.assign_pointer(lhs, addr);
+            }
+            IROp::StrAddr => {
+                let symbol = format!("@{}.str{}", self.func.name, rhs);
+                self.assign_pointer(lhs, symbol);
+            }
+            IROp::GlobalAddr => {
+                let symbol = format!("@{}", self.func.statics[rhs].0);
+                self.assign_pointer(lhs, symbol);
+            }
+            IROp::Load => {
+                let addr = self.address(rhs);
+                if ir.size == 1 {
+                    let byte = self.define(format!("load i8, ptr {}", addr));
+                    self.assign(lhs, format!("zext i8 {} to i64", byte));
+                } else {
+                    self.assign(lhs, format!("load i64, ptr {}", addr));
+                }
+            }
+            IROp::Store => {
+                let addr = self.address(lhs);
+                let value = self.value(rhs);
+                if ir.size == 1 {
+                    let byte = self.define(format!("trunc i64 {} to i8", value));
+                    self.push(format!("store i8 {}, ptr {}", byte, addr));
+                } else {
+                    self.push(format!("store i64 {}, ptr {}", value, addr));
+                }
+            }
+            IROp::LoadParam => {
+                let addr = self.address(rhs);
+                self.push(format!("store i64 %arg{}, ptr {}", lhs, addr));
+            }
+            IROp::StoreArg => {
+                let value = self.value(rhs);
+                self.args.insert(lhs, value);
+            }
+            IROp::FuncCall(name) | IROp::TailCall(name) => {
+                let args: Vec<_> = self
+                    .args
+                    .values()
+                    .map(|arg| format!("i64 {}", arg))
+                    .collect();
+                self.args.clear();
+                let tail = match ir.op {
+                    IROp::TailCall(_) => "tail ",
+                    _ => "",
+                };
+                // `rhs` is given for a call of a variadic function.
+                let (signature, params) = match ir.rhs {
+                    Some(_) => ("(...) ", "...".to_string()),
+                    None => ("", vec!["i64"; args.len()].join(", ")),
+                };
+                self.declare(name, "i64", &params);
+                let expr = format!(
+                    "{}call i64 {}@{}({})",
+                    tail,
+                    signature,
+                    name,
+                    args.join(", ")
+                );
+                self.assign(lhs, expr);
+            }
+            IROp::Cond => {
+                let label = format!("{}..else{}", self.func.name, rhs);
+                self.branch_unless_zero(lhs, None, Some(label));
+            }
+            IROp::Branch(label) => {
+                self.branch_unless_zero(lhs, Some(label.clone()), None);
+            }
+            IROp::BranchOverflow(label) => {
+                let flag = self.overflow.take().unwrap_or_else(|| "false".to_string());
+                self.branch(flag, Some(label.clone()), None);
+            }
+            IROp::Label(name) => {
+                let label = match ir.lhs {
+                    Some(label_number) => format!("{}..{}{}", self.func.name, name, label_number),
+                    None => name.clone(),
+                };
+                self.push_label(&label);
+            }
+            IROp::Jmp(label) => {
+                if *label == format!("return_{}", self.func.name) {
+                    self.has_return_block = true;
+                }
+                self.push(format!("br label %{}", label));
+                self.is_terminated = true;
+            }
+            IROp::Return => {
+                let value = self.value(lhs);
+                self.push(format!("ret i64 {}", value));
+                self.is_terminated = true;
+            }
+            IROp::Trap => {
+                self.declare("llvm.trap", "void", "");
+                self.push("call void @llvm.trap()".to_string());
+                self.push("unreachable".to_string());
+                self.is_terminated = true;
+            }
+            IROp::Kill => (),
+        }
+    }
+
+    /// Value held by a virtual register, which is loaded if it is in a stack slot.
+    fn value(&mut self, reg: usize) -> String {
+        if self.slots.contains(&reg) {
+            return self.define(format!("load i64, ptr %reg{}", reg));
+        }
+        self.values
+            .get(&reg)
+            .cloned()
+            .unwrap_or_else(|| format!("%undef.{}", reg))
+    }
+
+    /// Record the declaration of a called function, dropped later if it is defined here.
+    fn declare(&mut self, name: &str, ret_type: &str, params: &str) {
+        let declaration = format!("declare {} @{}({})", ret_type, name, params);
+        self.callees.insert(name.to_string(), declaration);
+    }
+
+    /// Let a virtual register hold `pointer` converted to `i64`.
+    fn assign_pointer(&mut self, reg: usize, pointer: String) {
+        let name = self.define(format!("ptrtoint ptr {} to i64", pointer));
+        self.pointers.insert(name.clone(), pointer);
+        self.set(reg, name);
+    }
+
+    /// Address held by a virtual register as `ptr`.
+    /// The original `ptr` is reused if the value was made from it.
+    fn address(&mut self, reg: usize) -> String {
+        let value = self.value(reg);
+        match self.pointers.get(&value) {
+            Some(pointer) => pointer.clone(),
+            None => self.define(format!("inttoptr i64 {} to ptr", value)),
+        }
+    }
+
+    /// Let a virtual register hold a value, which is stored if it is in a stack slot.
+    fn set(&mut self, reg: usize, value: String) {
+        if self.slots.contains(&reg) {
+            self.push(format!("store i64 {}, ptr %reg{}", value, reg));
+        } else {
+            self.values.insert(reg, value);
+        }
+    }
+
+    /// Define a new value by `expr` and return its name.
+    fn define(&mut self, expr: String) -> String {
+        self.value_count += 1;
+        let name = format!("%{}", self.value_count);
+        self.push(format!("{} = {}", name, expr));
+        name
+    }
+
+    /// Define a new value by `expr` and let `reg` hold it.
+    fn assign(&mut self, reg: usize, expr: String) {
+        let name = self.define(expr);
+        self.set(reg, name);
+    }
+
+    /// Compare a register with zero and branch on it.
+    fn branch_unless_zero(&mut self, reg: usize, then: Option<String>, els: Option<String>) {
+        let value = self.value(reg);
+        let flag = self.define(format!("icmp ne i64 {}, 0", value));
+        self.branch(flag, then, els);
+    }
+
+    /// Branch on `flag`. A missing target is a new block which follows the branch.
+    fn branch(&mut self, flag: String, then: Option<String>, els: Option<String>) {
+        self.block_count += 1;
+        let next = format!("next{}", self.block_count);
+        let then = then.unwrap_or_else(|| next.clone());
+        let els = els.unwrap_or_else(|| next.clone());
+        self.push(format!("br i1 {}, label %{}, label %{}", flag, then, els));
+        self.is_terminated = true;
+        self.push_label(&next);
+    }
+
+    /// Start a block. The previous block falls through to it by an explicit branch.
+    fn push_label(&mut self, label: &str) {
+        if !self.is_terminated {
+            self.push(format!("br label %{}", label));
+        }
+        self.lines.push(format!("{}:", label));
+        self.is_terminated = false;
+    }
+
+    fn push(&mut self, line: String) {
+        self.lines.push(format!("  {}", line));
+    }
+}
+
+/// Escape a string for a constant of `c"..."`, where bytes are written as `\XX`.
+fn escape_str(string: &str) -> String {
+    string
+        .bytes()
+        .map(|b| match b {
+            b' '..=b'~' if b != b'"' && b != b'\\' => (b as char).to_string(),
+            _ => format!("\\{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::code::llvm::emit_llvm;
+
+    #[test]
+    fn test_emit_add() {
+        let ir_generator = crate::compile("func main(a: u64, b: u64) { return a + b; }").unwrap();
+        let text = emit_llvm(&ir_generator);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "define i64 @main(i64 %arg0, i64 %arg1) {",
+                "  %frame.base = alloca i8, i64 32",
+                "  %frame = getelementptr i8, ptr %frame.base, i64 32",
+                "  %1 = getelementptr i8, ptr %frame, i64 -8",
+                "  %2 = ptrtoint ptr %1 to i64",
+                "  store i64 %arg0, ptr %1",
+                "  %3 = getelementptr i8, ptr %frame, i64 -16",
+                "  %4 = ptrtoint ptr %3 to i64",
+                "  store i64 %arg1, ptr %3",
+                "  %5 = getelementptr i8, ptr %frame, i64 -8",
+                "  %6 = ptrtoint ptr %5 to i64",
+                "  %7 = load i64, ptr %5",
+                "  %8 = getelementptr i8, ptr %frame, i64 -16",
+                "  %9 = ptrtoint ptr %8 to i64",
+                "  %10 = load i64, ptr %8",
+                "  %11 = add i64 %7, %10",
+                "  ret i64 %11",
+                "}",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_emit_branch() {
+        let source = "func main() { let a: u64 = 1; if (a) { a = 2; } return a; }";
+        let ir_generator = crate::compile_with(
+            source,
+            crate::opt::OptLevel::O0,
+            crate::ir::OverflowMode::default(),
+        )
+        .unwrap();
+        let text = emit_llvm(&ir_generator);
+        // `Cond` falls through to a new block and jumps to `else` on zero.
+        assert!(text.contains("br i1 %6, label %next1, label %main..else1\nnext1:\n"));
+        // The end of `then` falls through to `else` by an explicit branch.
+        assert!(text.contains("br label %main..else1\nmain..else1:\n"));
+    }
+
+    const POINTERS: &str = "
+        extern func printf(fmt: u8*, ...);
+        extern func malloc(n: u64);
+        func main() {
+            let p: u64* = malloc(16);
+            *p = 3;
+            printf(\"%d\\n\", *p);
+            if (*p > 10) { __builtin_trap(); }
+            let b: u8* = malloc(1);
+            *b = 4;
+            return *p + *b;
+        }";
+
+    #[test]
+    fn test_emit_pointer() {
+        let ir_generator = crate::compile(POINTERS).unwrap();
+        let text = emit_llvm(&ir_generator);
+        // Addresses are held as `i64` and converted to `ptr` where they are dereferenced.
+        assert!(text.contains("  %7 = inttoptr i64 %6 to ptr\n  store i64 3, ptr %7\n"));
+        assert!(text.contains("ptrtoint ptr @main.str0 to i64"));
+        // A byte is truncated before it is stored, and zero-extended after it is loaded.
+        assert!(text.contains("  %32 = trunc i64 %30 to i8\n  store i8 %32, ptr %31\n"));
+        assert!(text.contains("  %42 = load i8, ptr %41\n  %43 = zext i8 %42 to i64\n"));
+        assert!(text.ends_with(
+            "declare void @llvm.trap()\ndeclare i64 @malloc(i64)\ndeclare i64 @printf(...)\n"
+        ));
+    }
+
+    /// Assemble the emitted IR with `llvm-as`. Skipped if it is not available.
+    #[test]
+    fn test_llvm_as() {
+        use std::io::Write;
+        use std::process::{Command, Stdio};
+
+        let ir_generator = crate::compile_with(
+            POINTERS,
+            crate::opt::OptLevel::O2,
+            crate::ir::OverflowMode::Checked,
+        )
+        .unwrap();
+        let text = emit_llvm(&ir_generator);
+        // LLVM before 15 needs the flag to accept `ptr`, which later versions reject.
+        let statuses: Vec<_> = [&["-opaque-pointers"][..], &[]]
+            .iter()
+            .filter_map(|flags| {
+                let mut child = Command::new("llvm-as")
+                    .args(*flags)
+                    .args(["-o", "/dev/null"])
+                    .stdin(Stdio::piped())
+                    .stderr(Stdio::null())
+                    .spawn()
+                    .ok()?;
+                child.stdin.take()?.write_all(text.as_bytes()).ok()?;
+                child.wait().ok()
+            })
+            .collect();
+        if statuses.is_empty() {
+            eprintln!("llvm-as is not available");
+            return;
+        }
+        assert!(statuses.iter().any(|status| status.success()), "{}", text);
+    }
+}
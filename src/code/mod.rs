@@ -1 +1,12 @@
 pub mod code_gen;
+pub mod llvm;
+
+/// Syntax of generated x86-64 assembly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AsmSyntax {
+    /// Destination first without register prefixes, as in `mov rbx, 1`.
+    #[default]
+    Intel,
+    /// Source first with `%` and `$` prefixes, as in `mov $1, %rbx`.
+    Att,
+}
@@ -0,0 +1,193 @@
+use std::fmt;
+
+use crate::parse::ParseError;
+use crate::preprocess::{SourceMap, MAIN_FILE};
+use crate::{CompileError, Loc, Warning};
+
+/// How serious a diagnostic is. Errors come first among diagnostics at the same location.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Error or warning to be reported to users.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    // `None` for a diagnostic on the whole program or on synthesized code,
+    // reported after located ones.
+    pub loc: Option<Loc>,
+    // Message including its location.
+    pub message: String,
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            loc: error.loc().filter(|loc| !loc.is_dummy()),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<&CompileError> for Diagnostic {
+    fn from(error: &CompileError) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            loc: error.loc().filter(|loc| !loc.is_dummy()),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<&Warning> for Diagnostic {
+    fn from(warning: &Warning) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            loc: Some(warning.loc()).filter(|loc| !loc.is_dummy()),
+            message: warning.to_string(),
+        }
+    }
+}
+
+impl Diagnostic {
+    /// Message without its location.
+    pub fn text(&self) -> &str {
+        match self.loc {
+            Some(loc) => self
+                .message
+                .strip_prefix(&format!("{}: ", loc))
+                .unwrap_or(&self.message),
+            None => &self.message,
+        }
+    }
+
+    /// Message prefixed by the file and the offset in it where the diagnostic is,
+    /// such as `a.pr:38: ...`, since its own location is in the source after
+    /// `#include` is expanded. The main file is named `main_path`.
+    pub fn located(&self, source_map: &SourceMap, main_path: &str) -> String {
+        match self.loc {
+            Some(loc) => {
+                let (file, offset) = source_map.locate(loc.0);
+                let file = if file == MAIN_FILE { main_path } else { file };
+                format!("{}:{}: {}", file, offset, self.text())
+            }
+            None => self.message.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Errors and warnings collected from passes, reported in source order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diagnostics {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn push(&mut self, diagnostic: impl Into<Diagnostic>) {
+        self.diagnostics.push(diagnostic.into());
+    }
+
+    /// Push an error stopping compilation, each of parse errors separately.
+    pub fn push_error(&mut self, error: &CompileError) {
+        match error {
+            CompileError::Parse(errors) => errors.iter().for_each(|error| self.push(error)),
+            error => self.push(error),
+        }
+    }
+
+    /// Whether any error is collected.
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+
+    /// Diagnostics sorted by the start of their location, then by severity.
+    /// Diagnostics which tie on both keep the order they are collected in.
+    pub fn sorted(&self) -> Vec<&Diagnostic> {
+        let mut diagnostics: Vec<&Diagnostic> = self.diagnostics.iter().collect();
+        diagnostics.sort_by_key(|diagnostic| {
+            (
+                diagnostic.loc.is_none(),
+                diagnostic.loc.map(|loc| loc.0),
+                diagnostic.severity,
+            )
+        });
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostics::{Diagnostic, Diagnostics, Severity};
+    use crate::parse::ParseError;
+    use crate::preprocess::preprocess_with_map;
+    use crate::token::{Token, TokenKind};
+    use crate::{Loc, Warning};
+
+    #[test]
+    fn test_sorted() {
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.push(&ParseError::TooManyErrors);
+        diagnostics.push(&Warning::ConstantOverflow(Loc(10, 15)));
+        diagnostics.push(&ParseError::NoSemicolon(token!(RBrace, 10, 11)));
+        diagnostics.push(&ParseError::Eof(Loc(20, 20)));
+        diagnostics.push(&Warning::ConstantOverflow(Loc(2, 5)));
+        assert!(diagnostics.has_errors());
+
+        let sorted: Vec<(Severity, Option<Loc>)> = diagnostics
+            .sorted()
+            .iter()
+            .map(|diagnostic| (diagnostic.severity, diagnostic.loc))
+            .collect();
+        assert_eq!(
+            sorted,
+            [
+                (Severity::Warning, Some(Loc(2, 5))),
+                (Severity::Error, Some(Loc(10, 11))),
+                (Severity::Warning, Some(Loc(10, 15))),
+                (Severity::Error, Some(Loc(20, 20))),
+                (Severity::Error, None),
+            ]
+        );
+        assert_eq!(
+            diagnostics.sorted()[0].to_string(),
+            "2-5: warning: Integer overflow in constant expression"
+        );
+    }
+
+    #[test]
+    fn test_located() {
+        let resolve = |name: &str| (name == "lib.pr").then(|| "func f() { x }\n".to_string());
+        let (source, source_map) =
+            preprocess_with_map("#include \"lib.pr\"\nfunc main() { 1 }\n", resolve).unwrap();
+        let in_lib = source.find('x').unwrap();
+        let in_main = source.find('1').unwrap();
+        let error = Diagnostic::from(&ParseError::Eof(Loc(in_lib, in_lib + 1)));
+        assert_eq!(error.text(), "Unexpected end of file");
+        assert_eq!(
+            error.located(&source_map, "a.pr"),
+            "lib.pr:11: Unexpected end of file"
+        );
+        let warning = Diagnostic::from(&Warning::ConstantOverflow(Loc(in_main, in_main + 1)));
+        assert_eq!(
+            warning.located(&source_map, "a.pr"),
+            "a.pr:32: warning: Integer overflow in constant expression"
+        );
+        let error = Diagnostic::from(&ParseError::TooManyErrors);
+        assert_eq!(error.located(&source_map, "a.pr"), error.message);
+    }
+}
@@ -4,10 +4,7 @@ use crate::token::Token;
 
 pub fn dump_tokens(tokens: &[Token]) {
     eprintln!("------DUMP TOKEN------");
-    for token in tokens {
-        eprintln!("{:?}, {:?}, {:?}", token.value, token.loc.0, token.loc.1);
-    }
-    eprintln!();
+    eprintln!("{}", crate::format_tokens(tokens));
 }
 
 pub fn dump_asts(asts: &[Ast]) {
@@ -20,11 +17,5 @@ pub fn dump_asts(asts: &[Ast]) {
 
 pub fn dump_ir(ir_generator: &IRGenerator) {
     eprintln!("------DUMP IR------");
-    for func in &ir_generator.funcs {
-        eprintln!("{}", func.name);
-        for ir in &func.ir_vec {
-            eprintln!("({:?}, {:?}, {:?})", ir.op, ir.lhs, ir.rhs);
-        }
-    }
-    eprintln!();
+    eprintln!("{}", crate::format_ir(ir_generator));
 }
@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::parse::AstKind::*;
+use crate::parse::{ArithError, Ast, BinOpKind, Type, UniOpKind};
+use crate::Loc;
+
+/// Data type that represents an error found while evaluating ASTs.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum EvalError {
+    UndefinedVariable {
+        name: String,
+        loc: Loc,
+    },
+    Arith(ArithError, Loc),
+    /// Construct which the interpreter cannot run yet.
+    Unsupported(Loc),
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::UndefinedVariable { name, loc } => {
+                write!(f, "{}: Undefined variable '{}'", loc, name)
+            }
+            EvalError::Arith(error, loc) => write!(f, "{}: {}", loc, error),
+            EvalError::Unsupported(loc) => write!(f, "{}: Cannot be evaluated", loc),
+        }
+    }
+}
+
+/// How control leaves a statement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flow {
+    Next,
+    Return(i64),
+    Break,
+    Continue,
+}
+
+/// Tree-walking interpreter, which runs statements without the backend.
+/// Variables at the top level are kept between calls of `eval` as in a REPL.
+#[derive(Debug, Clone, Default)]
+pub struct Interpreter {
+    // Mapping variable name to its type and value for each scope.
+    // The innermost one places last.
+    scopes: Vec<HashMap<String, (Type, i64)>>,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter::default()
+    }
+
+    /// Run statements in order and return the value of `return` if any.
+    pub fn eval(&mut self, asts: &[Ast]) -> Result<Option<i64>, EvalError> {
+        for ast in asts {
+            match self.exec(ast)? {
+                Flow::Next => (),
+                Flow::Return(value) => return Ok(Some(value)),
+                // `break` or `continue` outside of a loop.
+                Flow::Break | Flow::Continue => return Err(EvalError::Unsupported(ast.loc)),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Run statements in order until one of them leaves the block.
+    fn exec_block(&mut self, stmts: &[Ast]) -> Result<Flow, EvalError> {
+        for stmt in stmts {
+            let flow = self.exec(stmt)?;
+            if flow != Flow::Next {
+                return Ok(flow);
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    /// Run a statement. A nonzero condition counts as true.
+    fn exec(&mut self, ast: &Ast) -> Result<Flow, EvalError> {
+        match &ast.value {
+            Decl { lhs, rhs, ty, .. } => {
+                // The interpreter has no memory to place an array in.
+                let rhs = rhs.as_ref().ok_or(EvalError::Unsupported(ast.loc))?;
+                let value = self.eval_expr(rhs)?;
+                let name = ident_val!(&lhs.value);
+                self.declare(name, ty.clone(), truncate(ty, value));
+                Ok(Flow::Next)
+            }
+            Return { expr } => self.eval_expr(expr).map(Flow::Return),
+            CompStmt { stmts } => {
+                self.scopes.push(HashMap::new());
+                let flow = self.exec_block(stmts);
+                self.scopes.pop();
+                flow
+            }
+            If { cond, then, els } => {
+                if self.eval_expr(cond)? != 0 {
+                    self.exec(then)
+                } else if let Some(els) = els {
+                    self.exec(els)
+                } else {
+                    Ok(Flow::Next)
+                }
+            }
+            // `els` runs when the loop ends without `break`.
+            While { cond, body, els } => loop {
+                if self.eval_expr(cond)? == 0 {
+                    return match els {
+                        Some(els) => self.exec(els),
+                        None => Ok(Flow::Next),
+                    };
+                }
+                match self.exec(body)? {
+                    Flow::Next | Flow::Continue => (),
+                    Flow::Break => return Ok(Flow::Next),
+                    flow @ Flow::Return(_) => return Ok(flow),
+                }
+            },
+            Break => Ok(Flow::Break),
+            Continue => Ok(Flow::Continue),
+            _ => self.eval_expr(ast).map(|_| Flow::Next),
+        }
+    }
+
+    /// Value of an expression.
+    fn eval_expr(&mut self, ast: &Ast) -> Result<i64, EvalError> {
+        match &ast.value {
+            Num(n) => Ok(*n as i64),
+            Variable(name) => self.lookup(name, ast.loc).map(|(_, value)| value),
+            Cast { ty, expr } => {
+                let value = self.eval_expr(expr)?;
+                Ok(truncate(ty, value))
+            }
+            // The interpreter has no memory to point into.
+            UniOp {
+                op: UniOpKind::Deref,
+                ..
+            } => Err(EvalError::Unsupported(ast.loc)),
+            UniOp { op, node } => {
+                let value = self.eval_expr(node)?;
+                op.eval(value)
+                    .map_err(|error| EvalError::Arith(error, ast.loc))
+            }
+            // `rhs` is evaluated only if `lhs` does not decide the result.
+            BinOp {
+                op: op @ (BinOpKind::LogAnd | BinOpKind::LogOr),
+                lhs,
+                rhs,
+            } => {
+                let lhs = self.eval_expr(lhs)? != 0;
+                if lhs == (*op == BinOpKind::LogOr) {
+                    return Ok(lhs as i64);
+                }
+                Ok((self.eval_expr(rhs)? != 0) as i64)
+            }
+            BinOp { op, lhs, rhs } => {
+                let lhs = self.eval_expr(lhs)?;
+                let rhs = self.eval_expr(rhs)?;
+                op.eval(lhs, rhs)
+                    .map_err(|error| EvalError::Arith(error, ast.loc))
+            }
+            Ternary { cond, then, els } => {
+                if self.eval_expr(cond)? != 0 {
+                    self.eval_expr(then)
+                } else {
+                    self.eval_expr(els)
+                }
+            }
+            Assignment { lhs, rhs } => {
+                let value = self.eval_expr(rhs)?;
+                self.store(lhs, value)
+            }
+            PostIncDec { op, lhs } => {
+                let value = self.eval_expr(lhs)?;
+                let updated = op
+                    .eval(value, 1)
+                    .map_err(|error| EvalError::Arith(error, ast.loc))?;
+                self.store(lhs, updated)?;
+                Ok(value)
+            }
+            _ => Err(EvalError::Unsupported(ast.loc)),
+        }
+    }
+
+    /// Declare a variable in the innermost scope.
+    fn declare(&mut self, name: String, ty: Type, value: i64) {
+        if self.scopes.is_empty() {
+            self.scopes.push(HashMap::new());
+        }
+        self.scopes.last_mut().unwrap().insert(name, (ty, value));
+    }
+
+    /// Store a value into the visible variable and return the value truncated to its type.
+    fn store(&mut self, lhs: &Ast, value: i64) -> Result<i64, EvalError> {
+        let name = match &lhs.value {
+            Variable(name) => name.clone(),
+            _ => return Err(EvalError::Unsupported(lhs.loc)),
+        };
+        let var = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(&name))
+            .ok_or_else(|| EvalError::UndefinedVariable {
+                name: name.clone(),
+                loc: lhs.loc,
+            })?;
+        var.1 = truncate(&var.0, value);
+        Ok(var.1)
+    }
+
+    fn lookup(&self, name: &str, loc: Loc) -> Result<(Type, i64), EvalError> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+            .ok_or_else(|| EvalError::UndefinedVariable {
+                name: name.to_string(),
+                loc,
+            })
+    }
+}
+
+fn truncate(ty: &Type, value: i64) -> i64 {
+    ty.truncate(value as usize) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::{EvalError, Interpreter};
+    use crate::parse::parser::Parser;
+    use crate::parse::ArithError;
+    use crate::token::lexer::Lexer;
+    use crate::Loc;
+
+    fn eval(interpreter: &mut Interpreter, source_code: &str) -> Result<Option<i64>, EvalError> {
+        let mut lexer = Lexer::new(source_code);
+        let tokens = lexer.lex().unwrap();
+        let mut parser = Parser::new(tokens).allow_trailing_expr();
+        let asts = parser.parse().unwrap();
+        interpreter.eval(&asts)
+    }
+
+    #[test]
+    fn test_trailing_expr() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(eval(&mut interpreter, "1 + 2"), Ok(Some(3)));
+        assert_eq!(eval(&mut interpreter, "let a: u8 = 255; a += 2;"), Ok(None));
+        // Variables live across inputs.
+        assert_eq!(eval(&mut interpreter, "a * 10"), Ok(Some(10)));
+        assert_eq!(
+            eval(&mut interpreter, "a / 0"),
+            Err(EvalError::Arith(ArithError::DivByZero, Loc(0, 5)))
+        );
+        assert_eq!(
+            eval(&mut interpreter, "b"),
+            Err(EvalError::UndefinedVariable {
+                name: "b".to_string(),
+                loc: Loc(0, 1)
+            })
+        );
+    }
+
+    #[test]
+    fn test_if_else() {
+        let mut interpreter = Interpreter::new();
+        let source_code =
+            "let a: u64 = 3; if (a > 5) { a = 1; } else if (a > 2) { a = 2; } else { a = 3; } a";
+        assert_eq!(eval(&mut interpreter, source_code), Ok(Some(2)));
+        assert_eq!(
+            eval(&mut interpreter, "if (a) { return 10; } return 20;"),
+            Ok(Some(10))
+        );
+    }
+
+    #[test]
+    fn test_while() {
+        let mut interpreter = Interpreter::new();
+        let source_code =
+            "let i: u64 = 0; let sum: u64 = 0; while (i < 5) { i += 1; sum += i; } sum";
+        assert_eq!(eval(&mut interpreter, source_code), Ok(Some(15)));
+        let source_code = "while (1) { i += 1; if (i == 8) { break; } } else { i = 0; } i";
+        assert_eq!(eval(&mut interpreter, source_code), Ok(Some(8)));
+    }
+
+    #[test]
+    fn test_consecutive_minus() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            eval(&mut interpreter, "let a: u64 = 3; a - -10"),
+            Ok(Some(13))
+        );
+        assert_eq!(eval(&mut interpreter, "- -10"), Ok(Some(10)));
+        assert_eq!(eval(&mut interpreter, "-(-10)"), Ok(Some(10)));
+    }
+
+    #[test]
+    fn test_scope() {
+        let mut interpreter = Interpreter::new();
+        let source_code = "let a: u64 = 1; { let a: u64 = 2; a = 3; } a";
+        assert_eq!(eval(&mut interpreter, source_code), Ok(Some(1)));
+        // An inner block assigns to the outer variable.
+        assert_eq!(eval(&mut interpreter, "{ a = 4; } a"), Ok(Some(4)));
+        assert!(matches!(
+            eval(&mut interpreter, "{ let b: u64 = 1; } b"),
+            Err(EvalError::UndefinedVariable { .. })
+        ));
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use crate::ir::gen_ir::{IROp, IR};
+
+/// Range of IR which is entered only at its first instruction and left only after its last one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    // Index of the first instruction in `ir_vec`.
+    pub start: usize,
+    // Index next to the last instruction in `ir_vec`.
+    pub end: usize,
+    // Indices of blocks which control may pass to after this one.
+    pub succs: Vec<usize>,
+    // Indices of blocks which control may come from.
+    pub preds: Vec<usize>,
+}
+
+/// Control-flow graph of a function. Blocks are in the order of `ir_vec`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+}
+
+/// Partition `ir` of a function into basic blocks at labels and jumps, and link them.
+/// A jump to a label outside of `ir`, such as the epilogue, leaves the function.
+pub fn build_cfg(ir: &[IR]) -> Cfg {
+    let mut blocks: Vec<BasicBlock> = Vec::new();
+    let mut start = 0;
+    for (i, inst) in ir.iter().enumerate() {
+        let starts_block = matches!(inst.op, IROp::Label(_)) && i > start;
+        if starts_block {
+            blocks.push(BasicBlock::new(start, i));
+            start = i;
+        }
+        if ends_block(&inst.op) {
+            blocks.push(BasicBlock::new(start, i + 1));
+            start = i + 1;
+        }
+    }
+    if start < ir.len() {
+        blocks.push(BasicBlock::new(start, ir.len()));
+    }
+
+    let labels: HashMap<String, usize> = blocks
+        .iter()
+        .enumerate()
+        .filter_map(|(index, block)| label_key(&ir[block.start]).map(|key| (key, index)))
+        .collect();
+    for index in 0..blocks.len() {
+        let last = &ir[blocks[index].end - 1];
+        let target = match &last.op {
+            IROp::Jmp(name) | IROp::Branch(name) | IROp::BranchOverflow(name) => {
+                labels.get(strip_func_name(name)).copied()
+            }
+            IROp::Cond => labels.get(&format!(".else{}", last.rhs.unwrap())).copied(),
+            _ => None,
+        };
+        let falls_through = !matches!(last.op, IROp::Jmp(_) | IROp::Trap);
+        let mut succs: Vec<usize> = target.into_iter().collect();
+        if falls_through && index + 1 < blocks.len() && !succs.contains(&(index + 1)) {
+            succs.push(index + 1);
+        }
+        for &succ in &succs {
+            blocks[succ].preds.push(index);
+        }
+        blocks[index].succs = succs;
+    }
+    Cfg { blocks }
+}
+
+impl BasicBlock {
+    fn new(start: usize, end: usize) -> Self {
+        BasicBlock {
+            start,
+            end,
+            succs: Vec::new(),
+            preds: Vec::new(),
+        }
+    }
+}
+
+impl Cfg {
+    /// Number of edges between blocks.
+    pub fn edge_count(&self) -> usize {
+        self.blocks.iter().map(|block| block.succs.len()).sum()
+    }
+
+    /// Index of the block which contains `ir_vec[index]`.
+    pub fn block_of(&self, index: usize) -> Option<usize> {
+        self.blocks
+            .iter()
+            .position(|block| block.start <= index && index < block.end)
+    }
+
+    /// Graphviz DOT of the graph, where each node lists the instructions of `ir` in the block.
+    pub fn to_dot(&self, name: &str, ir: &[IR]) -> String {
+        let mut dot = format!("digraph \"{}\" {{\n  node [shape=box];\n", name);
+        for (index, block) in self.blocks.iter().enumerate() {
+            let label: String = ir[block.start..block.end]
+                .iter()
+                .map(|inst| format!("({:?}, {:?}, {:?})\\l", inst.op, inst.lhs, inst.rhs))
+                .collect();
+            dot += &format!("  b{} [label=\"{}\"];\n", index, label.replace('"', "\\\""));
+        }
+        for (index, block) in self.blocks.iter().enumerate() {
+            for succ in &block.succs {
+                dot += &format!("  b{} -> b{};\n", index, succ);
+            }
+        }
+        dot + "}\n"
+    }
+}
+
+fn ends_block(op: &IROp) -> bool {
+    matches!(
+        op,
+        IROp::Jmp(_) | IROp::Cond | IROp::Branch(_) | IROp::BranchOverflow(_) | IROp::Trap
+    )
+}
+
+/// Name of a label as jumps in the same function refer to it, without the function name.
+fn label_key(ir: &IR) -> Option<String> {
+    match (&ir.op, ir.lhs) {
+        (IROp::Label(name), Some(label_number)) => Some(format!(".{}{}", name, label_number)),
+        (IROp::Label(name), None) => Some(strip_func_name(name).to_string()),
+        _ => None,
+    }
+}
+
+/// `end` of `main.end` for a user label, or `.begin1` of `main..begin1` for a generated one.
+/// Function names never contain `.`.
+fn strip_func_name(name: &str) -> &str {
+    name.split_once('.').map_or(name, |(_, label)| label)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ir::cfg::build_cfg;
+
+    #[test]
+    fn test_conditional_branch() {
+        // if (a) { b = 1; } else { b = 2; } return b;
+        let ir_vec = ir![
+            imm(1, 0),
+            cond(1, 1),
+            imm(2, 1),
+            jmp("main..end1"),
+            label("else", 1),
+            imm(2, 2),
+            label("end", 1),
+            ret(2),
+            jmp("return_main")
+        ];
+        let cfg = build_cfg(&ir_vec);
+        assert_eq!(cfg.blocks.len(), 4);
+        assert_eq!(cfg.edge_count(), 4);
+        assert_eq!(cfg.blocks[0].succs, [2, 1]);
+        assert_eq!(cfg.blocks[3].preds, [1, 2]);
+        // The epilogue is outside of the function.
+        assert!(cfg.blocks[3].succs.is_empty());
+        assert_eq!(cfg.block_of(5), Some(2));
+
+        let dot = cfg.to_dot("main", &ir_vec);
+        assert!(dot.starts_with("digraph \"main\" {\n"));
+        assert!(dot.contains("  b0 -> b2;\n  b0 -> b1;\n"));
+        assert!(dot.contains("(Label(\\\"end\\\"), Some(1), None)"));
+    }
+}
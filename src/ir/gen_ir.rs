@@ -1,30 +1,104 @@
 // use crate::code_gen;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 
+use crate::ir::{IRError, OverflowMode};
+use crate::parse::infer::TypeError;
 use crate::parse::AstKind::*;
-use crate::parse::{Ast, BinOpKind, UniOpKind};
+use crate::parse::{Ast, BinOpKind, Type, UniOpKind};
+use crate::target::Target;
+use crate::{Loc, ARG_REGISTER_COUNT};
 
 /// Kinds of IR operand.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum IROp {
     Imm,
+    Mov, // Copy a value from the register of `rhs` into the register of `lhs`.
     Add,
     Sub,
     Mul,
     Div,
-    Plus,
-    Minus,
-    BpOffset, // Load variable offset from $rbp.
-    FuncCall(String),
+    Rem,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr, // Arithmetic shift to right.
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Min,              // Signed minimum of two registers, selected without a branch.
+    Max,              // Signed maximum of two registers, selected without a branch.
+    Neg,              // Negate a value in the register in place.
+    Cast(Type),       // Truncate a value in the register into the type.
+    Zext(Type),       // Zero-extend a value of the type to the full register width.
+    BpOffset,         // Load variable offset from $rbp.
+    StrAddr,          // Load address of a string literal in read-only data.
+    GlobalAddr,       // Load address of a static variable in data.
+    FuncCall(String), // `rhs` is the number of vector registers used by a variadic call.
+    TailCall(String), // `FuncCall` whose result is returned right away. It may be lowered to `jmp`.
     Load,
     LoadParam,
     Store,
     StoreArg,
     Cond,
+    Branch(String),         // Jump to the label if the register is not zero.
+    BranchOverflow(String), // Jump to the label if the preceding arithmetic overflowed.
     Label(String),
     Jmp(String),
     Return,
     Kill,
+    Trap, // Abort the program.
+}
+
+impl IROp {
+    /// Name of the kind of the operation, without its operand.
+    pub fn name(&self) -> &'static str {
+        match self {
+            IROp::Imm => "Imm",
+            IROp::Mov => "Mov",
+            IROp::Add => "Add",
+            IROp::Sub => "Sub",
+            IROp::Mul => "Mul",
+            IROp::Div => "Div",
+            IROp::Rem => "Rem",
+            IROp::BitAnd => "BitAnd",
+            IROp::BitOr => "BitOr",
+            IROp::BitXor => "BitXor",
+            IROp::Shl => "Shl",
+            IROp::Shr => "Shr",
+            IROp::Eq => "Eq",
+            IROp::Ne => "Ne",
+            IROp::Lt => "Lt",
+            IROp::Le => "Le",
+            IROp::Gt => "Gt",
+            IROp::Ge => "Ge",
+            IROp::Min => "Min",
+            IROp::Max => "Max",
+            IROp::Neg => "Neg",
+            IROp::Cast(_) => "Cast",
+            IROp::Zext(_) => "Zext",
+            IROp::BpOffset => "BpOffset",
+            IROp::StrAddr => "StrAddr",
+            IROp::GlobalAddr => "GlobalAddr",
+            IROp::FuncCall(_) => "FuncCall",
+            IROp::TailCall(_) => "TailCall",
+            IROp::Load => "Load",
+            IROp::LoadParam => "LoadParam",
+            IROp::Store => "Store",
+            IROp::StoreArg => "StoreArg",
+            IROp::Cond => "Cond",
+            IROp::Branch(_) => "Branch",
+            IROp::BranchOverflow(_) => "BranchOverflow",
+            IROp::Label(_) => "Label",
+            IROp::Jmp(_) => "Jmp",
+            IROp::Return => "Return",
+            IROp::Kill => "Kill",
+            IROp::Trap => "Trap",
+        }
+    }
 }
 
 /// Inner representation.
@@ -36,19 +110,45 @@ pub struct IR {
     pub op: IROp,
     pub lhs: Option<usize>,
     pub rhs: Option<usize>,
+    // Width of arithmetic operands in bytes, which is narrower than 8 only for `int`,
+    // or width of memory accessed by `Load` and `Store` through a pointer.
+    pub size: usize,
 }
 
 impl IR {
     pub fn new(op: IROp, lhs: Option<usize>, rhs: Option<usize>) -> Self {
-        IR { op, lhs, rhs }
+        IR {
+            op,
+            lhs,
+            rhs,
+            size: 8,
+        }
+    }
+
+    /// Same IR operating on `size`-byte operands.
+    pub fn with_size(self, size: usize) -> Self {
+        IR { size, ..self }
     }
 }
 
+/// Local variable stored in a stack.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Var {
+    // Offset from rbp.
+    pub offset: usize,
+    // Type of the variable.
+    pub ty: Type,
+    // Whether the variable cannot be reassigned.
+    pub is_const: bool,
+    // Index into `statics` of a static variable, which lives in data instead of the stack.
+    pub global: Option<usize>,
+}
+
 /// Struct to contain pairs of variables and offset from rbp in the scope.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Env {
-    // Mapping variable name to offset.
-    pub local_var_map: HashMap<String, usize>,
+    // Mapping variable name to its offset and type.
+    pub local_var_map: HashMap<String, Var>,
     // Current maximum offset from rbp.
     pub current_var_offset: usize,
 }
@@ -62,16 +162,68 @@ impl Env {
     }
 
     /// Add a new pair of a variable and a offset value.
-    fn add(&mut self, var_name: String, offset: usize) {
+    fn add(&mut self, var_name: String, offset: usize, ty: Type, is_const: bool) {
         self.current_var_offset += offset;
-        self.local_var_map.insert(var_name, self.current_var_offset);
+        let var = Var {
+            offset: self.current_var_offset,
+            ty,
+            is_const,
+            global: None,
+        };
+        self.local_var_map.insert(var_name, var);
+    }
+
+    /// Add a static variable, which takes no stack space.
+    fn add_static(&mut self, var_name: String, ty: Type, is_const: bool, index: usize) {
+        let var = Var {
+            offset: 0,
+            ty,
+            is_const,
+            global: Some(index),
+        };
+        self.local_var_map.insert(var_name, var);
     }
 }
 
+/// Labels which `break` and `continue` jump to inside a loop or a switch.
+#[derive(Debug, Clone, PartialEq)]
+struct JumpTargets {
+    break_label: String,
+    // `None` for a switch, where `continue` refers to the enclosing loop.
+    continue_label: Option<String>,
+}
+
+/// Generates IR for a call to an intrinsic instead of calling a function.
+/// Receives arguments and the location of the call and returns a register holding the result.
+pub type Intrinsic = fn(&mut Function, &[Ast], Loc) -> Option<usize>;
+
+/// Intrinsics recognized by default, keyed by call name.
+fn default_intrinsics() -> HashMap<String, Intrinsic> {
+    let mut intrinsics: HashMap<String, Intrinsic> = HashMap::new();
+    intrinsics.insert("__builtin_trap".to_string(), Function::gen_ir_trap);
+    intrinsics.insert("min".to_string(), Function::gen_ir_min);
+    intrinsics.insert("max".to_string(), Function::gen_ir_max);
+    intrinsics
+}
+
 /// Entry point to generate IR.
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Clone)]
 pub struct IRGenerator {
     pub funcs: Vec<Function>,
+    intrinsics: HashMap<String, Intrinsic>,
+    target: Target,
+    overflow_mode: OverflowMode,
+}
+
+impl Default for IRGenerator {
+    fn default() -> Self {
+        IRGenerator {
+            funcs: Vec::new(),
+            intrinsics: default_intrinsics(),
+            target: Target::default(),
+            overflow_mode: OverflowMode::default(),
+        }
+    }
 }
 
 impl IRGenerator {
@@ -79,20 +231,89 @@ impl IRGenerator {
         Default::default()
     }
 
+    /// Generate IR checked against `target`.
+    pub fn with_target(target: Target) -> Self {
+        IRGenerator {
+            target,
+            ..Default::default()
+        }
+    }
+
+    /// Generate IR whose arithmetic behaves on overflow as `overflow_mode`.
+    pub fn with_overflow_mode(self, overflow_mode: OverflowMode) -> Self {
+        IRGenerator {
+            overflow_mode,
+            ..self
+        }
+    }
+
+    /// Make calls to `name` generate IR by `intrinsic`.
+    pub fn register_intrinsic(&mut self, name: &str, intrinsic: Intrinsic) {
+        self.intrinsics.insert(name.to_string(), intrinsic);
+    }
+
     /// Iterates over a vector of AST whose root is a function definition
     /// and generate IR for each of them.
-    pub fn gen_ir(&mut self, asts: &[Ast]) {
+    /// Declarations of external functions generate no IR.
+    pub fn gen_ir(&mut self, asts: &[Ast]) -> Result<(), IRError> {
+        let variadics: HashSet<String> = asts
+            .iter()
+            .filter_map(|ast| match &ast.value {
+                ExternFunc {
+                    name,
+                    is_variadic: true,
+                    ..
+                } => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
         for ast in asts {
+            if let ExternFunc { .. } = ast.value {
+                continue;
+            }
             let mut func = Function::new();
-            func.gen_ir(ast);
+            func.intrinsics = self.intrinsics.clone();
+            func.variadics = variadics.clone();
+            func.target = self.target;
+            func.overflow_mode = self.overflow_mode;
+            func.gen_ir(ast)?;
             self.funcs.push(func);
         }
+        Ok(())
+    }
+
+    /// Validate IR of each `Function`.
+    pub fn validate(&self) -> Result<(), IRError> {
+        self.funcs.iter().try_for_each(|func| func.validate())
+    }
+
+    /// Share registers holding the same immediate in each `Function`,
+    /// keeping within the registers of the target.
+    pub fn dedup_immediates(&mut self) {
+        for func in &mut self.funcs {
+            func.dedup_immediates(self.target.register_count);
+        }
+    }
+
+    /// Hoist loop-invariant arithmetic out of loops in each `Function`,
+    /// keeping within the registers of the target.
+    pub fn hoist_loop_invariants(&mut self) {
+        for func in &mut self.funcs {
+            func.hoist_loop_invariants(self.target.register_count);
+        }
+    }
+
+    /// Remove repeated `Kill` of the same register in each `Function`.
+    pub fn remove_redundant_kills(&mut self) {
+        for func in &mut self.funcs {
+            func.remove_redundant_kills();
+        }
     }
 
     /// Do register allocation for each `Function`.
-    pub fn reg_alloc(&mut self) {
+    pub fn reg_alloc(&mut self, target: &Target) {
         for func in &mut self.funcs {
-            func.reg_alloc();
+            func.reg_alloc(target);
         }
     }
 }
@@ -107,11 +328,33 @@ pub struct Function {
     // Function name.
     pub name: String,
     // Used register count in the AST.
-    reg_count: usize,
+    pub(crate) reg_count: usize,
     // Current label number for controll statement.
     pub label_number: usize,
     // Total stack size.
     pub stack_size: usize,
+    // String literals placed in read-only data.
+    pub strings: Vec<String>,
+    // Static variables placed in data as pairs of a mangled name and an initial value.
+    pub statics: Vec<(String, usize)>,
+    // Labels defined by labeled statements.
+    labels: HashSet<String>,
+    // Target labels of `goto` and their location.
+    gotos: Vec<(String, Loc)>,
+    // Errors found while generating IR.
+    errors: Vec<IRError>,
+    // Intrinsics which take precedence over normal function calls.
+    intrinsics: HashMap<String, Intrinsic>,
+    // Names of external functions taking variable arguments.
+    variadics: HashSet<String>,
+    // Jump targets of enclosing loops and switches. The innermost one places last.
+    jump_targets: Vec<JumpTargets>,
+    // Machine which the IR is generated for.
+    target: Target,
+    // How arithmetic behaves on overflow.
+    overflow_mode: OverflowMode,
+    // Label number of the trap which overflow checks jump to, once any check is generated.
+    overflow_label: Option<usize>,
 }
 
 impl Function {
@@ -119,6 +362,12 @@ impl Function {
         Default::default()
     }
 
+    /// Allocate a new virtual register.
+    pub fn new_reg(&mut self) -> Option<usize> {
+        self.reg_count += 1;
+        Some(self.reg_count)
+    }
+
     /// Returns sum of stack size of the function.
     pub fn sum_stack_offset(&self) -> usize {
         let mut sum = 0;
@@ -128,24 +377,117 @@ impl Function {
         sum
     }
 
-    pub fn gen_ir(&mut self, ast: &Ast) {
-        self.gen_expr(ast);
+    pub fn gen_ir(&mut self, ast: &Ast) -> Result<(), IRError> {
+        self.gen_node(ast);
+        // `goto` can jump forward, so its target is checked after the whole function is generated.
+        for (label, loc) in &self.gotos {
+            if !self.labels.contains(label) {
+                self.errors.push(IRError::UndefinedLabel {
+                    name: label.clone(),
+                    loc: *loc,
+                });
+            }
+        }
+        match self.errors.first() {
+            Some(error) => Err(error.clone()),
+            None => Ok(()),
+        }
+    }
+
+    /// Generate IR for an expression, appending it to `ir_vec`,
+    /// and return the virtual register holding its value.
+    /// Operands of statements are generated by this, so IR for a sub-expression
+    /// can be generated in isolation as well.
+    /// Something without a value, such as `__builtin_trap()`, is reported
+    /// and yields a register holding `0` to keep generating.
+    pub fn gen_expr(&mut self, ast: &Ast) -> usize {
+        match self.gen_node(ast) {
+            Some(reg) => reg,
+            None => {
+                self.errors
+                    .push(IRError::Type(TypeError::NotExpression(ast.loc)));
+                self.gen_ir_immidiate(0).unwrap()
+            }
+        }
     }
 
-    /// Generate IR for an AST.
-    fn gen_expr(&mut self, ast: &Ast) -> Option<usize> {
+    /// Generate IR for an AST, which is either a statement or an expression.
+    /// Returns the register holding the value if it is an expression.
+    fn gen_node(&mut self, ast: &Ast) -> Option<usize> {
+        if let Some(error) = self.undefined_var(ast) {
+            self.errors.push(error);
+            return self.gen_ir_immidiate(0);
+        }
         match &ast.value {
-            Num(n) => self.gen_ir_immidiate(*n),
+            Num(n) => {
+                if !self.target.fits_int(*n) {
+                    self.errors.push(IRError::LiteralTooWide {
+                        value: *n,
+                        loc: ast.loc,
+                    });
+                }
+                self.gen_ir_immidiate(*n)
+            }
+            StrLit(string) => self.gen_ir_str_lit(string),
             Variable(var) => self.gen_ir_variable(var),
-            Decl { lhs, rhs } => self.gen_ir_decl_var(lhs, rhs),
+            Param { .. } | ExternFunc { .. } => unreachable!(),
+            Decl {
+                lhs,
+                rhs,
+                ty,
+                is_const,
+                is_static,
+                ..
+            } => self.gen_ir_decl_var(lhs, rhs.as_deref(), ty, *is_const, *is_static),
+            Cast { ty, expr } => self.gen_ir_cast(ty, expr),
+            BinOp {
+                op: op @ (BinOpKind::LogAnd | BinOpKind::LogOr),
+                lhs,
+                rhs,
+            } => self.gen_ir_logical(op.clone(), lhs, rhs),
             BinOp { op, lhs, rhs } => self.gen_ir_binary_operator(op.clone(), lhs, rhs),
-            UniOp { op, node } => self.gen_ir_unary_operator(op.clone(), node),
-            Func { name, params, body } => self.gen_ir_func(name, params, body),
-            FuncCall { name, args } => self.gen_ir_func_call(name.to_string(), args),
+            UniOp { op, node } => self.gen_ir_unary_operator(op.clone(), node, ast.loc),
+            Func {
+                name, params, body, ..
+            } => self.gen_ir_func(name, params, body),
+            FuncCall { args, .. } if args.len() > ARG_REGISTER_COUNT => {
+                self.errors.push(IRError::TooManyArgs {
+                    max: ARG_REGISTER_COUNT,
+                    loc: ast.loc,
+                });
+                self.gen_ir_immidiate(0)
+            }
+            FuncCall { name, args } => self.gen_ir_func_call(name.to_string(), args, ast.loc),
             If { cond, then, els } => self.gen_ir_if(cond, then, els),
+            Ternary { cond, then, els } => self.gen_ir_ternary(cond, then, els),
             CompStmt { stmts } => self.gen_ir_comp_stmt(stmts),
             Assignment { lhs, rhs } => self.gen_ir_assignment(lhs, rhs),
+            SizeOf { expr } => {
+                // The operand is not evaluated, but its variable must exist.
+                if let Some(error) = self.undefined_var(expr) {
+                    self.errors.push(error);
+                }
+                self.gen_ir_immidiate(self.object_type(expr).size())
+            }
+            PostIncDec { op, lhs } => self.gen_ir_post_inc_dec(op.clone(), lhs),
             Return { expr } => self.gen_ir_return(expr),
+            Goto { label } => self.gen_ir_goto(label, ast.loc),
+            Label { name, stmt } => self.gen_ir_labeled_stmt(name, stmt, ast.loc),
+            Switch {
+                cond,
+                cases,
+                default,
+            } => self.gen_ir_switch(cond, cases, default),
+            Break => self.gen_ir_break(ast.loc),
+            While { cond, body, els } => self.gen_ir_while(cond, body, els),
+            For {
+                init,
+                cond,
+                step,
+                body,
+                els,
+            } => self.gen_ir_for(init, cond, step, body, els),
+            Continue => self.gen_ir_continue(ast.loc),
         }
     }
 
@@ -156,40 +498,220 @@ impl Function {
         Some(self.reg_count)
     }
 
-    fn gen_ir_lval(&mut self, var_name: &str) -> Option<usize> {
-        let mut env_iter = self.env.iter_mut();
+    fn gen_ir_str_lit(&mut self, string: &str) -> Option<usize> {
+        self.strings.push(string.to_string());
+        self.reg_count += 1;
+        let ir = IR::new(
+            IROp::StrAddr,
+            Some(self.reg_count),
+            Some(self.strings.len() - 1),
+        );
+        self.ir_vec.push(ir);
+        Some(self.reg_count)
+    }
+
+    /// Static type of a value an expression yields.
+    fn expr_type(&self, ast: &Ast) -> Type {
+        match &ast.value {
+            Variable(_)
+            | Cast { .. }
+            | UniOp {
+                op: UniOpKind::Deref,
+                ..
+            } => self.object_type(ast).decay(),
+            Assignment { lhs, .. } | PostIncDec { lhs, .. } => self.expr_type(lhs),
+            Ternary { then, .. } => self.expr_type(then),
+            BinOp {
+                op: BinOpKind::Add | BinOpKind::Sub,
+                lhs,
+                rhs,
+            } => {
+                let lhs_ty = self.expr_type(lhs);
+                let rhs_ty = self.expr_type(rhs);
+                match (lhs_ty.pointee(), rhs_ty.pointee()) {
+                    (Some(_), None) => lhs_ty,
+                    (None, Some(_)) => rhs_ty,
+                    _ => Type::U64,
+                }
+            }
+            _ => Type::U64,
+        }
+    }
+
+    /// Type of the object which `ast` designates, whose array is not decayed into a pointer
+    /// as it is for `sizeof`, or the same as `expr_type` if `ast` is not an object.
+    fn object_type(&self, ast: &Ast) -> Type {
+        match &ast.value {
+            Variable(var) => self.find_var(var).map_or(Type::U64, |var| var.ty.clone()),
+            Cast { ty, .. } => ty.clone(),
+            UniOp {
+                op: UniOpKind::Deref,
+                node,
+            } => self.expr_type(node).pointee().cloned().unwrap_or(Type::U64),
+            _ => self.expr_type(ast),
+        }
+    }
+
+    /// Whether an expression is of `int`, which only literals and arithmetic of them are.
+    /// Variables are `u64` or narrower, so operations on them keep the width of `u64`.
+    fn is_int_expr(&self, ast: &Ast) -> bool {
+        match &ast.value {
+            Num(_) => true,
+            UniOp {
+                op: UniOpKind::Plus | UniOpKind::Minus,
+                node,
+            } => self.is_int_expr(node),
+            BinOp { lhs, rhs, .. } => self.is_int_expr(lhs) && self.is_int_expr(rhs),
+            _ => false,
+        }
+    }
+
+    /// Look up a variable visible from the current scope.
+    fn find_var(&self, var_name: &str) -> Option<&Var> {
         // Because `Env` of inner scope is placed in the front of vector,
         // accessibility of local variables is controlled by iterating over vector from begining.
-        let var_offset = loop {
-            let env = env_iter.next().expect("Variable not found");
-            if let Some(offset) = env.local_var_map.get(var_name) {
-                break Some(*offset);
-            }
+        self.env
+            .iter()
+            .find_map(|env| env.local_var_map.get(var_name))
+    }
+
+    /// Look up a variable which `gen_node` has checked to be visible.
+    fn lookup_var(&self, var_name: &str) -> Var {
+        self.find_var(var_name)
+            .cloned()
+            .expect("Variable not found")
+    }
+
+    /// Error on a variable which `ast` reads or writes directly
+    /// but is not visible from the current scope.
+    fn undefined_var(&self, ast: &Ast) -> Option<IRError> {
+        let var = match &ast.value {
+            Variable(_) => ast,
+            Assignment { lhs, .. } | PostIncDec { lhs, .. } => lhs,
+            _ => return None,
         };
+        match &var.value {
+            Variable(name) if self.find_var(name).is_none() => {
+                Some(IRError::Type(TypeError::UndefinedVariable {
+                    name: name.clone(),
+                    loc: var.loc,
+                }))
+            }
+            _ => None,
+        }
+    }
+
+    /// Check that a variable just added to the current scope does not share
+    /// its offset with another variable which is still live.
+    fn check_stack_slot(&mut self, var_name: &str) {
+        let offset = self.env.front().unwrap().local_var_map[var_name].offset;
+        let other = self
+            .env
+            .iter()
+            .enumerate()
+            .flat_map(|(depth, env)| {
+                env.local_var_map
+                    .iter()
+                    .filter(move |(name, _)| depth != 0 || name.as_str() != var_name)
+            })
+            .filter(|(_, var)| var.offset == offset)
+            .map(|(name, _)| name)
+            .min();
+        if let Some(other) = other {
+            self.errors.push(IRError::StackSlotCollision {
+                offset,
+                first: other.clone(),
+                second: var_name.to_string(),
+            });
+        }
+    }
+
+    fn gen_ir_lval(&mut self, var_name: &str) -> Option<usize> {
+        let var = self.lookup_var(var_name);
         self.reg_count += 1;
         let reg_dst = Some(self.reg_count);
-        let ir = IR::new(IROp::BpOffset, reg_dst, var_offset);
+        let ir = match var.global {
+            Some(index) => IR::new(IROp::GlobalAddr, reg_dst, Some(index)),
+            None => IR::new(IROp::BpOffset, reg_dst, Some(var.offset)),
+        };
         self.ir_vec.push(ir);
         reg_dst
     }
 
+    /// An array is not loaded, as its value is the address of its first element.
     fn gen_ir_variable(&mut self, var_name: &str) -> Option<usize> {
         let reg = self.gen_ir_lval(var_name);
+        if let Type::Array(..) = self.lookup_var(var_name).ty {
+            return reg;
+        }
         let ir = IR::new(IROp::Load, reg, reg);
         self.ir_vec.push(ir);
         reg
     }
 
-    fn gen_ir_decl_var(&mut self, lhs: &Ast, rhs: &Ast) -> Option<usize> {
-        let var_name = ident_val!(&lhs.value);
+    /// Report assignment at `loc` to an object of `ty` if it is an array.
+    fn check_assignable(&mut self, ty: &Type, loc: Loc) {
+        if let Type::Array(..) = ty {
+            self.errors.push(IRError::AssignToArray { loc });
+        }
+    }
+
+    fn gen_ir_decl_var(
+        &mut self,
+        lhs: &Ast,
+        rhs: Option<&Ast>,
+        ty: &Type,
+        is_const: bool,
+        is_static: bool,
+    ) -> Option<usize> {
+        let var_name = match &lhs.value {
+            Variable(name) => name.clone(),
+            _ => {
+                self.errors
+                    .push(IRError::InvalidDeclTarget { loc: lhs.loc });
+                return None;
+            }
+        };
+        // A static variable is initialized in data once, so the declaration generates no IR.
+        if is_static {
+            // The variable is still declared on an error so that its uses can be generated.
+            let init = match rhs {
+                Some(rhs) => match rhs.value {
+                    Num(n) => ty.truncate(n),
+                    _ => {
+                        self.errors
+                            .push(IRError::NonConstantStatic { loc: rhs.loc });
+                        0
+                    }
+                },
+                None => {
+                    self.errors.push(IRError::StaticArray { loc: lhs.loc });
+                    0
+                }
+            };
+            // The index keeps static variables of the same name in different blocks apart.
+            let index = self.statics.len();
+            let symbol = format!("{}.{}.{}", self.name, var_name, index);
+            self.statics.push((symbol, init));
+            let env = self.env.front_mut().unwrap();
+            env.add_static(var_name, ty.clone(), is_const, index);
+            return None;
+        }
+        // Every variable takes a multiple of 8 bytes, so an array of bytes is padded.
+        let size = ty.size().max(1).div_ceil(8) * 8;
         let env = self.env.front_mut().unwrap();
-        env.add(var_name, 8);
+        env.add(var_name.clone(), size, ty.clone(), is_const);
+        let offset = env.current_var_offset;
+        self.check_stack_slot(&var_name);
+        // An array has no initializer.
+        let rhs = rhs?;
 
         self.reg_count += 1;
         let reg_lhs = Some(self.reg_count);
-        let ir = IR::new(IROp::BpOffset, reg_lhs, Some(env.current_var_offset));
+        let ir = IR::new(IROp::BpOffset, reg_lhs, Some(offset));
         self.ir_vec.push(ir);
-        let reg_rhs = self.gen_expr(rhs);
+        let reg_rhs = Some(self.gen_expr(rhs));
+        self.truncate(reg_rhs, ty);
         let ir = IR::new(IROp::Store, reg_lhs, reg_rhs);
         self.ir_vec.push(ir);
 
@@ -199,67 +721,257 @@ impl Function {
     }
 
     fn gen_ir_binary_operator(&mut self, op: BinOpKind, lhs: &Ast, rhs: &Ast) -> Option<usize> {
-        let reg_lhs = self.gen_expr(lhs);
-        let reg_rhs = self.gen_expr(rhs);
+        // Integer promotion: the narrower operand is extended to the width of the other.
+        let lhs_ty = self.expr_type(lhs);
+        let rhs_ty = self.expr_type(rhs);
+        let reg_lhs = Some(self.gen_expr(lhs));
+        if lhs_ty.size() < rhs_ty.size() {
+            self.zero_extend(reg_lhs, &lhs_ty);
+        }
+        let reg_rhs = Some(self.gen_expr(rhs));
+        if rhs_ty.size() < lhs_ty.size() {
+            self.zero_extend(reg_rhs, &rhs_ty);
+        }
+        // In pointer arithmetic, an integer counts elements rather than bytes.
+        let lhs_elem_size = lhs_ty.pointee().map(Type::size);
+        let rhs_elem_size = rhs_ty.pointee().map(Type::size);
+        match (&op, lhs_elem_size, rhs_elem_size) {
+            (BinOpKind::Add, Some(size), None) | (BinOpKind::Sub, Some(size), None) => {
+                self.scale(reg_rhs, size)
+            }
+            (BinOpKind::Add, None, Some(size)) => self.scale(reg_lhs, size),
+            _ => (),
+        }
 
         let ir = match op {
             BinOpKind::Add => IR::new(IROp::Add, reg_lhs, reg_rhs),
             BinOpKind::Sub => IR::new(IROp::Sub, reg_lhs, reg_rhs),
             BinOpKind::Mul => IR::new(IROp::Mul, reg_lhs, reg_rhs),
             BinOpKind::Div => IR::new(IROp::Div, reg_lhs, reg_rhs),
+            BinOpKind::Rem => IR::new(IROp::Rem, reg_lhs, reg_rhs),
+            BinOpKind::BitAnd => IR::new(IROp::BitAnd, reg_lhs, reg_rhs),
+            BinOpKind::BitOr => IR::new(IROp::BitOr, reg_lhs, reg_rhs),
+            BinOpKind::BitXor => IR::new(IROp::BitXor, reg_lhs, reg_rhs),
+            BinOpKind::Shl => IR::new(IROp::Shl, reg_lhs, reg_rhs),
+            BinOpKind::Shr => IR::new(IROp::Shr, reg_lhs, reg_rhs),
+            BinOpKind::Eq => IR::new(IROp::Eq, reg_lhs, reg_rhs),
+            BinOpKind::Ne => IR::new(IROp::Ne, reg_lhs, reg_rhs),
+            BinOpKind::Lt => IR::new(IROp::Lt, reg_lhs, reg_rhs),
+            BinOpKind::Le => IR::new(IROp::Le, reg_lhs, reg_rhs),
+            BinOpKind::Gt => IR::new(IROp::Gt, reg_lhs, reg_rhs),
+            BinOpKind::Ge => IR::new(IROp::Ge, reg_lhs, reg_rhs),
+            BinOpKind::LogAnd | BinOpKind::LogOr => unreachable!(),
+        };
+        let ir = if self.is_int_expr(lhs) && self.is_int_expr(rhs) {
+            ir.with_size(self.target.int_size)
+        } else {
+            ir
         };
         self.ir_vec.push(ir);
+        if self.overflow_mode == OverflowMode::Checked
+            && matches!(op, BinOpKind::Add | BinOpKind::Sub | BinOpKind::Mul)
+        {
+            self.gen_ir_overflow_check();
+        }
         self.kill(reg_rhs);
+        // Difference of two pointers is the number of elements between them.
+        if let (BinOpKind::Sub, Some(size), Some(_)) = (&op, lhs_elem_size, rhs_elem_size) {
+            let reg_size = self.gen_ir_immidiate(size);
+            self.ir_vec.push(IR::new(IROp::Div, reg_lhs, reg_size));
+            self.kill(reg_size);
+        }
         reg_lhs
     }
 
-    fn gen_ir_unary_operator(&mut self, op: UniOpKind, node: &Ast) -> Option<usize> {
-        let node = self.gen_expr(node);
-        let ir = match op {
-            UniOpKind::Minus => IR::new(IROp::Minus, node, None),
+    /// `rhs` is evaluated only if `lhs` does not decide the result:
+    /// `&&` is decided by a zero operand and `||` by a non-zero one.
+    /// Both paths load `1` or `0` into the same register, so the value is a boolean.
+    fn gen_ir_logical(&mut self, op: BinOpKind, lhs: &Ast, rhs: &Ast) -> Option<usize> {
+        self.label_number += 1;
+        let label_number = self.label_number;
+        let decided = match op {
+            BinOpKind::LogAnd => 0,
+            _ => 1,
         };
-        self.ir_vec.push(ir);
+        for operand in [lhs, rhs] {
+            let reg = Some(self.gen_expr(operand));
+            let ir = match op {
+                BinOpKind::LogAnd => IR::new(IROp::Cond, reg, Some(label_number)),
+                _ => IR::new(
+                    IROp::Branch(self.local_label_name("else", label_number)),
+                    reg,
+                    None,
+                ),
+            };
+            self.ir_vec.push(ir);
+            self.kill(reg);
+        }
+        let reg_result = self.gen_ir_immidiate(1 - decided);
+        self.gen_ir_jmp(self.local_label_name("end", label_number));
+        self.gen_ir_label("else".to_string(), label_number);
+        self.ir_vec
+            .push(IR::new(IROp::Imm, reg_result, Some(decided)));
+        self.gen_ir_label("end".to_string(), label_number);
+        reg_result
+    }
+
+    /// Jump to the trap of the function if the last arithmetic overflowed.
+    fn gen_ir_overflow_check(&mut self) {
+        let label_number = match self.overflow_label {
+            Some(label_number) => label_number,
+            None => {
+                self.label_number += 1;
+                self.overflow_label = Some(self.label_number);
+                self.label_number
+            }
+        };
+        let label = self.local_label_name("overflow", label_number);
+        self.ir_vec
+            .push(IR::new(IROp::BranchOverflow(label), None, None));
+    }
+
+    /// Multiply a value in `reg` by `size`.
+    fn scale(&mut self, reg: Option<usize>, size: usize) {
+        if size != 1 {
+            let reg_size = self.gen_ir_immidiate(size);
+            self.ir_vec.push(IR::new(IROp::Mul, reg, reg_size));
+            self.kill(reg_size);
+        }
+    }
+
+    /// Memory through a pointer is accessed in the size of its pointee,
+    /// and a narrower value is zero-extended by the load.
+    fn gen_ir_unary_operator(&mut self, op: UniOpKind, node: &Ast, loc: Loc) -> Option<usize> {
+        let ty = self.expr_type(node);
+        if op == UniOpKind::Deref {
+            self.check_pointer(&ty, loc);
+        }
+        let size = if self.is_int_expr(node) {
+            self.target.int_size
+        } else {
+            8
+        };
+        let node = Some(self.gen_expr(node));
+        match op {
+            UniOpKind::Plus => (),
+            UniOpKind::Minus => self
+                .ir_vec
+                .push(IR::new(IROp::Neg, node, None).with_size(size)),
+            // An array is not loaded, as its value is the address of its first element.
+            UniOpKind::Deref if matches!(ty.pointee(), Some(Type::Array(..))) => (),
+            UniOpKind::Deref => {
+                let pointee_size = ty.pointee().map_or(8, Type::size);
+                self.ir_vec
+                    .push(IR::new(IROp::Load, node, node).with_size(pointee_size));
+            }
+        }
         node
     }
 
-    fn gen_ir_func(&mut self, name: &str, params: &Vec<Ast>, body: &Ast) -> Option<usize> {
+    fn gen_ir_cast(&mut self, ty: &Type, expr: &Ast) -> Option<usize> {
+        let reg = Some(self.gen_expr(expr));
+        self.truncate(reg, ty);
+        reg
+    }
+
+    /// Truncate a value in `reg` if `ty` is narrower than a register.
+    /// The value is left zero-extended, so `Zext` is not needed right after it.
+    fn truncate(&mut self, reg: Option<usize>, ty: &Type) {
+        let ty = ty.decay();
+        if ty.size() < Type::U64.size() {
+            self.ir_vec.push(IR::new(IROp::Cast(ty), reg, None));
+        }
+    }
+
+    /// Zero-extend a value of `ty` in `reg` unless it has just been truncated by `Cast`
+    /// or loaded from a byte, both of which already leave it zero-extended.
+    fn zero_extend(&mut self, reg: Option<usize>, ty: &Type) {
+        let is_extended = self.ir_vec.last().is_some_and(|ir| match ir.op {
+            IROp::Cast(_) => ir.lhs == reg,
+            IROp::Load => ir.size == 1 && ir.rhs == reg,
+            _ => false,
+        });
+        if !is_extended {
+            self.ir_vec.push(IR::new(IROp::Zext(ty.clone()), reg, None));
+        }
+    }
+
+    fn gen_ir_func(&mut self, name: &str, params: &[Ast], body: &Ast) -> Option<usize> {
         self.name = name.to_string();
         self.env.push_front(Env::new(0));
         for (i, param) in params.iter().enumerate() {
             self.gen_ir_func_param(i, param);
         }
-        self.gen_expr(body);
+        self.gen_node(body);
+        if let Some(label_number) = self.overflow_label {
+            // The trap is placed after the body, which must not fall into it.
+            self.gen_ir_jmp(format!("return_{}", name));
+            self.ir_vec.push(IR::new(
+                IROp::Label("overflow".to_string()),
+                Some(label_number),
+                None,
+            ));
+            self.ir_vec.push(IR::new(IROp::Trap, None, None));
+        }
         None
     }
 
     fn gen_ir_func_param(&mut self, param_reg_num: usize, param: &Ast) -> Option<usize> {
-        let var_name = ident_val!(&param.value);
+        let (var_name, ty) = match &param.value {
+            // An array parameter is passed as a pointer to its first element.
+            Param { name, ty } => (name.clone(), ty.decay()),
+            _ => unreachable!(),
+        };
         let env = self.env.front_mut().unwrap();
-        env.add(var_name, 8);
+        env.add(var_name.clone(), 8, ty.clone(), false);
+        let offset = env.current_var_offset;
+        self.check_stack_slot(&var_name);
 
         self.reg_count += 1;
         let reg_lhs = Some(self.reg_count);
-        self.ir_vec.push(IR::new(
-            IROp::BpOffset,
-            reg_lhs,
-            Some(env.current_var_offset),
-        ));
+        self.ir_vec
+            .push(IR::new(IROp::BpOffset, reg_lhs, Some(offset)));
         self.ir_vec
             .push(IR::new(IROp::LoadParam, Some(param_reg_num), reg_lhs));
+        // Caller passes a full register, so a narrower parameter is truncated in place.
+        if ty.size() < Type::U64.size() {
+            let reg_param = self.gen_ir_variable(&var_name);
+            self.truncate(reg_param, &ty);
+            self.ir_vec.push(IR::new(IROp::Store, reg_lhs, reg_param));
+            self.kill(reg_param);
+        }
         self.kill(reg_lhs);
         None
     }
 
-    fn gen_ir_func_call(&mut self, name: String, args: &Vec<Ast>) -> Option<usize> {
-        let mut reg_args = [None; 6];
+    fn gen_ir_func_call(&mut self, name: String, args: &[Ast], loc: Loc) -> Option<usize> {
+        if let Some(intrinsic) = self.intrinsics.get(&name).copied() {
+            return intrinsic(self, args, loc);
+        }
+        self.gen_ir_call(name, args)
+    }
+
+    /// Call a function without looking up intrinsics.
+    /// Every argument is evaluated before any of them is stored, since evaluating one
+    /// may use an argument register, such as `rcx` for a shift or `rdx` for a division.
+    fn gen_ir_call(&mut self, name: String, args: &[Ast]) -> Option<usize> {
+        let mut reg_args = [None; ARG_REGISTER_COUNT];
         for (i, arg) in args.iter().enumerate() {
-            let reg_arg = self.gen_expr(arg);
-            reg_args[i] = reg_arg;
-            self.ir_vec.push(IR::new(IROp::StoreArg, Some(i), reg_arg));
+            reg_args[i] = Some(self.gen_expr(arg));
         }
+        for (i, reg_arg) in reg_args.iter().take(args.len()).enumerate() {
+            self.ir_vec.push(IR::new(IROp::StoreArg, Some(i), *reg_arg));
+        }
+        // A variadic callee is told how many vector registers hold arguments,
+        // which is always zero as floating-point numbers are not supported.
+        let vector_args = if self.variadics.contains(&name) {
+            Some(0)
+        } else {
+            None
+        };
         self.reg_count += 1;
         let reg = Some(self.reg_count);
-        let ir = IR::new(IROp::FuncCall(name), reg, None);
+        let ir = IR::new(IROp::FuncCall(name), reg, vector_args);
         self.ir_vec.push(ir);
         for reg_arg in reg_args.iter() {
             if reg_arg.is_some() {
@@ -269,37 +981,363 @@ impl Function {
         reg
     }
 
-    fn gen_ir_if(&mut self, cond: &Ast, then: &Ast, _els: &Option<Box<Ast>>) -> Option<usize> {
+    /// `__builtin_trap()`: abort the program.
+    fn gen_ir_trap(&mut self, _args: &[Ast], _loc: Loc) -> Option<usize> {
+        self.ir_vec.push(IR::new(IROp::Trap, None, None));
+        None
+    }
+
+    /// `min(a, b)`: the smaller of two values.
+    fn gen_ir_min(&mut self, args: &[Ast], loc: Loc) -> Option<usize> {
+        self.gen_ir_select(IROp::Min, "min", args, loc)
+    }
+
+    /// `max(a, b)`: the larger of two values.
+    fn gen_ir_max(&mut self, args: &[Ast], loc: Loc) -> Option<usize> {
+        self.gen_ir_select(IROp::Max, "max", args, loc)
+    }
+
+    /// Select one of two arguments by `op` without a branch.
+    fn gen_ir_select(&mut self, op: IROp, name: &str, args: &[Ast], loc: Loc) -> Option<usize> {
+        match args {
+            [lhs, rhs] => {
+                let reg_lhs = Some(self.gen_expr(lhs));
+                let reg_rhs = Some(self.gen_expr(rhs));
+                self.ir_vec.push(IR::new(op, reg_lhs, reg_rhs));
+                self.kill(reg_rhs);
+                reg_lhs
+            }
+            _ => {
+                self.errors.push(IRError::ArgCountMismatch {
+                    name: name.to_string(),
+                    expected: 2,
+                    found: args.len(),
+                    loc,
+                });
+                self.gen_ir_immidiate(0)
+            }
+        }
+    }
+
+    fn gen_ir_if(&mut self, cond: &Ast, then: &Ast, els: &Option<Box<Ast>>) -> Option<usize> {
         self.label_number += 1;
-        let reg_flag = self.gen_expr(cond);
-        let ir_condition = IR::new(IROp::Cond, reg_flag, Some(self.label_number));
+        // Nested statements also take label numbers, so keep this one.
+        let label_number = self.label_number;
+        let reg_flag = Some(self.gen_expr(cond));
+        let ir_condition = IR::new(IROp::Cond, reg_flag, Some(label_number));
         self.ir_vec.push(ir_condition);
         self.kill(reg_flag);
 
-        self.gen_expr(then);
-        self.gen_ir_label("else".to_string(), self.label_number);
+        self.gen_node(then);
+        match els {
+            Some(els) => {
+                self.gen_ir_jmp(self.local_label_name("end", label_number));
+                self.gen_ir_label("else".to_string(), label_number);
+                self.gen_node(els);
+                self.gen_ir_label("end".to_string(), label_number);
+            }
+            None => {
+                self.gen_ir_label("else".to_string(), label_number);
+            }
+        }
         None
     }
 
+    /// Both branches move their value into the same register,
+    /// so the value is there whichever branch is taken.
+    fn gen_ir_ternary(&mut self, cond: &Ast, then: &Ast, els: &Ast) -> Option<usize> {
+        self.label_number += 1;
+        let label_number = self.label_number;
+        let reg_result = self.new_reg();
+        let reg_flag = Some(self.gen_expr(cond));
+        let ir_condition = IR::new(IROp::Cond, reg_flag, Some(label_number));
+        self.ir_vec.push(ir_condition);
+        self.kill(reg_flag);
+
+        let reg_then = Some(self.gen_expr(then));
+        self.ir_vec.push(IR::new(IROp::Mov, reg_result, reg_then));
+        self.kill(reg_then);
+        self.gen_ir_jmp(self.local_label_name("end", label_number));
+        self.gen_ir_label("else".to_string(), label_number);
+        let reg_els = Some(self.gen_expr(els));
+        self.ir_vec.push(IR::new(IROp::Mov, reg_result, reg_els));
+        self.kill(reg_els);
+        self.gen_ir_label("end".to_string(), label_number);
+        reg_result
+    }
+
     fn gen_ir_comp_stmt(&mut self, stmts: &[Ast]) -> Option<usize> {
         let sum_of_outer_scope_offset = self.env.front().unwrap().current_var_offset;
         self.env.push_front(Env::new(sum_of_outer_scope_offset));
         for stmt in stmts {
-            self.gen_expr(stmt);
+            self.gen_expr_stmt(stmt);
+        }
+        self.stack_size += self.sum_stack_offset();
+        self.env.pop_front();
+        None
+    }
+
+    /// The value is compared with each case in order and control jumps to the first match.
+    /// If nothing matches, control jumps to `default` or past the switch.
+    fn gen_ir_switch(
+        &mut self,
+        cond: &Ast,
+        cases: &[(usize, Vec<Ast>)],
+        default: &Option<Vec<Ast>>,
+    ) -> Option<usize> {
+        self.label_number += 1;
+        let label_number = self.label_number;
+        let case_label_numbers: Vec<usize> = cases
+            .iter()
+            .map(|_| {
+                self.label_number += 1;
+                self.label_number
+            })
+            .collect();
+
+        let reg_cond = Some(self.gen_expr(cond));
+        for ((value, _), case_label_number) in cases.iter().zip(&case_label_numbers) {
+            let reg_case = self.gen_ir_immidiate(*value);
+            self.ir_vec.push(IR::new(IROp::Eq, reg_case, reg_cond));
+            let case_label = self.local_label_name("case", *case_label_number);
+            self.ir_vec
+                .push(IR::new(IROp::Branch(case_label), reg_case, None));
+            self.kill(reg_case);
+        }
+        self.kill(reg_cond);
+        let end_label = self.local_label_name("end", label_number);
+        match default {
+            Some(_) => self.gen_ir_jmp(self.local_label_name("default", label_number)),
+            None => self.gen_ir_jmp(end_label.clone()),
+        };
+
+        // All cases share one scope as a block does.
+        let sum_of_outer_scope_offset = self.env.front().unwrap().current_var_offset;
+        self.env.push_front(Env::new(sum_of_outer_scope_offset));
+        self.jump_targets.push(JumpTargets {
+            break_label: end_label,
+            continue_label: None,
+        });
+        for ((_, stmts), case_label_number) in cases.iter().zip(case_label_numbers) {
+            self.gen_ir_label("case".to_string(), case_label_number);
+            for stmt in stmts {
+                self.gen_expr_stmt(stmt);
+            }
+        }
+        if let Some(stmts) = default {
+            self.gen_ir_label("default".to_string(), label_number);
+            for stmt in stmts {
+                self.gen_expr_stmt(stmt);
+            }
+        }
+        self.jump_targets.pop();
+        self.stack_size += self.sum_stack_offset();
+        self.env.pop_front();
+        self.gen_ir_label("end".to_string(), label_number);
+        None
+    }
+
+    fn gen_ir_break(&mut self, loc: Loc) -> Option<usize> {
+        match self.jump_targets.last() {
+            Some(targets) => self.gen_ir_jmp(targets.break_label.clone()),
+            None => {
+                self.errors.push(IRError::MisplacedBreak { loc });
+                None
+            }
         }
+    }
+
+    fn gen_ir_continue(&mut self, loc: Loc) -> Option<usize> {
+        let label = self
+            .jump_targets
+            .iter()
+            .rev()
+            .find_map(|targets| targets.continue_label.clone());
+        match label {
+            Some(label) => self.gen_ir_jmp(label),
+            None => {
+                self.errors.push(IRError::MisplacedContinue { loc });
+                None
+            }
+        }
+    }
+
+    /// `continue` jumps to the condition.
+    fn gen_ir_while(&mut self, cond: &Ast, body: &Ast, els: &Option<Box<Ast>>) -> Option<usize> {
+        self.label_number += 1;
+        let label_number = self.label_number;
+        self.gen_ir_label("begin".to_string(), label_number);
+        let reg_flag = Some(self.gen_expr(cond));
+        self.ir_vec
+            .push(IR::new(IROp::Cond, reg_flag, Some(label_number)));
+        self.kill(reg_flag);
+
+        self.gen_ir_loop_body(body, label_number, "begin", els.is_some());
+        self.gen_ir_jmp(self.local_label_name("begin", label_number));
+        // `Cond` jumps to the else label when the condition does not hold.
+        self.gen_ir_loop_else(els, label_number);
+        None
+    }
+
+    /// `continue` jumps to the step, which is followed by the condition.
+    fn gen_ir_for(
+        &mut self,
+        init: &Option<Box<Ast>>,
+        cond: &Option<Box<Ast>>,
+        step: &Option<Box<Ast>>,
+        body: &Ast,
+        els: &Option<Box<Ast>>,
+    ) -> Option<usize> {
+        self.label_number += 1;
+        let label_number = self.label_number;
+        // A variable declared in `init` is visible only inside the loop.
+        let sum_of_outer_scope_offset = self.env.front().unwrap().current_var_offset;
+        self.env.push_front(Env::new(sum_of_outer_scope_offset));
+        if let Some(init) = init {
+            self.gen_expr_stmt(init);
+        }
+        self.gen_ir_label("begin".to_string(), label_number);
+        if let Some(cond) = cond {
+            let reg_flag = Some(self.gen_expr(cond));
+            self.ir_vec
+                .push(IR::new(IROp::Cond, reg_flag, Some(label_number)));
+            self.kill(reg_flag);
+        }
+
+        self.gen_ir_loop_body(body, label_number, "step", els.is_some());
+        self.gen_ir_label("step".to_string(), label_number);
+        if let Some(step) = step {
+            self.gen_expr_stmt(step);
+        }
+        self.gen_ir_jmp(self.local_label_name("begin", label_number));
         self.stack_size += self.sum_stack_offset();
         self.env.pop_front();
+        self.gen_ir_loop_else(els, label_number);
         None
     }
 
+    /// `break` skips the else clause of the loop if it exists.
+    fn gen_ir_loop_body(
+        &mut self,
+        body: &Ast,
+        label_number: usize,
+        continue_label: &str,
+        has_else: bool,
+    ) {
+        let break_label = if has_else { "end" } else { "else" };
+        self.jump_targets.push(JumpTargets {
+            break_label: self.local_label_name(break_label, label_number),
+            continue_label: Some(self.local_label_name(continue_label, label_number)),
+        });
+        self.gen_node(body);
+        self.jump_targets.pop();
+    }
+
+    /// The loop reaches the else label only when its condition does not hold.
+    fn gen_ir_loop_else(&mut self, els: &Option<Box<Ast>>, label_number: usize) {
+        self.gen_ir_label("else".to_string(), label_number);
+        if let Some(els) = els {
+            self.gen_node(els);
+            self.gen_ir_label("end".to_string(), label_number);
+        }
+    }
+
+    /// Generate IR for a statement. The value of an expression statement,
+    /// such as `a + b;`, is not used and its register is killed right away.
+    fn gen_expr_stmt(&mut self, ast: &Ast) {
+        let reg = self.gen_node(ast);
+        if reg.is_some() {
+            self.kill(reg);
+        }
+    }
+
     fn gen_ir_assignment(&mut self, lhs: &Ast, rhs: &Ast) -> Option<usize> {
+        if let UniOp {
+            op: UniOpKind::Deref,
+            node,
+        } = &lhs.value
+        {
+            return self.gen_ir_deref_assignment(node, rhs, lhs.loc);
+        }
         let val_name = ident_val!(&lhs.value);
+        let var = self.lookup_var(&val_name);
+        if var.is_const {
+            self.errors.push(IRError::AssignToConst {
+                name: val_name.clone(),
+                loc: lhs.loc,
+            });
+        }
+        self.check_assignable(&var.ty, lhs.loc);
         let reg_lhs = self.gen_ir_lval(&val_name);
-        let reg_rhs = self.gen_expr(rhs);
+        let reg_rhs = Some(self.gen_expr(rhs));
+        let ty = self.lookup_var(&val_name).ty;
+        self.truncate(reg_rhs, &ty);
         let ir = IR::new(IROp::Store, reg_lhs, reg_rhs);
         self.ir_vec.push(ir);
+        self.kill(reg_lhs);
 
-        reg_lhs
+        // Assignment expression yields the assigned value, e.g. `a = b = c`.
+        reg_rhs
+    }
+
+    /// Report dereference at `loc` of a value of `ty` unless it is a pointer.
+    fn check_pointer(&mut self, ty: &Type, loc: Loc) {
+        if ty.pointee().is_none() {
+            self.errors.push(IRError::Type(TypeError::InvalidOperand {
+                op: UniOpKind::Deref,
+                ty: ty.clone(),
+                loc,
+            }));
+        }
+    }
+
+    /// Store into the address which `ptr` evaluates to, as `*ptr = rhs`.
+    fn gen_ir_deref_assignment(&mut self, ptr: &Ast, rhs: &Ast, loc: Loc) -> Option<usize> {
+        let ty = self.expr_type(ptr);
+        self.check_pointer(&ty, loc);
+        let pointee = ty.pointee().unwrap_or(&Type::U64);
+        self.check_assignable(pointee, loc);
+        let reg_lhs = Some(self.gen_expr(ptr));
+        let reg_rhs = Some(self.gen_expr(rhs));
+        self.truncate(reg_rhs, pointee);
+        self.ir_vec
+            .push(IR::new(IROp::Store, reg_lhs, reg_rhs).with_size(pointee.size()));
+        self.kill(reg_lhs);
+        reg_rhs
+    }
+
+    /// The old value is loaded into its own register before the variable is updated.
+    /// A pointer steps by the size of its pointee as in pointer arithmetic.
+    fn gen_ir_post_inc_dec(&mut self, op: BinOpKind, lhs: &Ast) -> Option<usize> {
+        let val_name = ident_val!(&lhs.value);
+        let var = self.lookup_var(&val_name);
+        if var.is_const {
+            self.errors.push(IRError::AssignToConst {
+                name: val_name.clone(),
+                loc: lhs.loc,
+            });
+        }
+        self.check_assignable(&var.ty, lhs.loc);
+        let reg_old = self.gen_ir_variable(&val_name);
+
+        let reg_new = self.new_reg();
+        self.ir_vec.push(IR::new(IROp::Mov, reg_new, reg_old));
+        let reg_one = self.gen_ir_immidiate(1);
+        if let Some(size) = var.ty.pointee().map(Type::size) {
+            self.scale(reg_one, size);
+        }
+        let ir_op = match op {
+            BinOpKind::Add => IROp::Add,
+            _ => IROp::Sub,
+        };
+        self.ir_vec.push(IR::new(ir_op, reg_new, reg_one));
+        self.kill(reg_one);
+        self.truncate(reg_new, &var.ty);
+        let reg_lhs = self.gen_ir_lval(&val_name);
+        self.ir_vec.push(IR::new(IROp::Store, reg_lhs, reg_new));
+        self.kill(reg_lhs);
+        self.kill(reg_new);
+        reg_old
     }
 
     fn gen_ir_label(&mut self, name: String, label_number: usize) -> Option<usize> {
@@ -315,12 +1353,54 @@ impl Function {
     }
 
     fn gen_ir_return(&mut self, expr: &Ast) -> Option<usize> {
-        let reg_expr = self.gen_expr(expr);
+        let reg_expr = Some(self.gen_expr(expr));
+        // `return f(x);` is a tail call. Intrinsics emit no call to be marked.
+        if let FuncCall { .. } = expr.value {
+            let call = self
+                .ir_vec
+                .iter_mut()
+                .rev()
+                .find(|ir| matches!(ir.op, IROp::FuncCall(_)) && ir.lhs == reg_expr);
+            if let Some(ir) = call {
+                if let IROp::FuncCall(name) = &ir.op {
+                    ir.op = IROp::TailCall(name.clone());
+                }
+            }
+        }
         let ir = IR::new(IROp::Return, reg_expr, None);
         self.ir_vec.push(ir);
         self.kill(reg_expr);
         self.gen_ir_jmp(format!("return_{}", self.name));
-        reg_expr
+        None
+    }
+
+    /// Label name in assembly is prefixed with the function name
+    /// because labels of different functions share one namespace.
+    fn user_label_name(&self, label: &str) -> String {
+        format!("{}.{}", self.name, label)
+    }
+
+    /// Name of a label generated by `gen_ir_label` as a target of `Jmp` or `Branch`.
+    /// Its name follows two dots, which `user_label_name` never yields from an identifier.
+    fn local_label_name(&self, name: &str, label_number: usize) -> String {
+        format!("{}..{}{}", self.name, name, label_number)
+    }
+
+    fn gen_ir_goto(&mut self, label: &str, loc: Loc) -> Option<usize> {
+        self.gotos.push((label.to_string(), loc));
+        self.gen_ir_jmp(self.user_label_name(label))
+    }
+
+    fn gen_ir_labeled_stmt(&mut self, name: &str, stmt: &Ast, loc: Loc) -> Option<usize> {
+        if !self.labels.insert(name.to_string()) {
+            self.errors.push(IRError::DuplicateLabel {
+                name: name.to_string(),
+                loc,
+            });
+        }
+        let ir = IR::new(IROp::Label(self.user_label_name(name)), None, None);
+        self.ir_vec.push(ir);
+        self.gen_node(stmt)
     }
 
     fn kill(&mut self, reg: Option<usize>) {
@@ -332,17 +1412,13 @@ impl Function {
 #[cfg(test)]
 mod tests {
     use crate::ir::gen_ir::*;
+    use crate::ir::testutil::{gen_ir_of, gen_ir_with};
     use crate::parse::parser::Parser;
     use crate::token::lexer::Lexer;
     #[test]
     fn test_calc() -> std::io::Result<()> {
         let source_code = crate::read_file_content("examples/calc.pr")?;
-        let mut lexer = Lexer::new(&source_code);
-        let tokens = lexer.lex().unwrap();
-        let mut parser = Parser::new(&tokens);
-        let ast = parser.parse().unwrap();
-        let mut ir_generator = IRGenerator::new();
-        ir_generator.gen_ir(&ast);
+        let ir_generator = gen_ir_of(&source_code).unwrap();
 
         assert_eq!(
             ir_generator.funcs[0].ir_vec,
@@ -392,16 +1468,16 @@ mod tests {
                 IR::new(IROp::Kill, Some(2), None),
                 IR::new(IROp::BpOffset, Some(3), Some(16)),
                 IR::new(IROp::Imm, Some(4), Some(2)),
-                IR::new(IROp::Minus, Some(4), None),
+                IR::new(IROp::Neg, Some(4), None),
                 IR::new(IROp::Store, Some(3), Some(4)),
                 IR::new(IROp::Kill, Some(3), None),
                 IR::new(IROp::Kill, Some(4), None),
                 IR::new(IROp::BpOffset, Some(5), Some(24)),
                 IR::new(IROp::BpOffset, Some(6), Some(8)),
                 IR::new(IROp::Load, Some(6), Some(6)),
-                IR::new(IROp::StoreArg, Some(0), Some(6)),
                 IR::new(IROp::BpOffset, Some(7), Some(16)),
                 IR::new(IROp::Load, Some(7), Some(7)),
+                IR::new(IROp::StoreArg, Some(0), Some(6)),
                 IR::new(IROp::StoreArg, Some(1), Some(7)),
                 IR::new(IROp::FuncCall("f".to_string()), Some(8), None),
                 IR::new(IROp::Kill, Some(6), None),
@@ -422,12 +1498,7 @@ mod tests {
     #[test]
     fn test_stmt() -> std::io::Result<()> {
         let source_code = crate::read_file_content("examples/stmt.pr")?;
-        let mut lexer = Lexer::new(&source_code);
-        let tokens = lexer.lex().unwrap();
-        let mut parser = Parser::new(&tokens);
-        let ast = parser.parse().unwrap();
-        let mut ir_generator = IRGenerator::new();
-        ir_generator.gen_ir(&ast);
+        let ir_generator = gen_ir_of(&source_code).unwrap();
 
         assert_eq!(
             ir_generator.funcs[0].ir_vec,
@@ -444,6 +1515,8 @@ mod tests {
                 IR::new(IROp::BpOffset, Some(4), Some(8)),
                 IR::new(IROp::Imm, Some(5), Some(2)),
                 IR::new(IROp::Store, Some(4), Some(5)),
+                IR::new(IROp::Kill, Some(4), None),
+                IR::new(IROp::Kill, Some(5), None),
                 IR::new(IROp::BpOffset, Some(6), Some(8)),
                 IR::new(IROp::Load, Some(6), Some(6)),
                 IR::new(IROp::Return, Some(6), None),
@@ -459,4 +1532,833 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_forward_goto() {
+        let source_code = "func main() { goto end; return 1; end: return 0; }";
+        let ir_generator = gen_ir_of(source_code).unwrap();
+
+        assert_eq!(
+            ir_generator.funcs[0].ir_vec,
+            vec![
+                IR::new(IROp::Jmp("main.end".to_string()), None, None),
+                IR::new(IROp::Imm, Some(1), Some(1)),
+                IR::new(IROp::Return, Some(1), None),
+                IR::new(IROp::Kill, Some(1), None),
+                IR::new(IROp::Jmp("return_main".to_string()), None, None),
+                IR::new(IROp::Label("main.end".to_string()), None, None),
+                IR::new(IROp::Imm, Some(2), Some(0)),
+                IR::new(IROp::Return, Some(2), None),
+                IR::new(IROp::Kill, Some(2), None),
+                IR::new(IROp::Jmp("return_main".to_string()), None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_return_comparison() {
+        let source_code = "func main() { return 3 == 4; }";
+        let ir_generator = gen_ir_of(source_code).unwrap();
+
+        assert_eq!(
+            ir_generator.funcs[0].ir_vec,
+            vec![
+                IR::new(IROp::Imm, Some(1), Some(3)),
+                IR::new(IROp::Imm, Some(2), Some(4)),
+                IR::new(IROp::Eq, Some(1), Some(2)),
+                IR::new(IROp::Kill, Some(2), None),
+                IR::new(IROp::Return, Some(1), None),
+                IR::new(IROp::Kill, Some(1), None),
+                IR::new(IROp::Jmp("return_main".to_string()), None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cast() {
+        let source_code = "func main() { let c: u8 = 300; return (u64)c + (u8)c; }";
+        let ir_generator = gen_ir_of(source_code).unwrap();
+
+        assert_eq!(
+            ir_generator.funcs[0].ir_vec,
+            vec![
+                IR::new(IROp::BpOffset, Some(1), Some(8)),
+                IR::new(IROp::Imm, Some(2), Some(300)),
+                IR::new(IROp::Cast(Type::U8), Some(2), None),
+                IR::new(IROp::Store, Some(1), Some(2)),
+                IR::new(IROp::Kill, Some(1), None),
+                IR::new(IROp::Kill, Some(2), None),
+                IR::new(IROp::BpOffset, Some(3), Some(8)),
+                IR::new(IROp::Load, Some(3), Some(3)),
+                IR::new(IROp::BpOffset, Some(4), Some(8)),
+                IR::new(IROp::Load, Some(4), Some(4)),
+                IR::new(IROp::Cast(Type::U8), Some(4), None),
+                IR::new(IROp::Add, Some(3), Some(4)),
+                IR::new(IROp::Kill, Some(4), None),
+                IR::new(IROp::Return, Some(3), None),
+                IR::new(IROp::Kill, Some(3), None),
+                IR::new(IROp::Jmp("return_main".to_string()), None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chained_assignment() {
+        let source_code = "func main() { let a: u64 = 0; let b: u64 = 0; a = b = 3; return a; }";
+        let ir_generator = gen_ir_of(source_code).unwrap();
+
+        assert_eq!(
+            ir_generator.funcs[0].ir_vec[10..17],
+            [
+                IR::new(IROp::BpOffset, Some(5), Some(8)),
+                IR::new(IROp::BpOffset, Some(6), Some(16)),
+                IR::new(IROp::Imm, Some(7), Some(3)),
+                IR::new(IROp::Store, Some(6), Some(7)),
+                IR::new(IROp::Kill, Some(6), None),
+                IR::new(IROp::Store, Some(5), Some(7)),
+                IR::new(IROp::Kill, Some(5), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_str_lit() {
+        let source_code = r#"func main() { let s: u64 = "hi\n"; return 0; }"#;
+        let ir_generator = gen_ir_of(source_code).unwrap();
+
+        assert_eq!(ir_generator.funcs[0].strings, vec!["hi\n".to_string()]);
+        assert_eq!(
+            ir_generator.funcs[0].ir_vec[..3],
+            [
+                IR::new(IROp::BpOffset, Some(1), Some(8)),
+                IR::new(IROp::StrAddr, Some(2), Some(0)),
+                IR::new(IROp::Store, Some(1), Some(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_static_local() {
+        let source_code = "func f() { static c: u8 = 257; c += 1; return c; }";
+        let ir_generator = gen_ir_of(source_code).unwrap();
+
+        let func = &ir_generator.funcs[0];
+        assert_eq!(func.statics, [("f.c.0".to_string(), 1)]);
+        assert_eq!(func.ir_vec[..1], ir![global_addr(1, 0)]);
+        assert!(func.ir_vec.iter().all(|ir| ir.op != IROp::BpOffset));
+        assert_eq!(func.stack_size, 0);
+
+        let source_code = "func f(a: u64) { static c: u64 = a; return c; }";
+        assert_eq!(
+            gen_ir_of(source_code).map(drop),
+            Err(IRError::NonConstantStatic { loc: Loc(33, 34) })
+        );
+    }
+
+    #[test]
+    fn test_negate_variable() {
+        let source_code = "func f(a: u64) { return -a; }";
+        let ir_generator = gen_ir_of(source_code).unwrap();
+
+        assert_eq!(
+            ir_generator.funcs[0].ir_vec[3..],
+            [
+                IR::new(IROp::BpOffset, Some(2), Some(8)),
+                IR::new(IROp::Load, Some(2), Some(2)),
+                IR::new(IROp::Neg, Some(2), None),
+                IR::new(IROp::Return, Some(2), None),
+                IR::new(IROp::Kill, Some(2), None),
+                IR::new(IROp::Jmp("return_f".to_string()), None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deref_assignment() {
+        let source_code = "func f(p: u64*) { *p = 5; }";
+        let ir_generator = gen_ir_of(source_code).unwrap();
+
+        // The value of `p` is the address to store into, not the slot of `p`.
+        assert_eq!(
+            ir_generator.funcs[0].ir_vec[3..],
+            [
+                IR::new(IROp::BpOffset, Some(2), Some(8)),
+                IR::new(IROp::Load, Some(2), Some(2)),
+                IR::new(IROp::Imm, Some(3), Some(5)),
+                IR::new(IROp::Store, Some(2), Some(3)),
+                IR::new(IROp::Kill, Some(2), None),
+                IR::new(IROp::Kill, Some(3), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_undefined_variable() {
+        let gen = |source_code: &str| gen_ir_of(source_code).map(drop);
+        for (source_code, loc) in [
+            ("func main() { return x + 1; }", Loc(21, 22)),
+            ("func main() { x = 1; }", Loc(14, 15)),
+            ("func main() { x++; }", Loc(14, 15)),
+            ("func main() { return sizeof x; }", Loc(28, 29)),
+        ]
+        .iter()
+        {
+            assert_eq!(
+                gen(source_code),
+                Err(IRError::Type(TypeError::UndefinedVariable {
+                    name: "x".to_string(),
+                    loc: *loc
+                }))
+            );
+        }
+        assert_eq!(
+            gen("func main() { return f(1, 2, 3, 4, 5, 6, 7); }"),
+            Err(IRError::TooManyArgs {
+                max: 6,
+                loc: Loc(21, 22)
+            })
+        );
+    }
+
+    #[test]
+    fn test_trap_as_value() {
+        let source_code = "func main() { return __builtin_trap() + 1; }";
+        assert_eq!(
+            gen_ir_of(source_code).map(drop),
+            Err(IRError::Type(TypeError::NotExpression(Loc(21, 35))))
+        );
+    }
+
+    #[test]
+    fn test_deref_non_pointer() {
+        let gen = |source_code: &str| gen_ir_of(source_code).map(drop);
+        assert_eq!(
+            gen("func main() { let a: u64 = 1; *a = 1; }"),
+            Err(IRError::Type(TypeError::InvalidOperand {
+                op: UniOpKind::Deref,
+                ty: Type::U64,
+                loc: Loc(31, 32)
+            }))
+        );
+        assert_eq!(
+            gen("func main() { return *1; }"),
+            Err(IRError::Type(TypeError::InvalidOperand {
+                op: UniOpKind::Deref,
+                ty: Type::U64,
+                loc: Loc(22, 23)
+            }))
+        );
+    }
+
+    #[test]
+    fn test_const() {
+        let source_code = "func main() { const x: u64 = 1; return x + 1; }";
+        assert_eq!(gen_ir_of(source_code).map(drop), Ok(()));
+
+        let source_code = "func main() { const x: u64 = 1; x = 2; return x; }";
+        assert_eq!(
+            gen_ir_of(source_code).map(drop),
+            Err(IRError::AssignToConst {
+                name: "x".to_string(),
+                loc: Loc(32, 33)
+            })
+        );
+    }
+
+    #[test]
+    fn test_integer_promotion() {
+        let source_code = "func main() { let c: u8 = 1; if c == 1 { return 1; } return 0; }";
+        let ir_generator = gen_ir_of(source_code).unwrap();
+
+        assert_eq!(
+            ir_generator.funcs[0].ir_vec[6..12],
+            [
+                IR::new(IROp::BpOffset, Some(3), Some(8)),
+                IR::new(IROp::Load, Some(3), Some(3)),
+                IR::new(IROp::Zext(Type::U8), Some(3), None),
+                IR::new(IROp::Imm, Some(4), Some(1)),
+                IR::new(IROp::Eq, Some(3), Some(4)),
+                IR::new(IROp::Kill, Some(4), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_if_else() {
+        let source_code =
+            "func main() { if 1 { if 2 { return 1; } } else { return 2; } return 3; }";
+        let ir_generator = gen_ir_of(source_code).unwrap();
+
+        let control: Vec<IROp> = ir_generator.funcs[0]
+            .ir_vec
+            .iter()
+            .filter(|ir| {
+                matches!(ir.op, IROp::Cond | IROp::Label(_))
+                    || ir.op == IROp::Jmp("main..end1".to_string())
+            })
+            .map(|ir| ir.op.clone())
+            .collect();
+        assert_eq!(
+            control,
+            [
+                IROp::Cond,
+                IROp::Cond,
+                IROp::Label("else".to_string()),
+                IROp::Jmp("main..end1".to_string()),
+                IROp::Label("else".to_string()),
+                IROp::Label("end".to_string()),
+            ]
+        );
+        let labels: Vec<Option<usize>> = ir_generator.funcs[0]
+            .ir_vec
+            .iter()
+            .filter(|ir| matches!(ir.op, IROp::Cond | IROp::Label(_)))
+            .map(|ir| match ir.op {
+                IROp::Cond => ir.rhs,
+                _ => ir.lhs,
+            })
+            .collect();
+        assert_eq!(labels, [Some(1), Some(2), Some(2), Some(1), Some(1)]);
+    }
+
+    #[test]
+    fn test_unary_plus() {
+        let gen = |source_code: &str| {
+            let mut ir_generator = gen_ir_of(source_code).unwrap();
+            ir_generator.funcs.remove(0).ir_vec
+        };
+        assert_eq!(
+            gen("func f(a: u64) { return +a; }"),
+            gen("func f(a: u64) { return a; }")
+        );
+    }
+
+    #[test]
+    fn test_intrinsic() {
+        fn answer(func: &mut Function, _args: &[Ast], _loc: Loc) -> Option<usize> {
+            let reg = func.new_reg();
+            func.ir_vec.push(IR::new(IROp::Imm, reg, Some(42)));
+            reg
+        }
+
+        let source_code = "func main() { __builtin_trap(); return answer(1); }";
+        let mut ir_generator = IRGenerator::new();
+        ir_generator.register_intrinsic("answer", answer);
+        let ir_generator = gen_ir_with(source_code, ir_generator).unwrap();
+
+        assert_eq!(
+            ir_generator.funcs[0].ir_vec,
+            vec![
+                IR::new(IROp::Trap, None, None),
+                IR::new(IROp::Imm, Some(1), Some(42)),
+                IR::new(IROp::Return, Some(1), None),
+                IR::new(IROp::Kill, Some(1), None),
+                IR::new(IROp::Jmp("return_main".to_string()), None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_switch() {
+        let source_code = "func main() { switch 2 { case 1: return 10; case 2: break; default: return 30; } return 0; }";
+        let ir_generator = gen_ir_of(source_code).unwrap();
+
+        assert_eq!(
+            ir_generator.funcs[0].ir_vec,
+            ir![
+                imm(1, 2),
+                imm(2, 1),
+                eq(2, 1),
+                branch(2, "main..case2"),
+                kill(2),
+                imm(3, 2),
+                eq(3, 1),
+                branch(3, "main..case3"),
+                kill(3),
+                kill(1),
+                jmp("main..default1"),
+                label("case", 2),
+                imm(4, 10),
+                ret(4),
+                kill(4),
+                jmp("return_main"),
+                label("case", 3),
+                jmp("main..end1"),
+                label("default", 1),
+                imm(5, 30),
+                ret(5),
+                kill(5),
+                jmp("return_main"),
+                label("end", 1),
+                imm(6, 0),
+                ret(6),
+                kill(6),
+                jmp("return_main"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_misplaced_break() {
+        let source_code = "func main() { break; return 0; }";
+        assert_eq!(
+            gen_ir_of(source_code).map(drop),
+            Err(IRError::MisplacedBreak { loc: Loc(14, 19) })
+        );
+    }
+
+    #[test]
+    fn test_literal_too_wide() {
+        let target = Target {
+            int_size: 4,
+            ..Target::x86_64_sysv()
+        };
+        let gen = |source_code: &str| {
+            gen_ir_with(source_code, IRGenerator::with_target(target)).map(drop)
+        };
+        assert_eq!(
+            gen("func main() { return 4294967296; }"),
+            Err(IRError::LiteralTooWide {
+                value: 4294967296,
+                loc: Loc(21, 31)
+            })
+        );
+        assert!(gen("func main() { return 4294967295; }").is_ok());
+        assert!(gen("func main() { return -2147483648; }").is_ok());
+        // Folded negative literals are accepted as two's complement.
+        let target = Target {
+            int_size: 4,
+            ..Target::x86_64_sysv()
+        };
+        assert!(target.fits_int(-2147483648i64 as usize));
+        assert!(!target.fits_int(-2147483649i64 as usize));
+    }
+
+    fn control_flow(source_code: &str) -> Vec<IROp> {
+        let ir_generator = gen_ir_of(source_code).unwrap();
+        ir_generator.funcs[0]
+            .ir_vec
+            .iter()
+            .filter(|ir| matches!(ir.op, IROp::Cond | IROp::Label(_) | IROp::Jmp(_)))
+            .map(|ir| ir.op.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_continue_in_for() {
+        assert_eq!(
+            control_flow(
+                "func main() { for (let i: u64 = 0; i < 3; i = i + 1) { continue; } return 0; }"
+            ),
+            [
+                IROp::Label("begin".to_string()),
+                IROp::Cond,
+                IROp::Jmp("main..step1".to_string()),
+                IROp::Label("step".to_string()),
+                IROp::Jmp("main..begin1".to_string()),
+                IROp::Label("else".to_string()),
+                IROp::Jmp("return_main".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_continue_in_while() {
+        assert_eq!(
+            control_flow("func main() { while 1 { continue; } return 0; }"),
+            [
+                IROp::Label("begin".to_string()),
+                IROp::Cond,
+                IROp::Jmp("main..begin1".to_string()),
+                IROp::Jmp("main..begin1".to_string()),
+                IROp::Label("else".to_string()),
+                IROp::Jmp("return_main".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_loop_else() {
+        // `break` jumps over the else clause, while the failed condition enters it.
+        assert_eq!(
+            control_flow("func main() { while 1 { break; } else { return 1; } return 0; }"),
+            [
+                IROp::Label("begin".to_string()),
+                IROp::Cond,
+                IROp::Jmp("main..end1".to_string()),
+                IROp::Jmp("main..begin1".to_string()),
+                IROp::Label("else".to_string()),
+                IROp::Jmp("return_main".to_string()),
+                IROp::Label("end".to_string()),
+                IROp::Jmp("return_main".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tail_call() {
+        let calls = |source_code: &str| -> Vec<IROp> {
+            let ir_generator = gen_ir_of(source_code).unwrap();
+            ir_generator.funcs[0]
+                .ir_vec
+                .iter()
+                .filter(|ir| matches!(ir.op, IROp::FuncCall(_) | IROp::TailCall(_)))
+                .map(|ir| ir.op.clone())
+                .collect()
+        };
+        assert_eq!(
+            calls("func f(x: u64) { return f(x); }"),
+            [IROp::TailCall("f".to_string())]
+        );
+        assert_eq!(
+            calls("func f(x: u64) { return f(x) + 1; }"),
+            [IROp::FuncCall("f".to_string())]
+        );
+        assert_eq!(
+            calls("func f(x: u64) { return g(f(x)); }"),
+            [
+                IROp::FuncCall("f".to_string()),
+                IROp::TailCall("g".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_discarded_expr_stmt() {
+        let source_code = "func f(a: u64, b: u64) { a + b; return 0; }";
+        let ir_generator = gen_ir_of(source_code).unwrap();
+        assert_eq!(
+            ir_generator.funcs[0].ir_vec[6..13],
+            ir![
+                bp_offset(3, 8),
+                load(3, 3),
+                bp_offset(4, 16),
+                load(4, 4),
+                add(3, 4),
+                kill(4),
+                kill(3),
+            ]
+        );
+        assert_eq!(ir_generator.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_variadic_call() {
+        let source_code = r#"extern func printf(fmt: u8*, ...);
+            func main() { printf("%d", 1); return f(2); }"#;
+        let ir_generator = gen_ir_of(source_code).unwrap();
+        // The declaration generates no function.
+        assert_eq!(ir_generator.funcs.len(), 1);
+        let calls: Vec<&IR> = ir_generator.funcs[0]
+            .ir_vec
+            .iter()
+            .filter(|ir| matches!(ir.op, IROp::FuncCall(_) | IROp::TailCall(_)))
+            .collect();
+        // Only the variadic call tells the number of vector registers.
+        assert_eq!(calls[0].op, IROp::FuncCall("printf".to_string()));
+        assert_eq!(calls[0].rhs, Some(0));
+        assert_eq!(calls[1].rhs, None);
+    }
+
+    #[test]
+    fn test_min_max() {
+        let source_code = "func f(a: u64, b: u64) { return min(a, b) + max(a, b); }";
+        let ir_generator = gen_ir_of(source_code).unwrap();
+        let ir_vec = &ir_generator.funcs[0].ir_vec;
+
+        // `min(a, b)` selects a register in place, not by a jump.
+        let min = ir_vec.iter().position(|ir| ir.op == IROp::Min).unwrap();
+        assert_eq!(ir_vec[min], IR::new(IROp::Min, Some(3), Some(4)));
+        assert_eq!(ir_vec[min + 1], IR::new(IROp::Kill, Some(4), None));
+        assert!(!ir_vec
+            .iter()
+            .any(|ir| matches!(ir.op, IROp::Cond | IROp::Branch(_) | IROp::Label(_))));
+        assert!(ir_vec.iter().any(|ir| ir.op == IROp::Max));
+        assert_eq!(ir_generator.validate(), Ok(()));
+
+        // Both of them take exactly two arguments.
+        let gen = |source_code: &str| gen_ir_of(source_code).map(drop);
+        for (source_code, name, found) in [
+            ("func main() { return min(1); }", "min", 1),
+            ("func main() { return max(1, 2, 3); }", "max", 3),
+            ("func main() { return min(); }", "min", 0),
+        ]
+        .iter()
+        {
+            assert_eq!(
+                gen(source_code),
+                Err(IRError::ArgCountMismatch {
+                    name: name.to_string(),
+                    expected: 2,
+                    found: *found,
+                    loc: Loc(21, 24)
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_logical_value() {
+        let source_code = "func f(a: u64, c: u64) { let b: u64 = a && c; return b; }";
+        let ir_generator = gen_ir_of(source_code).unwrap();
+        let ir_vec = &ir_generator.funcs[0].ir_vec;
+
+        // Either operand being zero jumps to `else`, where `0` is loaded.
+        let conds: Vec<_> = ir_vec.iter().filter(|ir| ir.op == IROp::Cond).collect();
+        assert_eq!(conds.len(), 2);
+        assert!(conds.iter().all(|ir| ir.rhs == Some(1)));
+        let imms: Vec<_> = ir_vec.iter().filter(|ir| ir.op == IROp::Imm).collect();
+        assert_eq!(imms.len(), 2);
+        assert_eq!(imms[0].rhs, Some(1));
+        assert_eq!(imms[1].rhs, Some(0));
+        assert_eq!(imms[0].lhs, imms[1].lhs);
+        let else_label = IR::new(IROp::Label("else".to_string()), Some(1), None);
+        let else_pos = ir_vec.iter().position(|ir| *ir == else_label).unwrap();
+        assert_eq!(ir_vec[else_pos + 1], *imms[1]);
+        // The boolean is stored into `b`.
+        assert!(ir_vec.contains(&IR::new(IROp::Store, Some(3), imms[0].lhs)));
+        assert_eq!(ir_generator.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_gen_expr() {
+        let mut lexer = Lexer::new("1 + 2 * 3");
+        let tokens = lexer.lex().unwrap();
+        let mut parser = Parser::new(tokens).allow_trailing_expr();
+        let asts = parser.parse().unwrap();
+        let expr = match &asts[0].value {
+            Return { expr } => expr,
+            _ => unreachable!(),
+        };
+        let mut func = Function::new();
+        let reg = func.gen_expr(expr);
+        assert_eq!(
+            func.ir_vec,
+            vec![
+                IR::new(IROp::Imm, Some(1), Some(1)),
+                IR::new(IROp::Imm, Some(2), Some(2)),
+                IR::new(IROp::Imm, Some(3), Some(3)),
+                IR::new(IROp::Mul, Some(2), Some(3)),
+                IR::new(IROp::Kill, Some(3), None),
+                IR::new(IROp::Add, Some(1), Some(2)),
+                IR::new(IROp::Kill, Some(2), None),
+            ]
+        );
+        // The sum is accumulated into the register of the left operand.
+        assert_eq!(reg, 1);
+    }
+
+    #[test]
+    fn test_stack_slot() {
+        let source_code =
+            "func f(a: u64) { let b: u64 = a; { let c: u64 = b; return a + b + c; } }";
+        let ir_generator = gen_ir_of(source_code).unwrap();
+        let mut offsets: Vec<usize> = ir_generator.funcs[0]
+            .ir_vec
+            .iter()
+            .filter(|ir| ir.op == IROp::BpOffset)
+            .map(|ir| ir.rhs.unwrap())
+            .collect();
+        offsets.sort_unstable();
+        offsets.dedup();
+        assert_eq!(offsets, [8, 16, 24]);
+
+        // An inner scope which wrongly starts from offset 0 reuses the slot of `a`.
+        let mut func = Function::new();
+        func.env.push_front(Env::new(0));
+        func.env
+            .front_mut()
+            .unwrap()
+            .add("a".to_string(), 8, Type::U64, false);
+        func.env.push_front(Env::new(0));
+        let decl = Ast::decl(
+            Ast::variable("b".to_string(), Loc(0, 1)),
+            Some(Ast::num(1, Loc(2, 3))),
+            Type::U64,
+            false,
+            false,
+            false,
+            Loc(0, 3),
+        );
+        func.gen_node(&decl);
+        assert_eq!(
+            func.errors,
+            [IRError::StackSlotCollision {
+                offset: 8,
+                first: "a".to_string(),
+                second: "b".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_invalid_decl_target() {
+        let mut func = Function::new();
+        func.env.push_front(Env::new(0));
+        let decl = Ast::decl(
+            Ast::num(1, Loc(0, 1)),
+            Some(Ast::num(2, Loc(2, 3))),
+            Type::U64,
+            false,
+            false,
+            false,
+            Loc(0, 3),
+        );
+        func.gen_node(&decl);
+        assert_eq!(func.errors, [IRError::InvalidDeclTarget { loc: Loc(0, 1) }]);
+        assert!(func.ir_vec.is_empty());
+    }
+
+    #[test]
+    fn test_post_increment() {
+        let source_code = "func f(a: u64) { return a++; }";
+        let ir_generator = gen_ir_of(source_code).unwrap();
+
+        assert_eq!(
+            ir_generator.funcs[0].ir_vec,
+            ir![
+                bp_offset(1, 8),
+                load_param(0, 1),
+                kill(1),
+                // The old value stays in register 2 and is returned.
+                bp_offset(2, 8),
+                load(2, 2),
+                // The variable is loaded once and copied to be updated.
+                mov(3, 2),
+                imm(4, 1),
+                add(3, 4),
+                kill(4),
+                bp_offset(5, 8),
+                store(5, 3),
+                kill(5),
+                kill(3),
+                ret(2),
+                kill(2),
+                jmp("return_f"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pointer_arithmetic() {
+        let gen = |source_code: &str| {
+            let mut ir_generator = gen_ir_of(source_code).unwrap();
+            ir_generator.funcs.remove(0).ir_vec
+        };
+        assert_eq!(
+            gen("func f(p: u64*) { return p + 1; }"),
+            ir![
+                bp_offset(1, 8),
+                load_param(0, 1),
+                kill(1),
+                bp_offset(2, 8),
+                load(2, 2),
+                imm(3, 1),
+                // The offset is scaled by the size of `u64`.
+                imm(4, 8),
+                mul(3, 4),
+                kill(4),
+                add(2, 3),
+                kill(3),
+                ret(2),
+                kill(2),
+                jmp("return_f"),
+            ]
+        );
+        // `p++` and `p--` step by an element as well.
+        assert_eq!(
+            gen("func f(p: u64*) { return p--; }")[6..11],
+            ir![imm(4, 1), imm(5, 8), mul(4, 5), kill(5), sub(3, 4)]
+        );
+        // Elements of `u8` need no scaling.
+        assert!(!gen("func f(p: u8*) { return p++; }")
+            .iter()
+            .any(|ir| ir.op == IROp::Mul));
+        assert!(!gen("func f(p: u8*) { return p + 1; }")
+            .iter()
+            .any(|ir| ir.op == IROp::Mul));
+        // `sizeof` yields the size of the operand's type without evaluating it.
+        assert_eq!(
+            gen("func f(p: u8*) { return sizeof p; }"),
+            ir![
+                bp_offset(1, 8),
+                load_param(0, 1),
+                kill(1),
+                imm(2, 8),
+                ret(2),
+                kill(2),
+                jmp("return_f"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_array() {
+        // `sizeof` an array counts all of its elements.
+        let ir_generator =
+            gen_ir_of("func main() { let a: u64[10]; let b: u8[10]; return sizeof a + sizeof b; }")
+                .unwrap();
+        assert_eq!(
+            ir_generator.funcs[0].ir_vec,
+            ir![
+                imm(1, 80),
+                imm(2, 10),
+                add(1, 2),
+                kill(2),
+                ret(1),
+                kill(1),
+                jmp("return_main"),
+            ]
+        );
+        // Ten bytes are padded to a multiple of 8.
+        assert_eq!(ir_generator.funcs[0].stack_size, 96);
+
+        // An array is not loaded but used as the address of its first element.
+        let ir_generator = gen_ir_of("func main() { let a: u64[2]; return *(a + 1); }").unwrap();
+        assert_eq!(
+            ir_generator.funcs[0].ir_vec[..6],
+            ir![
+                bp_offset(1, 16),
+                imm(2, 1),
+                imm(3, 8),
+                mul(2, 3),
+                kill(3),
+                add(1, 2)
+            ]
+        );
+
+        let gen = |source_code: &str| gen_ir_of(source_code).map(drop);
+        assert_eq!(
+            gen("func main() { let a: u64[2]; a = 1; return 0; }"),
+            Err(IRError::AssignToArray { loc: Loc(29, 30) })
+        );
+        assert_eq!(
+            gen("func main() { static a: u64[2]; return 0; }"),
+            Err(IRError::StaticArray { loc: Loc(21, 22) })
+        );
+    }
+
+    #[test]
+    fn test_goto_undefined_label() {
+        let source_code = "func main() { goto nowhere; return 0; }";
+        assert_eq!(
+            gen_ir_of(source_code).map(drop),
+            Err(IRError::UndefinedLabel {
+                name: "nowhere".to_string(),
+                loc: Loc(19, 26)
+            })
+        );
+    }
+
+    #[test]
+    fn test_duplicate_label() {
+        let source_code = "func main() { end: return 1; end: return 2; }";
+        assert_eq!(
+            gen_ir_of(source_code).map(drop),
+            Err(IRError::DuplicateLabel {
+                name: "end".to_string(),
+                loc: Loc(29, 42)
+            })
+        );
+    }
 }
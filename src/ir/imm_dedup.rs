@@ -0,0 +1,287 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ir::gen_ir::{Function, IROp};
+
+impl Function {
+    /// Reuse a register which already holds the same immediate inside a basic block
+    /// instead of loading it again with another `Imm`.
+    /// The reused register lives until the last use of the removed one,
+    /// so it is reused only while no more than `register_count` registers are live at once.
+    pub fn dedup_immediates(&mut self, register_count: usize) {
+        // key: immediate value, value: virtual register holding it
+        let mut cache: HashMap<usize, usize> = HashMap::new();
+        // key: register of a removed `Imm`, value: register used instead
+        let mut renames: HashMap<usize, usize> = HashMap::new();
+        // Indices of removed `Imm`.
+        let mut removed = HashSet::new();
+
+        for i in 0..self.ir_vec.len() {
+            let (has_lhs_reg, has_rhs_reg) = Function::reg_operands(&self.ir_vec[i].op);
+            let ir = &mut self.ir_vec[i];
+            if has_lhs_reg {
+                ir.lhs = ir.lhs.map(|reg| *renames.get(&reg).unwrap_or(&reg));
+            }
+            if has_rhs_reg {
+                ir.rhs = ir.rhs.map(|reg| *renames.get(&reg).unwrap_or(&reg));
+            }
+
+            let ir = &self.ir_vec[i];
+            match ir.op {
+                IROp::Imm => {
+                    let (reg, n) = (ir.lhs.unwrap(), ir.rhs.unwrap());
+                    match cache.get(&n) {
+                        Some(&cached)
+                            if !self.is_modified_while_live(i, reg, cached, &renames)
+                                && self.can_extend(i, cached, register_count, &renames) =>
+                        {
+                            renames.insert(reg, cached);
+                            removed.insert(i);
+                        }
+                        _ => {
+                            cache.retain(|_, cached| *cached != reg);
+                            cache.insert(n, reg);
+                        }
+                    }
+                }
+                // The end of a basic block.
+                IROp::Label(_)
+                | IROp::Jmp(_)
+                | IROp::Cond
+                | IROp::Branch(_)
+                | IROp::BranchOverflow(_)
+                | IROp::Return
+                | IROp::FuncCall(_)
+                | IROp::TailCall(_) => cache.clear(),
+                _ if Function::modifies_lhs(&ir.op) => {
+                    let reg = ir.lhs.unwrap();
+                    cache.retain(|_, cached| *cached != reg);
+                }
+                _ => (),
+            }
+        }
+
+        let mut ir_vec: Vec<_> = self
+            .ir_vec
+            .drain(..)
+            .enumerate()
+            .filter(|(i, _)| !removed.contains(i))
+            .map(|(_, ir)| ir)
+            .collect();
+        // A shared register is killed more than once. Keep only the last one.
+        let mut killed = HashSet::new();
+        for i in (0..ir_vec.len()).rev() {
+            if ir_vec[i].op == IROp::Kill && !killed.insert(ir_vec[i].lhs) {
+                ir_vec.remove(i);
+            }
+        }
+        self.ir_vec = ir_vec;
+    }
+
+    /// Whether `reg` or `cached` is modified in place between the `Imm` at `index`
+    /// and the `Kill` of `reg`.
+    fn is_modified_while_live(
+        &self,
+        index: usize,
+        reg: usize,
+        cached: usize,
+        renames: &HashMap<usize, usize>,
+    ) -> bool {
+        for ir in &self.ir_vec[index + 1..] {
+            if ir.op == IROp::Kill && ir.lhs == Some(reg) {
+                break;
+            }
+            if Function::modifies_lhs(&ir.op) {
+                let lhs = ir.lhs.unwrap();
+                let lhs = *renames.get(&lhs).unwrap_or(&lhs);
+                if lhs == reg || lhs == cached {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Whether `cached` can live until `index` without more than `register_count`
+    /// registers live at once. A register lives from its first use to its last `Kill`,
+    /// or to the end of the function if it is not killed last.
+    fn can_extend(
+        &self,
+        index: usize,
+        cached: usize,
+        register_count: usize,
+        renames: &HashMap<usize, usize>,
+    ) -> bool {
+        // key: register, value: first and last index where it appears
+        let mut ranges: HashMap<usize, (usize, usize)> = HashMap::new();
+        let mut killed_last = HashSet::new();
+        for (i, ir) in self.ir_vec.iter().enumerate() {
+            let (has_lhs_reg, has_rhs_reg) = Function::reg_operands(&ir.op);
+            let regs = [(has_lhs_reg, ir.lhs), (has_rhs_reg, ir.rhs)];
+            for &(_, reg) in regs.iter().filter(|(is_reg, _)| *is_reg) {
+                let reg = reg.unwrap();
+                let reg = *renames.get(&reg).unwrap_or(&reg);
+                ranges.entry(reg).or_insert((i, i)).1 = i;
+                if ir.op == IROp::Kill {
+                    killed_last.insert(reg);
+                } else {
+                    killed_last.remove(&reg);
+                }
+            }
+        }
+        let len = self.ir_vec.len();
+        let end = |reg: &usize, last: usize| if killed_last.contains(reg) { last } else { len };
+        let cached_end = end(&cached, ranges[&cached].1);
+        (cached_end + 1..index).all(|i| {
+            let live = ranges
+                .iter()
+                .filter(|&(reg, &(first, last))| {
+                    *reg != cached && first <= i && i <= end(reg, last)
+                })
+                .count();
+            live < register_count
+        })
+    }
+
+    /// Whether an operation writes into the register of its `lhs`.
+    pub(super) fn modifies_lhs(op: &IROp) -> bool {
+        matches!(
+            op,
+            IROp::Imm
+                | IROp::Mov
+                | IROp::Add
+                | IROp::Sub
+                | IROp::Mul
+                | IROp::Div
+                | IROp::Rem
+                | IROp::BitAnd
+                | IROp::BitOr
+                | IROp::BitXor
+                | IROp::Shl
+                | IROp::Shr
+                | IROp::Eq
+                | IROp::Ne
+                | IROp::Lt
+                | IROp::Le
+                | IROp::Gt
+                | IROp::Ge
+                | IROp::Min
+                | IROp::Max
+                | IROp::Neg
+                | IROp::Cast(_)
+                | IROp::Zext(_)
+                | IROp::BpOffset
+                | IROp::StrAddr
+                | IROp::GlobalAddr
+                | IROp::FuncCall(_)
+                | IROp::TailCall(_)
+                | IROp::Load
+        )
+    }
+
+    /// Whether `lhs` and `rhs` of an operation are registers respectively.
+    pub(super) fn reg_operands(op: &IROp) -> (bool, bool) {
+        match op {
+            IROp::Imm
+            | IROp::Neg
+            | IROp::Cast(_)
+            | IROp::Zext(_)
+            | IROp::BpOffset
+            | IROp::StrAddr
+            | IROp::GlobalAddr
+            | IROp::Cond
+            | IROp::Branch(_)
+            | IROp::Return
+            | IROp::FuncCall(_)
+            | IROp::TailCall(_)
+            | IROp::Kill => (true, false),
+            IROp::LoadParam | IROp::StoreArg => (false, true),
+            IROp::Mov
+            | IROp::Add
+            | IROp::Sub
+            | IROp::Mul
+            | IROp::Div
+            | IROp::Rem
+            | IROp::BitAnd
+            | IROp::BitOr
+            | IROp::BitXor
+            | IROp::Shl
+            | IROp::Shr
+            | IROp::Eq
+            | IROp::Ne
+            | IROp::Lt
+            | IROp::Le
+            | IROp::Gt
+            | IROp::Ge
+            | IROp::Min
+            | IROp::Max
+            | IROp::Store
+            | IROp::Load => (true, true),
+            IROp::Label(_) | IROp::Jmp(_) | IROp::BranchOverflow(_) | IROp::Trap => (false, false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ir::gen_ir::*;
+    use crate::ir::testutil::gen_ir_of;
+    use crate::target::Target;
+
+    fn dedup(source_code: &str) -> Function {
+        let mut ir_generator = gen_ir_of(source_code).unwrap();
+        ir_generator.dedup_immediates();
+        ir_generator.funcs.remove(0)
+    }
+
+    #[test]
+    fn test_shared_immediate() {
+        let func = dedup("func f(a: u64, b: u64) { return (a + 1) * (b + 1); }");
+        assert_eq!(
+            func.ir_vec[6..],
+            ir![
+                bp_offset(3, 8),
+                load(3, 3),
+                imm(4, 1),
+                add(3, 4),
+                bp_offset(5, 16),
+                load(5, 5),
+                add(5, 4),
+                kill(4),
+                mul(3, 5),
+                kill(5),
+                ret(3),
+                kill(3),
+                jmp("return_f"),
+            ]
+        );
+        assert_eq!(func.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_modified_immediate() {
+        // `1 + b` adds into the register of `1`, so it cannot be shared.
+        let func = dedup("func f(a: u64, b: u64) { return (a + 1) * (1 + b); }");
+        let imms = func.ir_vec.iter().filter(|ir| ir.op == IROp::Imm).count();
+        assert_eq!(imms, 2);
+        assert_eq!(func.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_basic_block() {
+        let func = dedup("func f(a: u64) { if a { return 1; } return 1; }");
+        let imms = func.ir_vec.iter().filter(|ir| ir.op == IROp::Imm).count();
+        assert_eq!(imms, 2);
+    }
+
+    #[test]
+    fn test_register_pressure() {
+        // Sharing every immediate of the first half keeps nine registers live.
+        let adds: String = (1..10).map(|n| format!("a = a + {}; ", n)).collect();
+        let func = dedup(&format!("func f(a: u64) {{ {}{}return a; }}", adds, adds));
+        let imms = func.ir_vec.iter().filter(|ir| ir.op == IROp::Imm).count();
+        assert!(9 < imms && imms < 18);
+        assert_eq!(func.validate(), Ok(()));
+        let mut func = func;
+        func.reg_alloc(&Target::default());
+    }
+}
@@ -0,0 +1,45 @@
+use std::collections::HashSet;
+
+use crate::ir::gen_ir::{Function, IROp};
+
+impl Function {
+    /// Remove a `Kill` of a register which has not been defined again since it was killed.
+    /// Register allocation frees the real register on each `Kill` in the order of `ir_vec`,
+    /// so a repeated one could free a register already given to another value.
+    pub fn remove_redundant_kills(&mut self) {
+        let mut dead = HashSet::new();
+        self.ir_vec.retain(|ir| {
+            if ir.op == IROp::Kill {
+                return dead.insert(ir.lhs);
+            }
+            if Function::modifies_lhs(&ir.op) {
+                dead.remove(&ir.lhs);
+            }
+            true
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ir::gen_ir::*;
+
+    #[test]
+    fn test_doubled_kill() {
+        let mut func = Function::new();
+        func.ir_vec = ir![
+            imm(1, 2),
+            kill(1),
+            kill(1),
+            imm(1, 3),
+            ret(1),
+            kill(1),
+            kill(2)
+        ];
+        func.remove_redundant_kills();
+        assert_eq!(
+            func.ir_vec,
+            ir![imm(1, 2), kill(1), imm(1, 3), ret(1), kill(1), kill(2)]
+        );
+    }
+}
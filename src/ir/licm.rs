@@ -0,0 +1,372 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use crate::ir::cfg::{build_cfg, Cfg};
+use crate::ir::gen_ir::{Function, IROp, IR};
+
+impl Function {
+    /// Move computations of a loop whose operands are all defined outside of it
+    /// into the preheader, just before the label of the loop header.
+    /// Only pure arithmetic is moved, from loops which lie contiguously in `ir_vec`
+    /// and are entered by falling into the header as `while` and `for` are generated.
+    /// Hoisted registers live throughout the loop, so only as many are hoisted
+    /// as fit in `register_count` together with those the loop uses at its peak.
+    pub fn hoist_loop_invariants(&mut self, register_count: usize) {
+        while self.hoist_first_loop(register_count) {}
+    }
+
+    /// Hoist invariants out of the first loop which has any. Return whether `ir_vec` changed.
+    fn hoist_first_loop(&mut self, register_count: usize) -> bool {
+        let cfg = build_cfg(&self.ir_vec);
+        for (tail, block) in cfg.blocks.iter().enumerate() {
+            for &header in block.succs.iter().filter(|&&header| header <= tail) {
+                let (start, end) = match loop_range(&self.ir_vec, &cfg, header, tail) {
+                    Some(range) => range,
+                    None => continue,
+                };
+                let regs = invariant_regs(&self.ir_vec, &cfg, start, end, register_count);
+                if !regs.is_empty() {
+                    self.hoist(start, end, &regs);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Move definitions of `regs` in `ir_vec[start..end]` before `start`,
+    /// and kill them after `end` so that they live throughout the loop.
+    fn hoist(&mut self, start: usize, end: usize, regs: &BTreeSet<usize>) {
+        let is_hoisted = |ir: &IR| {
+            (Function::modifies_lhs(&ir.op) || ir.op == IROp::Kill)
+                && regs.contains(&ir.lhs.unwrap())
+        };
+        let body: Vec<IR> = self.ir_vec.splice(start..end, Vec::new()).collect();
+        let (hoisted, body): (Vec<IR>, Vec<IR>) = body.into_iter().partition(is_hoisted);
+        let defs = hoisted.into_iter().filter(|ir| ir.op != IROp::Kill);
+        let kills = regs.iter().map(|&reg| IR::new(IROp::Kill, Some(reg), None));
+        let loop_ir: Vec<IR> = defs.chain(body).chain(kills).collect();
+        self.ir_vec.splice(start..start, loop_ir);
+    }
+}
+
+/// Range of `ir_vec` from the block `header` to the block `tail`, which jumps back to `header`.
+/// `None` if the loop can be entered other than by falling into the label of `header`.
+fn loop_range(ir_vec: &[IR], cfg: &Cfg, header: usize, tail: usize) -> Option<(usize, usize)> {
+    let blocks = &cfg.blocks;
+    let is_inside = |block: usize| header <= block && block <= tail;
+    if !matches!(ir_vec[blocks[header].start].op, IROp::Label(_)) {
+        return None;
+    }
+    let enters_body = (header + 1..=tail).any(|b| blocks[b].preds.iter().any(|&p| !is_inside(p)));
+    let jumps_to_header = blocks[header].preds.iter().any(|&p| {
+        !is_inside(p)
+            && (p + 1 != header
+                || matches!(
+                    ir_vec[blocks[p].end - 1].op,
+                    IROp::Jmp(_) | IROp::Cond | IROp::Branch(_) | IROp::BranchOverflow(_)
+                ))
+    });
+    if enters_body || jumps_to_header {
+        return None;
+    }
+    Some((blocks[header].start, blocks[tail].end))
+}
+
+/// Registers which can be computed once before the loop of `ir_vec[start..end]`.
+/// Each of them appears only in one block of the loop, where it is defined by `Imm` or `Mov`
+/// first and then modified only by pure arithmetic with invariant operands.
+/// Registers without arithmetic are left unless hoisted arithmetic reads them,
+/// since moving them only lengthens their lifetime.
+/// Chains of registers are taken in order while the loop still fits in `register_count`.
+fn invariant_regs(
+    ir_vec: &[IR],
+    cfg: &Cfg,
+    start: usize,
+    end: usize,
+    register_count: usize,
+) -> BTreeSet<usize> {
+    // Indices of IR in the loop where each register appears, except `Kill`.
+    let mut occurrences: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    // Registers which appear out of the loop.
+    let mut outside = HashSet::new();
+    // Registers which are written in the loop.
+    let mut written = HashSet::new();
+    for (i, ir) in ir_vec.iter().enumerate() {
+        let (lhs_is_reg, rhs_is_reg) = Function::reg_operands(&ir.op);
+        let regs = [(lhs_is_reg, ir.lhs), (rhs_is_reg, ir.rhs)];
+        for reg in regs
+            .iter()
+            .filter(|(is_reg, _)| *is_reg)
+            .map(|(_, reg)| reg.unwrap())
+        {
+            if i < start || end <= i {
+                outside.insert(reg);
+            } else if ir.op != IROp::Kill {
+                occurrences.entry(reg).or_default().push(i);
+            }
+        }
+        if start <= i && i < end && Function::modifies_lhs(&ir.op) {
+            written.insert(ir.lhs.unwrap());
+        }
+    }
+
+    // Indices of IR writing each candidate.
+    let mut candidates: BTreeMap<usize, Vec<usize>> = occurrences
+        .into_iter()
+        .filter(|(reg, indices)| {
+            let first = &ir_vec[indices[0]];
+            let block = cfg.block_of(indices[0]);
+            !outside.contains(reg)
+                && matches!(first.op, IROp::Imm | IROp::Mov)
+                && first.lhs == Some(*reg)
+                && indices.iter().all(|&i| cfg.block_of(i) == block)
+        })
+        .map(|(reg, indices)| {
+            let writes = indices
+                .into_iter()
+                .filter(|&i| Function::modifies_lhs(&ir_vec[i].op) && ir_vec[i].lhs == Some(reg))
+                .collect();
+            (reg, writes)
+        })
+        .filter(|(_, writes): &(usize, Vec<usize>)| {
+            writes.iter().all(|&i| is_pure_arith(ir_vec, i))
+        })
+        .collect();
+
+    // Drop candidates reading a register which changes in the loop, until none is dropped.
+    loop {
+        let variant: Vec<usize> = candidates
+            .iter()
+            .filter(|(_, writes)| {
+                writes.iter().any(|&i| {
+                    let (_, rhs_is_reg) = Function::reg_operands(&ir_vec[i].op);
+                    rhs_is_reg
+                        && written.contains(&ir_vec[i].rhs.unwrap())
+                        && !candidates.contains_key(&ir_vec[i].rhs.unwrap())
+                })
+            })
+            .map(|(reg, _)| *reg)
+            .collect();
+        if variant.is_empty() {
+            break;
+        }
+        for reg in variant {
+            candidates.remove(&reg);
+        }
+    }
+
+    // Keep registers with arithmetic and those which they read, as long as they fit.
+    let mut hoisted = BTreeSet::new();
+    let roots = candidates.iter().filter(|(_, writes)| {
+        writes
+            .iter()
+            .any(|&i| !matches!(ir_vec[i].op, IROp::Imm | IROp::Mov))
+    });
+    for (&root, _) in roots {
+        let mut chain = hoisted.clone();
+        let mut stack = vec![root];
+        while let Some(reg) = stack.pop() {
+            if !chain.insert(reg) {
+                continue;
+            }
+            for &i in &candidates[&reg] {
+                let (_, rhs_is_reg) = Function::reg_operands(&ir_vec[i].op);
+                if rhs_is_reg && candidates.contains_key(&ir_vec[i].rhs.unwrap()) {
+                    stack.push(ir_vec[i].rhs.unwrap());
+                }
+            }
+        }
+        if peak_pressure(ir_vec, start, end, &chain) + chain.len() <= register_count {
+            hoisted = chain;
+        }
+    }
+    hoisted
+}
+
+/// Largest number of registers live at once in `ir_vec[start..end]`, except `excluded`.
+/// A register is live from its first appearance to its `Kill` as `reg_alloc` assigns it.
+fn peak_pressure(ir_vec: &[IR], start: usize, end: usize, excluded: &BTreeSet<usize>) -> usize {
+    let mut live = HashSet::new();
+    let mut peak = 0;
+    for (i, ir) in ir_vec[..end].iter().enumerate() {
+        let (lhs_is_reg, rhs_is_reg) = Function::reg_operands(&ir.op);
+        let regs = [(lhs_is_reg, ir.lhs), (rhs_is_reg, ir.rhs)];
+        for &(_, reg) in regs.iter().filter(|(is_reg, _)| *is_reg) {
+            if !excluded.contains(&reg.unwrap()) {
+                live.insert(reg.unwrap());
+            }
+        }
+        if start <= i {
+            peak = peak.max(live.len());
+        }
+        if ir.op == IROp::Kill {
+            live.remove(&ir.lhs.unwrap());
+        }
+    }
+    peak
+}
+
+/// Whether `ir_vec[index]` computes a value from registers without side effects.
+/// Division may trap, and arithmetic checked for overflow must stay with the check.
+fn is_pure_arith(ir_vec: &[IR], index: usize) -> bool {
+    let checked = ir_vec
+        .get(index + 1)
+        .is_some_and(|next| matches!(next.op, IROp::BranchOverflow(_)));
+    !checked
+        && matches!(
+            ir_vec[index].op,
+            IROp::Imm
+                | IROp::Mov
+                | IROp::Add
+                | IROp::Sub
+                | IROp::Mul
+                | IROp::BitAnd
+                | IROp::BitOr
+                | IROp::BitXor
+                | IROp::Shl
+                | IROp::Shr
+                | IROp::Eq
+                | IROp::Ne
+                | IROp::Lt
+                | IROp::Le
+                | IROp::Gt
+                | IROp::Ge
+                | IROp::Min
+                | IROp::Max
+                | IROp::Neg
+                | IROp::Cast(_)
+                | IROp::Zext(_)
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ir::gen_ir::*;
+    use crate::REGISTER_COUNT;
+
+    #[test]
+    fn test_hoist_invariant_mul() {
+        // while (i < 10) { s = s + a * b; i = i + 1; }
+        // with a, b, i and s in registers 1, 2, 3 and 4.
+        let mut func = Function::new();
+        func.ir_vec = ir![
+            imm(1, 3),
+            imm(2, 4),
+            imm(3, 0),
+            imm(4, 0),
+            label("begin", 1),
+            imm(5, 10),
+            mov(6, 3),
+            lt(6, 5),
+            kill(5),
+            cond(6, 1),
+            kill(6),
+            mov(7, 1),
+            mul(7, 2),
+            add(4, 7),
+            kill(7),
+            imm(8, 1),
+            add(3, 8),
+            kill(8),
+            jmp("main..begin1"),
+            label("else", 1),
+            ret(4),
+            jmp("return_main")
+        ];
+        func.hoist_loop_invariants(REGISTER_COUNT);
+        assert_eq!(
+            func.ir_vec,
+            ir![
+                imm(1, 3),
+                imm(2, 4),
+                imm(3, 0),
+                imm(4, 0),
+                mov(7, 1),
+                mul(7, 2),
+                label("begin", 1),
+                imm(5, 10),
+                mov(6, 3),
+                lt(6, 5),
+                kill(5),
+                cond(6, 1),
+                kill(6),
+                add(4, 7),
+                imm(8, 1),
+                add(3, 8),
+                kill(8),
+                jmp("main..begin1"),
+                kill(7),
+                label("else", 1),
+                ret(4),
+                jmp("return_main")
+            ]
+        );
+        assert_eq!(func.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_keep_variant_operand() {
+        // while (i < 10) { s = i * b; i = i + 1; }
+        let mut func = Function::new();
+        func.ir_vec = ir![
+            imm(2, 4),
+            imm(3, 0),
+            label("begin", 1),
+            imm(5, 10),
+            mov(6, 3),
+            lt(6, 5),
+            kill(5),
+            cond(6, 1),
+            kill(6),
+            mov(7, 3),
+            mul(7, 2),
+            kill(7),
+            imm(8, 1),
+            add(3, 8),
+            kill(8),
+            jmp("main..begin1"),
+            label("else", 1)
+        ];
+        let ir_vec = func.ir_vec.clone();
+        func.hoist_loop_invariants(REGISTER_COUNT);
+        assert_eq!(func.ir_vec, ir_vec);
+    }
+
+    #[test]
+    fn test_register_pressure() {
+        let mut func = Function::new();
+        func.ir_vec = ir![
+            imm(1, 3),
+            imm(2, 4),
+            label("begin", 1),
+            mov(3, 1),
+            mul(3, 2),
+            kill(3),
+            jmp("main..begin1")
+        ];
+        let ir_vec = func.ir_vec.clone();
+        // Registers 1, 2 and 3 are live at once in the loop.
+        func.hoist_loop_invariants(2);
+        assert_eq!(func.ir_vec, ir_vec);
+        func.hoist_loop_invariants(3);
+        assert_eq!(func.ir_vec[2], IR::new(IROp::Mov, Some(3), Some(1)));
+
+        // Each `p + k` is invariant, but all of them do not fit in registers.
+        let stores = (0..8)
+            .map(|k| format!("*(p + {}) = i;", k))
+            .collect::<String>();
+        let source = format!(
+            "extern func malloc(n: u64);
+            func main() {{
+                let p: u64* = malloc(64);
+                let i: u64 = 0;
+                while (i < 3) {{ {} i = i + 1; }}
+                return i;
+            }}",
+            stores
+        );
+        let mut ir_generator =
+            crate::compile_with(&source, crate::opt::OptLevel::O2, Default::default()).unwrap();
+        ir_generator.reg_alloc(&crate::target::Target::default());
+    }
+}
@@ -1,2 +1,186 @@
+pub mod cfg;
 pub mod gen_ir;
+pub mod imm_dedup;
+pub mod kill_dedup;
+pub mod licm;
 pub mod reg_alloc;
+pub mod stats;
+#[cfg(test)]
+pub mod testutil;
+pub mod validate;
+
+use crate::parse::infer::TypeError;
+use crate::Loc;
+use std::fmt;
+
+/// How signed overflow of `+`, `-` and `*` behaves at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum OverflowMode {
+    /// The result wraps around.
+    #[default]
+    Wrapping,
+    /// The program traps.
+    Checked,
+}
+
+/// Data type that represents an error found while generating IR.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IRError {
+    UndefinedLabel {
+        name: String,
+        loc: Loc,
+    },
+    /// Label which is defined more than once in a function.
+    DuplicateLabel {
+        name: String,
+        loc: Loc,
+    },
+    AssignToConst {
+        name: String,
+        loc: Loc,
+    },
+    /// Integer literal which does not fit in `int` of the target.
+    LiteralTooWide {
+        value: usize,
+        loc: Loc,
+    },
+    /// `break` which is not inside a loop or a switch.
+    MisplacedBreak {
+        loc: Loc,
+    },
+    /// `continue` which is not inside a loop.
+    MisplacedContinue {
+        loc: Loc,
+    },
+    /// Virtual register which is read without holding a value.
+    UseBeforeDef {
+        reg: usize,
+    },
+    /// Internal error: two live variables are placed at the same offset from rbp.
+    StackSlotCollision {
+        offset: usize,
+        first: String,
+        second: String,
+    },
+    /// Static variable whose initializer is not a constant.
+    NonConstantStatic {
+        loc: Loc,
+    },
+    /// Static variable of an array type, which data cannot hold yet.
+    StaticArray {
+        loc: Loc,
+    },
+    /// Assignment to a whole array rather than to its elements.
+    AssignToArray {
+        loc: Loc,
+    },
+    /// Internal error: a declaration whose left hand side is not a variable.
+    InvalidDeclTarget {
+        loc: Loc,
+    },
+    /// Call with more arguments than registers to pass them.
+    TooManyArgs {
+        max: usize,
+        loc: Loc,
+    },
+    /// Call of an intrinsic with a wrong number of arguments.
+    ArgCountMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+        loc: Loc,
+    },
+    /// Operation on a value of a type which does not allow it, such as `*1`.
+    Type(TypeError),
+}
+
+impl IRError {
+    /// Location of the error, or `None` for an internal error on IR.
+    pub fn loc(&self) -> Option<Loc> {
+        match self {
+            IRError::UndefinedLabel { loc, .. }
+            | IRError::DuplicateLabel { loc, .. }
+            | IRError::AssignToConst { loc, .. }
+            | IRError::LiteralTooWide { loc, .. }
+            | IRError::MisplacedBreak { loc }
+            | IRError::MisplacedContinue { loc }
+            | IRError::NonConstantStatic { loc }
+            | IRError::StaticArray { loc }
+            | IRError::AssignToArray { loc }
+            | IRError::InvalidDeclTarget { loc }
+            | IRError::TooManyArgs { loc, .. }
+            | IRError::ArgCountMismatch { loc, .. } => Some(*loc),
+            IRError::Type(error) => Some(error.loc()),
+            IRError::UseBeforeDef { .. } | IRError::StackSlotCollision { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for IRError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IRError::UndefinedLabel { name, loc } => {
+                write!(f, "{}: Undefined label '{}'", loc, name)
+            }
+            IRError::DuplicateLabel { name, loc } => {
+                write!(f, "{}: Duplicate label '{}'", loc, name)
+            }
+            IRError::AssignToConst { name, loc } => {
+                write!(
+                    f,
+                    "{}: Cannot assign twice to const variable '{}'",
+                    loc, name
+                )
+            }
+            IRError::LiteralTooWide { value, loc } => {
+                write!(f, "{}: Literal {} is too wide for int", loc, value)
+            }
+            IRError::MisplacedBreak { loc } => {
+                write!(f, "{}: 'break' outside of a loop or switch", loc)
+            }
+            IRError::MisplacedContinue { loc } => {
+                write!(f, "{}: 'continue' outside of a loop", loc)
+            }
+            IRError::UseBeforeDef { reg } => {
+                write!(f, "Virtual register {} is used before its definition", reg)
+            }
+            IRError::StackSlotCollision {
+                offset,
+                first,
+                second,
+            } => write!(
+                f,
+                "Internal error: variables '{}' and '{}' share stack offset {}",
+                first, second, offset
+            ),
+            IRError::NonConstantStatic { loc } => write!(
+                f,
+                "{}: Initializer of a static variable must be a constant",
+                loc
+            ),
+            IRError::StaticArray { loc } => {
+                write!(f, "{}: Static array is not supported", loc)
+            }
+            IRError::AssignToArray { loc } => write!(f, "{}: Cannot assign to an array", loc),
+            IRError::TooManyArgs { max, loc } => {
+                write!(f, "{}: More than {} arguments are not supported", loc, max)
+            }
+            IRError::ArgCountMismatch {
+                name,
+                expected,
+                found,
+                loc,
+            } => write!(
+                f,
+                "{}: '{}' takes {} arguments but {} are given",
+                loc, name, expected, found
+            ),
+            IRError::Type(error) => write!(f, "{}", error),
+            IRError::InvalidDeclTarget { loc } => write!(
+                f,
+                "{}: Internal error: declaration of something other than a variable",
+                loc
+            ),
+        }
+    }
+}
@@ -1,30 +1,57 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use crate::ir::gen_ir::{Function, IROp};
-use crate::REGISTER_COUNT;
+use crate::target::Target;
 
 impl Function {
-    pub fn reg_alloc(&mut self) {
+    pub fn reg_alloc(&mut self, target: &Target) {
         // Remember whether each real register is used.
-        let mut is_reg_used = [false; REGISTER_COUNT];
+        let mut is_reg_used = vec![false; target.register_count];
         // Map a virtual register onto a real register.
         // key: virtual register, value: real register
-        let mut reg_map: HashMap<usize, usize> = HashMap::new();
+        // An ordered map keeps the allocation (and its panic dump) reproducible.
+        let mut reg_map: BTreeMap<usize, usize> = BTreeMap::new();
 
         for ir in &mut self.ir_vec {
             match ir.op {
                 IROp::Imm
-                | IROp::Minus
+                | IROp::Neg
+                | IROp::Cast(_)
+                | IROp::Zext(_)
                 | IROp::BpOffset
+                | IROp::StrAddr
+                | IROp::GlobalAddr
                 | IROp::Cond
+                | IROp::Branch(_)
                 | IROp::Return
-                | IROp::FuncCall(_) => {
+                | IROp::FuncCall(_)
+                | IROp::TailCall(_) => {
                     ir.lhs = Function::alloc(ir.lhs, &mut is_reg_used, &mut reg_map)
                 }
                 IROp::LoadParam | IROp::StoreArg => {
                     ir.rhs = Function::alloc(ir.rhs, &mut is_reg_used, &mut reg_map);
                 }
-                IROp::Add | IROp::Sub | IROp::Mul | IROp::Div | IROp::Store | IROp::Load => {
+                IROp::Mov
+                | IROp::Add
+                | IROp::Sub
+                | IROp::Mul
+                | IROp::Div
+                | IROp::Rem
+                | IROp::BitAnd
+                | IROp::BitOr
+                | IROp::BitXor
+                | IROp::Shl
+                | IROp::Shr
+                | IROp::Eq
+                | IROp::Ne
+                | IROp::Lt
+                | IROp::Le
+                | IROp::Gt
+                | IROp::Ge
+                | IROp::Min
+                | IROp::Max
+                | IROp::Store
+                | IROp::Load => {
                     ir.lhs = Function::alloc(ir.lhs, &mut is_reg_used, &mut reg_map);
                     ir.rhs = Function::alloc(ir.rhs, &mut is_reg_used, &mut reg_map);
                 }
@@ -41,17 +68,14 @@ impl Function {
     fn alloc(
         ir_reg: Option<usize>,
         is_reg_used: &mut [bool],
-        reg_map: &mut HashMap<usize, usize>,
+        reg_map: &mut BTreeMap<usize, usize>,
     ) -> Option<usize> {
-        let ir_reg = ir_reg.expect(&format!("{:?}", ir_reg));
+        let ir_reg = ir_reg.unwrap_or_else(|| panic!("{:?}", ir_reg));
         if let Some(real_reg) = reg_map.get(&ir_reg) {
             return Some(*real_reg);
         }
 
-        for i in 0..REGISTER_COUNT {
-            if is_reg_used[i] {
-                continue;
-            }
+        if let Some(i) = find_free(is_reg_used) {
             is_reg_used[i] = true;
             reg_map.insert(ir_reg, i);
             return Some(i);
@@ -64,21 +88,21 @@ impl Function {
     }
 }
 
+/// Find the lowest real register which is not used.
+pub(crate) fn find_free(is_reg_used: &[bool]) -> Option<usize> {
+    is_reg_used.iter().position(|is_used| !is_used)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::ir::gen_ir::*;
-    use crate::parse::parser::Parser;
-    use crate::token::lexer::Lexer;
+    use crate::ir::testutil::gen_ir_of;
+    use crate::target::Target;
     #[test]
     fn test_calc() -> std::io::Result<()> {
         let source_code = crate::read_file_content("examples/calc.pr")?;
-        let mut lexer = Lexer::new(&source_code);
-        let tokens = lexer.lex().unwrap();
-        let mut parser = Parser::new(&tokens);
-        let ast = parser.parse().unwrap();
-        let mut ir_generator = IRGenerator::new();
-        ir_generator.gen_ir(&ast);
-        ir_generator.reg_alloc();
+        let mut ir_generator = gen_ir_of(&source_code).unwrap();
+        ir_generator.reg_alloc(&Target::x86_64_sysv());
 
         assert_eq!(
             ir_generator.funcs[0].ir_vec,
@@ -128,16 +152,16 @@ mod tests {
                 IR::new(IROp::Kill, Some(1), None),
                 IR::new(IROp::BpOffset, Some(0), Some(16)),
                 IR::new(IROp::Imm, Some(1), Some(2)),
-                IR::new(IROp::Minus, Some(1), None),
+                IR::new(IROp::Neg, Some(1), None),
                 IR::new(IROp::Store, Some(0), Some(1)),
                 IR::new(IROp::Kill, Some(0), None),
                 IR::new(IROp::Kill, Some(1), None),
                 IR::new(IROp::BpOffset, Some(0), Some(24)),
                 IR::new(IROp::BpOffset, Some(1), Some(8)),
                 IR::new(IROp::Load, Some(1), Some(1)),
-                IR::new(IROp::StoreArg, Some(0), Some(1)),
                 IR::new(IROp::BpOffset, Some(2), Some(16)),
                 IR::new(IROp::Load, Some(2), Some(2)),
+                IR::new(IROp::StoreArg, Some(0), Some(1)),
                 IR::new(IROp::StoreArg, Some(1), Some(2)),
                 IR::new(IROp::FuncCall("f".to_string()), Some(3), None),
                 IR::new(IROp::Kill, Some(1), None),
@@ -155,43 +179,104 @@ mod tests {
         Ok(())
     }
 
+    fn alloc_with_registers(register_count: usize) -> Function {
+        let source_code = "func main() { return 1 + 2 * 3; }";
+        let mut ir_generator = gen_ir_of(source_code).unwrap();
+        let target = Target {
+            register_count,
+            ..Target::x86_64_sysv()
+        };
+        ir_generator.reg_alloc(&target);
+        ir_generator.funcs.remove(0)
+    }
+
+    #[test]
+    fn test_target_register_count() {
+        let func = alloc_with_registers(3);
+        assert!(func
+            .ir_vec
+            .iter()
+            .filter(|ir| ir.op != IROp::Jmp("return_main".to_string()))
+            .all(|ir| ir.lhs.unwrap() < 3));
+    }
+
+    #[test]
+    #[should_panic(expected = "No availabale register")]
+    fn test_target_register_shortage() {
+        alloc_with_registers(2);
+    }
+
+    fn alloc_source(source_code: &str) -> Vec<Vec<IR>> {
+        let mut ir_generator = gen_ir_of(source_code).unwrap();
+        ir_generator.reg_alloc(&Target::x86_64_sysv());
+        ir_generator
+            .funcs
+            .into_iter()
+            .map(|func| func.ir_vec)
+            .collect()
+    }
+
+    #[test]
+    fn test_mov() {
+        let funcs = alloc_source("func f(a: u64) { return a++; }");
+        let mov = funcs[0].iter().find(|ir| ir.op == IROp::Mov).unwrap();
+        let register_count = Target::x86_64_sysv().register_count;
+        // Both the copy and the original are live, so they are in distinct registers.
+        assert!(mov.lhs.unwrap() < register_count);
+        assert!(mov.rhs.unwrap() < register_count);
+        assert_ne!(mov.lhs, mov.rhs);
+    }
+
+    #[test]
+    fn test_find_free() {
+        use crate::ir::reg_alloc::find_free;
+        assert_eq!(find_free(&[false, false, false]), Some(0));
+        assert_eq!(find_free(&[true, false, true, false]), Some(1));
+        assert_eq!(find_free(&[true, true, true]), None);
+        assert_eq!(find_free(&[]), None);
+    }
+
+    #[test]
+    fn test_deterministic() -> std::io::Result<()> {
+        let source_code = crate::read_file_content("examples/calc.pr")?;
+        assert_eq!(alloc_source(&source_code), alloc_source(&source_code));
+        Ok(())
+    }
+
     #[test]
     fn test_stmt() -> std::io::Result<()> {
         let source_code = crate::read_file_content("examples/stmt.pr")?;
-        let mut lexer = Lexer::new(&source_code);
-        let tokens = lexer.lex().unwrap();
-        let mut parser = Parser::new(&tokens);
-        let ast = parser.parse().unwrap();
-        let mut ir_generator = IRGenerator::new();
-        ir_generator.gen_ir(&ast);
-        ir_generator.reg_alloc();
+        let mut ir_generator = gen_ir_of(&source_code).unwrap();
+        ir_generator.reg_alloc(&Target::x86_64_sysv());
 
         assert_eq!(
             ir_generator.funcs[0].ir_vec,
-            vec![
-                IR::new(IROp::BpOffset, Some(0), Some(8)),
-                IR::new(IROp::Imm, Some(1), Some(1)),
-                IR::new(IROp::Store, Some(0), Some(1)),
-                IR::new(IROp::Kill, Some(0), None),
-                IR::new(IROp::Kill, Some(1), None),
-                IR::new(IROp::BpOffset, Some(0), Some(8)),
-                IR::new(IROp::Load, Some(0), Some(0)),
-                IR::new(IROp::Cond, Some(0), Some(1)),
-                IR::new(IROp::Kill, Some(0), None),
-                IR::new(IROp::BpOffset, Some(0), Some(8)),
-                IR::new(IROp::Imm, Some(1), Some(2)),
-                IR::new(IROp::Store, Some(0), Some(1)),
-                IR::new(IROp::BpOffset, Some(2), Some(8)),
-                IR::new(IROp::Load, Some(2), Some(2)),
-                IR::new(IROp::Return, Some(2), None),
-                IR::new(IROp::Kill, Some(2), None),
-                IR::new(IROp::Jmp("return_main".to_string()), None, None),
-                IR::new(IROp::Label("else".to_string()), Some(1), None),
-                IR::new(IROp::BpOffset, Some(2), Some(8)),
-                IR::new(IROp::Load, Some(2), Some(2)),
-                IR::new(IROp::Return, Some(2), None),
-                IR::new(IROp::Kill, Some(2), None),
-                IR::new(IROp::Jmp("return_main".to_string()), None, None),
+            ir![
+                bp_offset(0, 8),
+                imm(1, 1),
+                store(0, 1),
+                kill(0),
+                kill(1),
+                bp_offset(0, 8),
+                load(0, 0),
+                cond(0, 1),
+                kill(0),
+                bp_offset(0, 8),
+                imm(1, 2),
+                store(0, 1),
+                kill(0),
+                kill(1),
+                bp_offset(0, 8),
+                load(0, 0),
+                ret(0),
+                kill(0),
+                jmp("return_main"),
+                label("else", 1),
+                bp_offset(0, 8),
+                load(0, 0),
+                ret(0),
+                kill(0),
+                jmp("return_main"),
             ]
         );
         Ok(())
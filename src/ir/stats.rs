@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+
+use crate::ir::gen_ir::{IRGenerator, IROp};
+
+/// Summary of generated IR, to compare the output of optimization passes.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IrStats {
+    /// Number of IR instructions in all functions.
+    pub instructions: usize,
+    /// Number of instructions for each kind of `IROp`, keyed by its name.
+    pub op_counts: BTreeMap<&'static str, usize>,
+    /// Number of virtual registers allocated in all functions.
+    pub virtual_regs: usize,
+    /// Largest offset from rbp a variable is placed at.
+    pub max_stack_offset: usize,
+}
+
+impl IRGenerator {
+    /// Collect `IrStats` of the IR generated so far.
+    pub fn stats(&self) -> IrStats {
+        let mut stats = IrStats::default();
+        for func in &self.funcs {
+            stats.virtual_regs += func.reg_count;
+            for ir in &func.ir_vec {
+                stats.instructions += 1;
+                *stats.op_counts.entry(ir.op.name()).or_insert(0) += 1;
+                if ir.op == IROp::BpOffset {
+                    stats.max_stack_offset = stats.max_stack_offset.max(ir.rhs.unwrap());
+                }
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ir::gen_ir::IRGenerator;
+    use crate::opt::fold::fold_constants;
+    use crate::parse::parser::Parser;
+    use crate::target::Target;
+    use crate::token::lexer::Lexer;
+
+    fn stats(source_code: &str, fold: bool) -> super::IrStats {
+        let mut lexer = Lexer::new(source_code);
+        let tokens = lexer.lex().unwrap();
+        let mut parser = Parser::new(tokens);
+        let mut asts = parser.parse().unwrap();
+        if fold {
+            asts = fold_constants(&asts, &Target::default()).0;
+        }
+        let mut ir_generator = IRGenerator::new();
+        ir_generator.gen_ir(&asts).unwrap();
+        ir_generator.stats()
+    }
+
+    #[test]
+    fn test_stats() {
+        let source_code = "func main() { let a: u64 = 1 + 2 * 3; return a; }";
+
+        let before = stats(source_code, false);
+        assert_eq!(before.instructions, 16);
+        assert_eq!(before.op_counts["Imm"], 3);
+        assert_eq!(before.op_counts["Mul"], 1);
+        assert_eq!(before.op_counts["Add"], 1);
+        assert_eq!(before.virtual_regs, 5);
+        assert_eq!(before.max_stack_offset, 8);
+
+        let after = stats(source_code, true);
+        assert_eq!(after.instructions, 9);
+        assert_eq!(after.op_counts["Imm"], 2);
+        assert!(!after.op_counts.contains_key("Mul"));
+        assert!(!after.op_counts.contains_key("Add"));
+        assert_eq!(after.virtual_regs, 3);
+        assert_eq!(after.max_stack_offset, 8);
+    }
+}
@@ -0,0 +1,145 @@
+//! Helpers for tests on IR.
+//! Constructors of `IR`, used through `ir!`, are named after `IROp`
+//! and take operands in the order of `lhs` and `rhs`.
+use crate::ir::gen_ir::{IRGenerator, IROp, IR};
+use crate::ir::IRError;
+use crate::parse::parser::Parser;
+use crate::parse::Type;
+use crate::token::lexer::Lexer;
+
+/// Generate IR for `source_code` by the default generator.
+/// The source must be valid up to parsing.
+pub fn gen_ir_of(source_code: &str) -> Result<IRGenerator, IRError> {
+    gen_ir_with(source_code, IRGenerator::new())
+}
+
+/// Generate IR for `source_code` by `ir_generator`, such as one for another target.
+pub fn gen_ir_with(
+    source_code: &str,
+    mut ir_generator: IRGenerator,
+) -> Result<IRGenerator, IRError> {
+    let mut lexer = Lexer::new(source_code);
+    let tokens = lexer.lex().unwrap();
+    let asts = Parser::new(tokens).parse().unwrap();
+    ir_generator.gen_ir(&asts)?;
+    Ok(ir_generator)
+}
+
+pub fn imm(reg: usize, n: usize) -> IR {
+    IR::new(IROp::Imm, Some(reg), Some(n))
+}
+
+pub fn mov(dst: usize, src: usize) -> IR {
+    IR::new(IROp::Mov, Some(dst), Some(src))
+}
+
+pub fn bp_offset(reg: usize, offset: usize) -> IR {
+    IR::new(IROp::BpOffset, Some(reg), Some(offset))
+}
+
+pub fn str_addr(reg: usize, index: usize) -> IR {
+    IR::new(IROp::StrAddr, Some(reg), Some(index))
+}
+
+pub fn global_addr(reg: usize, index: usize) -> IR {
+    IR::new(IROp::GlobalAddr, Some(reg), Some(index))
+}
+
+pub fn load(dst: usize, addr: usize) -> IR {
+    IR::new(IROp::Load, Some(dst), Some(addr))
+}
+
+pub fn store(addr: usize, src: usize) -> IR {
+    IR::new(IROp::Store, Some(addr), Some(src))
+}
+
+pub fn load_param(index: usize, addr: usize) -> IR {
+    IR::new(IROp::LoadParam, Some(index), Some(addr))
+}
+
+pub fn store_arg(index: usize, src: usize) -> IR {
+    IR::new(IROp::StoreArg, Some(index), Some(src))
+}
+
+pub fn func_call(name: &str, reg: usize) -> IR {
+    IR::new(IROp::FuncCall(name.to_string()), Some(reg), None)
+}
+
+pub fn tail_call(name: &str, reg: usize) -> IR {
+    IR::new(IROp::TailCall(name.to_string()), Some(reg), None)
+}
+
+pub fn neg(reg: usize) -> IR {
+    IR::new(IROp::Neg, Some(reg), None)
+}
+
+pub fn cast(ty: Type, reg: usize) -> IR {
+    IR::new(IROp::Cast(ty), Some(reg), None)
+}
+
+pub fn zext(ty: Type, reg: usize) -> IR {
+    IR::new(IROp::Zext(ty), Some(reg), None)
+}
+
+pub fn cond(reg: usize, label_number: usize) -> IR {
+    IR::new(IROp::Cond, Some(reg), Some(label_number))
+}
+
+/// Label of a control statement such as `.Lelse1`.
+pub fn label(name: &str, label_number: usize) -> IR {
+    IR::new(IROp::Label(name.to_string()), Some(label_number), None)
+}
+
+/// Label of a labeled statement such as `.Lmain.end`.
+pub fn user_label(name: &str) -> IR {
+    IR::new(IROp::Label(name.to_string()), None, None)
+}
+
+pub fn jmp(name: &str) -> IR {
+    IR::new(IROp::Jmp(name.to_string()), None, None)
+}
+
+pub fn branch(reg: usize, label: &str) -> IR {
+    IR::new(IROp::Branch(label.to_string()), Some(reg), None)
+}
+
+pub fn ret(reg: usize) -> IR {
+    IR::new(IROp::Return, Some(reg), None)
+}
+
+pub fn kill(reg: usize) -> IR {
+    IR::new(IROp::Kill, Some(reg), None)
+}
+
+pub fn trap() -> IR {
+    IR::new(IROp::Trap, None, None)
+}
+
+macro_rules! binary_ops {
+    ($($name: ident => $op: ident),* $(,)?) => {
+        $(
+            pub fn $name(lhs: usize, rhs: usize) -> IR {
+                IR::new(IROp::$op, Some(lhs), Some(rhs))
+            }
+        )*
+    };
+}
+
+binary_ops! {
+    add => Add,
+    sub => Sub,
+    mul => Mul,
+    div => Div,
+    rem => Rem,
+    bit_and => BitAnd,
+    bit_or => BitOr,
+    bit_xor => BitXor,
+    shl => Shl,
+    shr => Shr,
+    eq => Eq,
+    ne => Ne,
+    lt => Lt,
+    le => Le,
+    gt => Gt,
+    ge => Ge,
+}
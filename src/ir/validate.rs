@@ -0,0 +1,116 @@
+use std::collections::HashSet;
+
+use crate::ir::gen_ir::{Function, IROp};
+use crate::ir::IRError;
+
+impl Function {
+    /// Check that every virtual register is defined before it is read
+    /// and is not read after it is killed.
+    pub fn validate(&self) -> Result<(), IRError> {
+        // Virtual registers holding a value at the current IR.
+        let mut defined: HashSet<usize> = HashSet::new();
+
+        for ir in &self.ir_vec {
+            match ir.op {
+                IROp::Imm
+                | IROp::BpOffset
+                | IROp::StrAddr
+                | IROp::GlobalAddr
+                | IROp::FuncCall(_)
+                | IROp::TailCall(_) => {
+                    defined.insert(Function::reg(ir.lhs));
+                }
+                IROp::Neg
+                | IROp::Cast(_)
+                | IROp::Zext(_)
+                | IROp::Cond
+                | IROp::Branch(_)
+                | IROp::Return => {
+                    Function::use_reg(ir.lhs, &defined)?;
+                }
+                IROp::LoadParam | IROp::StoreArg => {
+                    Function::use_reg(ir.rhs, &defined)?;
+                }
+                IROp::Mov | IROp::Load => {
+                    Function::use_reg(ir.rhs, &defined)?;
+                    defined.insert(Function::reg(ir.lhs));
+                }
+                IROp::Add
+                | IROp::Sub
+                | IROp::Mul
+                | IROp::Div
+                | IROp::Rem
+                | IROp::BitAnd
+                | IROp::BitOr
+                | IROp::BitXor
+                | IROp::Shl
+                | IROp::Shr
+                | IROp::Eq
+                | IROp::Ne
+                | IROp::Lt
+                | IROp::Le
+                | IROp::Gt
+                | IROp::Ge
+                | IROp::Min
+                | IROp::Max
+                | IROp::Store => {
+                    Function::use_reg(ir.lhs, &defined)?;
+                    Function::use_reg(ir.rhs, &defined)?;
+                }
+                IROp::Kill => {
+                    Function::use_reg(ir.lhs, &defined)?;
+                    defined.remove(&Function::reg(ir.lhs));
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn reg(ir_reg: Option<usize>) -> usize {
+        ir_reg.unwrap_or_else(|| panic!("{:?}", ir_reg))
+    }
+
+    /// Check if a register read by an IR holds a value.
+    fn use_reg(ir_reg: Option<usize>, defined: &HashSet<usize>) -> Result<(), IRError> {
+        let reg = Function::reg(ir_reg);
+        if defined.contains(&reg) {
+            Ok(())
+        } else {
+            Err(IRError::UseBeforeDef { reg })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ir::gen_ir::*;
+    use crate::ir::testutil::gen_ir_of;
+    use crate::ir::IRError;
+
+    #[test]
+    fn test_valid_ir() {
+        let source_code =
+            "func f(a: u64) { let b: u8 = a; if b == 1 { return f(b); } return -a / 2; }";
+        let ir_generator = gen_ir_of(source_code).unwrap();
+
+        assert_eq!(ir_generator.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_use_before_def() {
+        let mut func = Function::new();
+        func.ir_vec = vec![
+            IR::new(IROp::Imm, Some(1), Some(2)),
+            IR::new(IROp::Add, Some(1), Some(2)),
+        ];
+        assert_eq!(func.validate(), Err(IRError::UseBeforeDef { reg: 2 }));
+
+        func.ir_vec = vec![
+            IR::new(IROp::Imm, Some(1), Some(2)),
+            IR::new(IROp::Kill, Some(1), None),
+            IR::new(IROp::Return, Some(1), None),
+        ];
+        assert_eq!(func.validate(), Err(IRError::UseBeforeDef { reg: 1 }));
+    }
+}
@@ -0,0 +1,131 @@
+//! Diagnostics in JSON for editors and other tools, enabled by the `json` feature.
+
+use serde::Serialize;
+
+use crate::compile_diagnosed;
+use crate::diagnostics::{Diagnostic, Diagnostics, Severity};
+use crate::ir::OverflowMode;
+use crate::opt::OptLevel;
+use crate::token::lexer::Lexer;
+
+#[derive(Debug, Serialize)]
+struct Report {
+    // Whether the program compiles, possibly with warnings.
+    ok: bool,
+    diagnostics: Vec<JsonDiagnostic>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonDiagnostic {
+    // "error" or "warning".
+    kind: &'static str,
+    // Message without its location.
+    message: String,
+    loc: Option<JsonLoc>,
+}
+
+/// Byte offsets of `Loc` with 1-origin line and column of its start.
+#[derive(Debug, Serialize)]
+struct JsonLoc {
+    start: usize,
+    end: usize,
+    line: usize,
+    column: usize,
+}
+
+/// `compile` a source code and report all of its diagnostics as a JSON document such as
+/// `{"ok":false,"diagnostics":[{"kind":"error","message":"...","loc":{"start":14,...}}]}`.
+/// Diagnostics are in source order, and `loc` is `null` for those on the whole program.
+pub fn compile_json(source: &str) -> String {
+    let mut diagnostics = Diagnostics::new();
+    let result = compile_diagnosed(
+        source,
+        |_| None,
+        OptLevel::default(),
+        OverflowMode::default(),
+        &mut diagnostics,
+    );
+    if let Err(error) = &result {
+        diagnostics.push_error(error);
+    }
+    // Without files to include, preprocessing keeps the source as it is.
+    let lexer = Lexer::new(source);
+    let report = Report {
+        ok: !diagnostics.has_errors(),
+        diagnostics: diagnostics
+            .sorted()
+            .into_iter()
+            .map(|diagnostic| JsonDiagnostic::new(diagnostic, &lexer))
+            .collect(),
+    };
+    serde_json::to_string(&report).unwrap()
+}
+
+impl JsonDiagnostic {
+    fn new(diagnostic: &Diagnostic, lexer: &Lexer) -> Self {
+        let (kind, prefix) = match diagnostic.severity {
+            Severity::Error => ("error", ""),
+            Severity::Warning => ("warning", "warning: "),
+        };
+        let message = diagnostic.text();
+        let message = message.strip_prefix(prefix).unwrap_or(message);
+        JsonDiagnostic {
+            kind,
+            message: message.to_string(),
+            loc: diagnostic.loc.map(|loc| {
+                let (line, column) = lexer.line_col(loc.0);
+                JsonLoc {
+                    start: loc.0,
+                    end: loc.1,
+                    line,
+                    column,
+                }
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::compile_json;
+
+    #[test]
+    fn test_missing_semicolon() {
+        let json = compile_json("func main() {\n  return 0\n}\n");
+        let report: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(report["ok"], false);
+        assert_eq!(
+            report["diagnostics"][0],
+            serde_json::json!({
+                "kind": "error",
+                "message": "Missing semicolon '}'",
+                "loc": {"start": 25, "end": 26, "line": 3, "column": 1}
+            })
+        );
+    }
+
+    #[test]
+    fn test_ir_error() {
+        let report: serde_json::Value =
+            serde_json::from_str(&compile_json("func main() { return x; }")).unwrap();
+        assert_eq!(report["ok"], false);
+        assert_eq!(
+            report["diagnostics"][0]["message"],
+            "Undefined variable 'x'"
+        );
+        assert_eq!(report["diagnostics"][0]["loc"]["column"], 22);
+    }
+
+    #[test]
+    fn test_ok() {
+        assert_eq!(
+            compile_json("func main() { return 0; }"),
+            r#"{"ok":true,"diagnostics":[]}"#
+        );
+        let report: serde_json::Value =
+            serde_json::from_str(&compile_json("func f() {}\nfunc main() { return 0; }")).unwrap();
+        assert_eq!(report["ok"], true);
+        assert_eq!(report["diagnostics"][0]["kind"], "warning");
+        assert_eq!(report["diagnostics"][0]["loc"]["line"], 1);
+    }
+}
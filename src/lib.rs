@@ -1,14 +1,33 @@
 #[macro_use]
 pub mod macros;
 pub mod code;
+pub mod diagnostics;
 pub mod dump_info;
+pub mod eval;
 pub mod ir;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod opt;
 pub mod parse;
+pub mod preprocess;
+pub mod target;
 pub mod token;
 
+use crate::diagnostics::Diagnostics;
+use crate::ir::gen_ir::IRGenerator;
+use crate::ir::{IRError, OverflowMode};
+use crate::opt::{dce, flatten, fold, OptLevel};
+use crate::parse::flow;
+use crate::parse::parser::Parser;
+use crate::parse::{program_span, Ast, AstKind, ParseError};
+use crate::preprocess::PreprocessError;
+use crate::target::Target;
+use crate::token::lexer::Lexer;
+use crate::token::{LexError, Token};
 use std::fmt;
 use std::fs::File;
 use std::io::Read;
+use std::path::{Path, PathBuf};
 
 const REGISTER_COUNT: usize = 7;
 const ARG_REGISTER_COUNT: usize = 6;
@@ -22,24 +41,335 @@ pub fn read_file_content<P: AsRef<std::path::Path>>(
     Ok(source_code)
 }
 
+/// Tokenize a source code.
+pub fn lex_source(source: &str) -> Result<Vec<Token>, LexError> {
+    let mut lexer = Lexer::new(source);
+    lexer.lex().cloned()
+}
+
+/// Format each token with its kind and location, one token per line.
+pub fn format_tokens(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .map(|token| {
+            format!(
+                "{:?}, {:?}, {:?}\n",
+                token.kind(),
+                token.loc().0,
+                token.loc().1
+            )
+        })
+        .collect()
+}
+
+/// Format IR of each function, its name followed by one instruction per line.
+pub fn format_ir(ir_generator: &IRGenerator) -> String {
+    let mut text = String::new();
+    for func in &ir_generator.funcs {
+        text += &format!("{}\n", func.name);
+        for ir in &func.ir_vec {
+            text += &format!("({:?}, {:?}, {:?})\n", ir.op, ir.lhs, ir.rhs);
+        }
+    }
+    text
+}
+
+/// `compile` a source code and format its IR by `format_ir`.
+pub fn emit_ir(source: &str) -> Result<String, CompileError> {
+    compile(source).map(|ir_generator| format_ir(&ir_generator))
+}
+
+/// Compile a source code into IR of each function, which is validated
+/// but not register-allocated yet. `#include` is not available.
+pub fn compile(source: &str) -> Result<IRGenerator, CompileError> {
+    compile_with(source, OptLevel::default(), OverflowMode::default())
+}
+
+/// Same as `compile`, but runs the passes of `opt_level`
+/// and makes arithmetic behave on overflow as `overflow_mode`.
+pub fn compile_with(
+    source: &str,
+    opt_level: OptLevel,
+    overflow_mode: OverflowMode,
+) -> Result<IRGenerator, CompileError> {
+    compile_source(source, |_| None, opt_level, overflow_mode)
+}
+
+/// Read a source file and `compile` it.
+/// Files included by `#include` are searched for relative to the directory of `path`.
+pub fn compile_file(path: &Path) -> Result<IRGenerator, CompileError> {
+    let source = read_file_content(path).map_err(|error| CompileError::Io {
+        path: path.to_path_buf(),
+        kind: error.kind(),
+    })?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    compile_source(
+        &source,
+        |name| read_file_content(dir.join(name)).ok(),
+        OptLevel::default(),
+        OverflowMode::default(),
+    )
+}
+
+fn compile_source(
+    source: &str,
+    resolve: impl Fn(&str) -> Option<String>,
+    opt_level: OptLevel,
+    overflow_mode: OverflowMode,
+) -> Result<IRGenerator, CompileError> {
+    let mut diagnostics = Diagnostics::new();
+    compile_diagnosed(source, resolve, opt_level, overflow_mode, &mut diagnostics)
+}
+
+/// `compile_source` which also collects warnings into `diagnostics`.
+/// Errors are returned, not collected.
+fn compile_diagnosed(
+    source: &str,
+    resolve: impl Fn(&str) -> Option<String>,
+    opt_level: OptLevel,
+    overflow_mode: OverflowMode,
+    diagnostics: &mut Diagnostics,
+) -> Result<IRGenerator, CompileError> {
+    let source = preprocess::preprocess(source, resolve).map_err(CompileError::Preprocess)?;
+    let options = CompileOptions {
+        opt_level,
+        overflow_mode,
+        ..Default::default()
+    };
+    compile_preprocessed(&source, &options, diagnostics)
+}
+
+/// How a preprocessed source code is compiled by `compile_preprocessed`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompileOptions {
+    pub target: Target,
+    pub opt_level: OptLevel,
+    pub overflow_mode: OverflowMode,
+    /// Deduplicate immediates even below `OptLevel::O2`.
+    pub dedup_immediates: bool,
+    /// Dump tokens into stderr.
+    pub dump_tokens: bool,
+    /// Dump ASTs into stderr.
+    pub dump_asts: bool,
+    /// Dump IR using virtual registers into stderr, before it is optimized.
+    pub dump_ir: bool,
+}
+
+/// Compile a source code whose `#include` is already expanded into IR of each function,
+/// which is validated but not register-allocated yet.
+/// Warnings are collected into `diagnostics`, and errors are returned.
+pub fn compile_preprocessed(
+    source: &str,
+    options: &CompileOptions,
+    diagnostics: &mut Diagnostics,
+) -> Result<IRGenerator, CompileError> {
+    let tokens = lex_source(source).map_err(CompileError::Lex)?;
+    if options.dump_tokens {
+        dump_info::dump_tokens(&tokens);
+    }
+    let (asts, errors) = Parser::new(&tokens).parse_recovering();
+    if !errors.is_empty() {
+        return Err(CompileError::Parse(errors));
+    }
+    if options.dump_asts {
+        dump_info::dump_asts(&asts);
+    }
+    check_main(&asts)?;
+    for warning in &flow::check_returns(&asts) {
+        diagnostics.push(warning);
+    }
+    let target = options.target;
+    let asts = if options.opt_level >= OptLevel::O1 {
+        let asts = flatten::flatten_blocks(&asts);
+        let (asts, warnings) = fold::fold_constants(&asts, &target);
+        for warning in &warnings {
+            diagnostics.push(warning);
+        }
+        dce::eliminate_dead_stores(&asts)
+    } else {
+        asts
+    };
+    let mut ir_generator =
+        IRGenerator::with_target(target).with_overflow_mode(options.overflow_mode);
+    ir_generator.gen_ir(&asts).map_err(CompileError::IR)?;
+    if options.dump_ir {
+        dump_info::dump_ir(&ir_generator);
+    }
+    if options.opt_level >= OptLevel::O2 {
+        ir_generator.hoist_loop_invariants();
+    }
+    if options.opt_level >= OptLevel::O2 || options.dedup_immediates {
+        ir_generator.dedup_immediates();
+    }
+    ir_generator.remove_redundant_kills();
+    ir_generator.validate().map_err(CompileError::IR)?;
+    Ok(ir_generator)
+}
+
+/// Check that exactly one function named `main` exists.
+pub fn check_main(asts: &[Ast]) -> Result<(), CompileError> {
+    let mut mains = asts
+        .iter()
+        .filter(|ast| matches!(&ast.value, AstKind::Func { name, .. } if name == "main"));
+    match (mains.next(), mains.next()) {
+        (None, _) => Err(CompileError::NoMain(program_span(asts))),
+        (Some(_), Some(second)) => Err(CompileError::MultipleMain(second.loc)),
+        _ => Ok(()),
+    }
+}
+
+/// Errors on a whole program.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CompileError {
+    /// Span of the program which lacks `main`.
+    NoMain(Loc),
+    /// Location of the second definition of `main`.
+    MultipleMain(Loc),
+    /// Source file which cannot be read.
+    Io {
+        path: PathBuf,
+        kind: std::io::ErrorKind,
+    },
+    Preprocess(PreprocessError),
+    Lex(LexError),
+    /// All errors found while parsing.
+    Parse(Vec<ParseError>),
+    IR(IRError),
+}
+
+impl CompileError {
+    /// Location of the error, or of the first one of parse errors.
+    /// `None` if it is not in the source code.
+    pub fn loc(&self) -> Option<Loc> {
+        match self {
+            CompileError::NoMain(loc) | CompileError::MultipleMain(loc) => Some(*loc),
+            CompileError::Io { .. } | CompileError::Preprocess(_) => None,
+            CompileError::Lex(error) => Some(error.loc),
+            CompileError::Parse(errors) => errors.first().and_then(ParseError::loc),
+            CompileError::IR(error) => error.loc(),
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::NoMain(loc) => write!(f, "{}: Function 'main' is not defined", loc),
+            CompileError::MultipleMain(loc) => {
+                write!(f, "{}: Function 'main' is defined multiple times", loc)
+            }
+            CompileError::Io { path, kind } => {
+                write!(f, "{}: Cannot read file: {:?}", path.display(), kind)
+            }
+            CompileError::Preprocess(error) => write!(f, "{}", error),
+            CompileError::Lex(error) => write!(f, "{}", error),
+            CompileError::Parse(errors) => {
+                let messages: Vec<String> = errors.iter().map(ParseError::to_string).collect();
+                write!(f, "{}", messages.join("\n"))
+            }
+            CompileError::IR(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// Suspicious code which does not stop compilation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Warning {
+    /// Constant operation whose result does not fit in `int`.
+    ConstantOverflow(Loc),
+    /// Function whose control can reach its end without `return`.
+    MissingReturn(Loc),
+}
+
+impl Warning {
+    /// Location of the suspicious code.
+    pub fn loc(&self) -> Loc {
+        match self {
+            Warning::ConstantOverflow(loc) | Warning::MissingReturn(loc) => *loc,
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::ConstantOverflow(loc) => {
+                write!(
+                    f,
+                    "{}: warning: Integer overflow in constant expression",
+                    loc
+                )
+            }
+            Warning::MissingReturn(loc) => {
+                write!(
+                    f,
+                    "{}: warning: Control may reach the end of the function without `return`",
+                    loc
+                )
+            }
+        }
+    }
+}
+
 /// Struct to have location of code.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Loc(pub usize, pub usize);
 
 impl Loc {
-    /// Function to merge two `Loc`.
+    /// Location of a node synthesized by a pass rather than written in the source.
+    pub fn dummy() -> Loc {
+        Loc(usize::MAX, usize::MAX)
+    }
+
+    pub fn is_dummy(&self) -> bool {
+        *self == Loc::dummy()
+    }
+
+    /// Function to merge two `Loc`. A dummy location is ignored.
     pub fn merge(&self, other: &Loc) -> Loc {
         use std::cmp::{max, min};
+        if self.is_dummy() {
+            return *other;
+        }
+        if other.is_dummy() {
+            return *self;
+        }
         Loc(min(self.0, other.0), max(self.1, other.1))
     }
+
+    /// Source text which the location covers.
+    /// A location out of `source`, such as a dummy one, is clamped
+    /// and yields `""` instead of panicking.
+    pub fn slice<'a>(&self, source: &'a str) -> &'a str {
+        let end = self.1.min(source.len());
+        let start = self.0.min(end);
+        source.get(start..end).unwrap_or("")
+    }
 }
 
 impl fmt::Display for Loc {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_dummy() {
+            return write!(f, "<synthesized>");
+        }
         write!(f, "{}-{}", self.0, self.1)
     }
 }
 
+/// Offsets in hex, which is easier to find in a hex editor. `{:#x}` prefixes them by `0x`.
+impl fmt::LowerHex for Loc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_dummy() {
+            return write!(f, "<synthesized>");
+        }
+        if f.alternate() {
+            write!(f, "{:#x}-{:#x}", self.0, self.1)
+        } else {
+            write!(f, "{:x}-{:x}", self.0, self.1)
+        }
+    }
+}
+
 /// Struct to hold value and location.
 /// `value` will be TokenKind, AstKind, or LexErrorKind.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -53,4 +383,176 @@ impl<T> Annotation<T> {
     pub fn new(value: T, loc: Loc) -> Self {
         Self { value, loc }
     }
+
+    /// Transform `value` while keeping `loc`.
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Annotation<U> {
+        Annotation {
+            value: f(self.value),
+            loc: self.loc,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::parser::Parser;
+    use crate::{
+        check_main, compile_file, compile_with, format_tokens, lex_source, Annotation,
+        CompileError, Loc,
+    };
+
+    fn check(source_code: &str) -> Result<(), CompileError> {
+        let tokens = lex_source(source_code).unwrap();
+        let asts = Parser::new(&tokens).parse().unwrap();
+        check_main(&asts)
+    }
+
+    #[test]
+    fn test_check_main() {
+        assert_eq!(
+            check("func f() { return 1; } func main() { return f(); }"),
+            Ok(())
+        );
+        assert_eq!(
+            check("func f() { return 1; }"),
+            Err(CompileError::NoMain(Loc(0, 22)))
+        );
+        assert_eq!(check(""), Err(CompileError::NoMain(Loc(0, 0))));
+        assert_eq!(
+            check("func main() { return 1; } func main() { return 2; }"),
+            Err(CompileError::MultipleMain(Loc(26, 51)))
+        );
+    }
+
+    #[test]
+    fn test_format_tokens() {
+        let tokens = lex_source("let a: u64 = 1;").unwrap();
+        assert_eq!(
+            format_tokens(&tokens),
+            "Let, 0, 3
+Identifier(\"a\"), 4, 5
+Colon, 5, 6
+U64, 7, 10
+Assignment, 11, 12
+Number(1, None), 13, 14
+Semicolon, 14, 15
+Eof, 15, 15
+"
+        );
+    }
+
+    #[test]
+    fn test_loc_slice() {
+        let source_code = "func main() { return a + b; }";
+        assert_eq!(Loc(0, 4).slice(source_code), "func");
+        assert_eq!(Loc(21, 26).slice(source_code), "a + b");
+        assert_eq!(Loc(26, 100).slice(source_code), "; }");
+        assert_eq!(Loc(usize::MAX, 0).slice(source_code), "");
+        assert_eq!(Loc(100, 200).slice(source_code), "");
+    }
+
+    #[test]
+    fn test_loc_hex() {
+        assert_eq!(format!("{:#x}", Loc(16, 32)), "0x10-0x20");
+        assert_eq!(format!("{:x}", Loc(255, 4096)), "ff-1000");
+        assert_eq!(format!("{:#x}", Loc::dummy()), "<synthesized>");
+    }
+
+    #[test]
+    fn test_dummy_loc() {
+        let dummy = Loc::dummy();
+        assert!(dummy.is_dummy());
+        assert!(!Loc(0, 0).is_dummy());
+        assert_eq!(dummy.merge(&Loc(3, 5)), Loc(3, 5));
+        assert_eq!(Loc(3, 5).merge(&dummy), Loc(3, 5));
+        assert_eq!(dummy.slice("func main() {}"), "");
+        assert_eq!(dummy.to_string(), "<synthesized>");
+    }
+
+    #[test]
+    fn test_compile_file() {
+        let path = std::env::temp_dir().join(format!("prodio_compile_{}.pr", std::process::id()));
+        std::fs::write(&path, "func main() { return 1 + 2; }").unwrap();
+        let result = compile_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_ok());
+
+        assert_eq!(
+            compile_file(&path).err(),
+            Some(CompileError::Io {
+                path,
+                kind: std::io::ErrorKind::NotFound
+            })
+        );
+    }
+
+    #[test]
+    fn test_overflow_mode() {
+        use crate::ir::gen_ir::{IROp, IR};
+        use crate::ir::OverflowMode;
+        use crate::opt::OptLevel;
+        let ir_vec = |overflow_mode| {
+            let ir_generator = compile_with(
+                "func main() { return f() * 2 + 1; }",
+                OptLevel::default(),
+                overflow_mode,
+            )
+            .unwrap();
+            ir_generator.funcs[0].ir_vec.clone()
+        };
+        let checked = ir_vec(OverflowMode::Checked);
+        let check = IROp::BranchOverflow("main..overflow1".to_string());
+        for op in [IROp::Mul, IROp::Add].iter() {
+            let i = checked.iter().position(|ir| ir.op == *op).unwrap();
+            assert_eq!(checked[i + 1].op, check);
+        }
+        assert_eq!(
+            checked[checked.len() - 3..],
+            [
+                IR::new(IROp::Jmp("return_main".to_string()), None, None),
+                IR::new(IROp::Label("overflow".to_string()), Some(1), None),
+                IR::new(IROp::Trap, None, None),
+            ]
+        );
+
+        let wrapping = ir_vec(OverflowMode::Wrapping);
+        assert!(!wrapping
+            .iter()
+            .any(|ir| matches!(ir.op, IROp::BranchOverflow(_) | IROp::Trap)));
+    }
+
+    #[test]
+    fn test_opt_level() {
+        use crate::ir::gen_ir::IROp;
+        use crate::ir::OverflowMode;
+        use crate::opt::OptLevel;
+        let ops = |opt_level| -> Vec<IROp> {
+            let ir_generator = compile_with(
+                "func main() { let a: u64 = 1; return a + 2 * 3; }",
+                opt_level,
+                OverflowMode::default(),
+            )
+            .unwrap();
+            ir_generator.funcs[0]
+                .ir_vec
+                .iter()
+                .map(|ir| ir.op.clone())
+                .collect()
+        };
+        assert!(ops(OptLevel::O0).contains(&IROp::Mul));
+        assert!(ops(OptLevel::O0).contains(&IROp::Add));
+        for opt_level in [OptLevel::O1, OptLevel::O2].iter() {
+            let ops = ops(*opt_level);
+            assert!(!ops.contains(&IROp::Mul));
+            assert!(!ops.contains(&IROp::Add));
+        }
+        assert_eq!(OptLevel::default(), OptLevel::O1);
+    }
+
+    #[test]
+    fn test_annotation_map() {
+        let annotation = Annotation::new(42, Loc(3, 5));
+        let mapped = annotation.map(|n: i32| n.to_string());
+        assert_eq!(mapped, Annotation::new("42".to_string(), Loc(3, 5)));
+    }
 }
@@ -3,8 +3,8 @@ macro_rules! token {
     ($token_kind: ident, $start: expr, $end: expr) => {
         Token::new(TokenKind::$token_kind, Loc($start, $end))
     };
-    ($token_kind: ident ($var: expr), $start: expr, $end: expr) => {
-        Token::new(TokenKind::$token_kind($var), Loc($start, $end))
+    ($token_kind: ident ($($var: expr),+), $start: expr, $end: expr) => {
+        Token::new(TokenKind::$token_kind($($var),+), Loc($start, $end))
     };
 }
 
@@ -17,3 +17,12 @@ macro_rules! ident_val {
         }
     };
 }
+
+/// Build `Vec<IR>` from constructors in `ir::testutil`, such as `ir![imm(1, 3), kill(1)]`.
+#[cfg(test)]
+macro_rules! ir {
+    ($($ir: expr),* $(,)?) => {{
+        use crate::ir::testutil::*;
+        vec![$($ir),*]
+    }};
+}
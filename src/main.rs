@@ -2,13 +2,24 @@
 extern crate clap;
 
 use prodio::code::code_gen::Generator;
+use prodio::code::llvm;
+use prodio::code::AsmSyntax;
+use prodio::diagnostics::Diagnostics;
 use prodio::dump_info;
-use prodio::ir::gen_ir;
-use prodio::parse::parser::Parser;
-use prodio::token::lexer::Lexer;
+use prodio::ir::OverflowMode;
+use prodio::opt::OptLevel;
+use prodio::target::Target;
+use prodio::CompileOptions;
 use std::fs::File;
 use std::io::Write;
 
+fn is_opt_level(level: String) -> Result<(), String> {
+    match level.as_str() {
+        "0" | "1" | "2" => Ok(()),
+        _ => Err(format!("Unknown optimization level '{}'", level)),
+    }
+}
+
 fn main() -> std::io::Result<()> {
     let matches = clap_app!(prodio =>
         (version: crate_version!())
@@ -16,6 +27,13 @@ fn main() -> std::io::Result<()> {
         (about: crate_description!())
         (@arg CODE: +required "Input source file.")
         (@arg OUTPUT: -o +takes_value "Specify output file.")
+        (@arg target: --target +takes_value possible_value[x86_64_sysv x86_64_win64] "Target machine (default: x86_64_sysv).")
+        (@arg call_conv: --("call-conv") +takes_value possible_value[sysv win64] conflicts_with[target] "Calling convention, the same as `--target x86_64_<call-conv>`.")
+        (@arg syntax: --syntax +takes_value possible_value[intel att] "Assembly syntax (default: intel).")
+        (@arg opt_level: -O +takes_value {is_opt_level} "Optimization level 0, 1 or 2 (default: 1).")
+        (@arg dedup_imm: --("dedup-imm") "Reuse registers holding the same immediate.")
+        (@arg checked: --checked "Trap on signed overflow of `+`, `-` and `*`.")
+        (@arg emit_llvm: --("emit-llvm") "Write textual LLVM IR instead of assembly.")
         (@arg dump_token: --("dump-token") "Dump tokens into stderr.")
         (@arg dump_ast: --("dump-ast") "Dump AST into stderr.")
         (@arg dump_ir_v: --("dump-ir-v") "Dump inner representation (using virtual register) into stderr.")
@@ -26,53 +44,80 @@ fn main() -> std::io::Result<()> {
     if let Some(ref source_file_path) = matches.value_of("CODE") {
         let source_code = prodio::read_file_content(source_file_path)?;
 
-        // Lex
-        let mut lexer = Lexer::new(&source_code);
-        let tokens = match lexer.lex() {
-            Ok(asts) => asts,
-            Err(error) => {
-                eprintln!("{}", error);
-                panic!()
-            }
-        };
-        if matches.is_present("dump_token") {
-            dump_info::dump_tokens(&tokens);
-        }
+        // Preprocess
+        let source_dir = std::path::Path::new(source_file_path)
+            .parent()
+            .unwrap_or_else(|| std::path::Path::new(""));
+        let resolve = |name: &str| prodio::read_file_content(source_dir.join(name)).ok();
+        let (source_code, source_map) =
+            match prodio::preprocess::preprocess_with_map(&source_code, resolve) {
+                Ok(preprocessed) => preprocessed,
+                Err(error) => {
+                    eprintln!("{}", error);
+                    panic!()
+                }
+            };
 
-        // Parse
-        let mut parser = Parser::new(&tokens);
-        let asts = match parser.parse() {
-            Ok(asts) => asts,
-            Err(error) => {
-                eprintln!("{}", error);
-                panic!()
-            }
+        let target = match (matches.value_of("target"), matches.value_of("call_conv")) {
+            (Some("x86_64_win64"), _) | (_, Some("win64")) => Target::x86_64_win64(),
+            _ => Target::x86_64_sysv(),
+        };
+        let opt_level = match matches.value_of("opt_level") {
+            Some("0") => OptLevel::O0,
+            Some("2") => OptLevel::O2,
+            _ => OptLevel::O1,
+        };
+        let overflow_mode = if matches.is_present("checked") {
+            OverflowMode::Checked
+        } else {
+            OverflowMode::Wrapping
         };
-        if matches.is_present("dump_ast") {
-            dump_info::dump_asts(&asts);
+        let options = CompileOptions {
+            target,
+            opt_level,
+            overflow_mode,
+            dedup_immediates: matches.is_present("dedup_imm"),
+            dump_tokens: matches.is_present("dump_token"),
+            dump_asts: matches.is_present("dump_ast"),
+            dump_ir: matches.is_present("dump_ir_v"),
+        };
+
+        // Lex, parse, generate IR and optimize it
+        let mut diagnostics = Diagnostics::new();
+        let result = prodio::compile_preprocessed(&source_code, &options, &mut diagnostics);
+        if let Err(error) = &result {
+            diagnostics.push_error(error);
         }
+        for diagnostic in diagnostics.sorted() {
+            eprintln!("{}", diagnostic.located(&source_map, source_file_path));
+        }
+        let mut ir_generator = match result {
+            Ok(ir_generator) => ir_generator,
+            Err(_) => panic!(),
+        };
 
-        // IR Generation
-        let mut ir_generator = gen_ir::IRGenerator::new();
-        ir_generator.gen_ir(&asts);
-        if matches.is_present("dump_ir_v") {
-            dump_info::dump_ir(&ir_generator);
+        if matches.is_present("emit_llvm") {
+            let output_file_path = matches.value_of("OUTPUT").unwrap_or("main.ll");
+            let mut output_file = File::create(output_file_path)?;
+            write!(output_file, "{}", llvm::emit_llvm(&ir_generator))?;
+            return Ok(());
         }
 
         // Register allocation
-        ir_generator.reg_alloc();
+        ir_generator.reg_alloc(&target);
         if matches.is_present("dump_ir_r") {
             dump_info::dump_ir(&ir_generator);
         }
 
         // Code Generation
-        let mut generator = Generator::new();
+        let syntax = match matches.value_of("syntax") {
+            Some("att") => AsmSyntax::Att,
+            _ => AsmSyntax::Intel,
+        };
+        let mut generator = Generator::with_target(target).with_syntax(syntax);
         generator.code_gen(&ir_generator);
 
-        let output_file_path = match matches.value_of("OUTPUT") {
-            Some(path) => path,
-            None => "main.s",
-        };
+        let output_file_path = matches.value_of("OUTPUT").unwrap_or("main.s");
         let mut output_file = File::create(output_file_path)?;
         for code in generator.code {
             writeln!(output_file, "{}", code)?;
@@ -0,0 +1,285 @@
+use std::collections::HashSet;
+
+use crate::opt::is_pure;
+use crate::parse::Ast;
+use crate::parse::AstKind::*;
+
+/// Remove stores into variables which are never read in each function definition.
+pub fn eliminate_dead_stores(asts: &[Ast]) -> Vec<Ast> {
+    asts.iter()
+        .map(|ast| {
+            let mut vars = Vars::default();
+            vars.collect(ast, false);
+            vars.eliminate(ast)
+        })
+        .collect()
+}
+
+/// Variables of a function classified by how they are used.
+#[derive(Debug, Default)]
+struct Vars {
+    reads: HashSet<String>,
+    volatiles: HashSet<String>,
+    // Variables declared in the function. Stores into others are left for IR generation to report.
+    decls: HashSet<String>,
+}
+
+impl Vars {
+    /// Collect variables read or declared `volatile` in `ast`.
+    /// `is_stmt` is true when `ast` is a statement of a block, which can be removed.
+    /// Variables assigned anywhere else count as read
+    /// because their declarations must be kept.
+    fn collect(&mut self, ast: &Ast, is_stmt: bool) {
+        match &ast.value {
+            Variable(name) => {
+                self.reads.insert(name.clone());
+            }
+            Decl {
+                rhs, is_volatile, ..
+            } => {
+                self.decls.insert(var_name(ast).unwrap().clone());
+                if *is_volatile {
+                    self.volatiles.insert(var_name(ast).unwrap().clone());
+                }
+                if let Some(rhs) = rhs {
+                    self.collect(rhs, false);
+                }
+            }
+            Assignment { lhs, rhs } => {
+                if !is_stmt || var_name(ast).is_none() {
+                    self.collect(lhs, false);
+                }
+                self.collect(rhs, false);
+            }
+            PostIncDec { lhs, .. } => self.collect(lhs, false),
+            Cast { expr, .. } | SizeOf { expr } | Return { expr } => self.collect(expr, false),
+            UniOp { node, .. } => self.collect(node, false),
+            BinOp { lhs, rhs, .. } => {
+                self.collect(lhs, false);
+                self.collect(rhs, false);
+            }
+            Func { params, body, .. } => {
+                for param in params {
+                    if let Param { name, .. } = &param.value {
+                        self.decls.insert(name.clone());
+                    }
+                }
+                self.collect(body, false);
+            }
+            FuncCall { args, .. } => args.iter().for_each(|arg| self.collect(arg, false)),
+            Ternary { cond, then, els } => {
+                self.collect(cond, false);
+                self.collect(then, false);
+                self.collect(els, false);
+            }
+            If { cond, then, els } => {
+                self.collect(cond, false);
+                self.collect(then, false);
+                if let Some(els) = els {
+                    self.collect(els, false);
+                }
+            }
+            CompStmt { stmts } => stmts.iter().for_each(|stmt| self.collect(stmt, true)),
+            Label { stmt, .. } => self.collect(stmt, false),
+            Switch {
+                cond,
+                cases,
+                default,
+            } => {
+                self.collect(cond, false);
+                for stmt in cases
+                    .iter()
+                    .flat_map(|(_, stmts)| stmts)
+                    .chain(default.iter().flatten())
+                {
+                    self.collect(stmt, true);
+                }
+            }
+            While { cond, body, els } => {
+                self.collect(cond, false);
+                self.collect(body, false);
+                if let Some(els) = els {
+                    self.collect(els, false);
+                }
+            }
+            For {
+                init,
+                cond,
+                step,
+                body,
+                els,
+            } => {
+                let nodes = [init, cond, step, els];
+                for node in nodes.iter().filter_map(|node| node.as_deref()) {
+                    self.collect(node, false);
+                }
+                self.collect(body, false);
+            }
+            Num(_)
+            | StrLit(_)
+            | Param { .. }
+            | ExternFunc { .. }
+            | Goto { .. }
+            | Break
+            | Continue => (),
+        }
+    }
+
+    /// Whether a store into the variable never affects the behavior.
+    fn is_dead(&self, name: &str) -> bool {
+        self.decls.contains(name) && !self.reads.contains(name) && !self.volatiles.contains(name)
+    }
+
+    /// Whether evaluating an expression has no side effect.
+    /// Reading a `volatile` variable counts as a side effect.
+    fn is_pure(&self, ast: &Ast) -> bool {
+        is_pure(ast, &|name| self.volatiles.contains(name))
+    }
+
+    /// A statement without dead stores, or `None` if nothing of it is left.
+    /// The right hand side of a dead assignment is kept for its side effects.
+    fn eliminate_stmt(&self, stmt: &Ast) -> Option<Ast> {
+        match &stmt.value {
+            Decl { rhs, .. }
+                if self.is_dead(var_name(stmt)?)
+                    && rhs.as_ref().is_none_or(|rhs| self.is_pure(rhs)) =>
+            {
+                None
+            }
+            Assignment { rhs, .. } if var_name(stmt).is_some_and(|name| self.is_dead(name)) => {
+                if self.is_pure(rhs) {
+                    None
+                } else {
+                    self.eliminate_stmt(rhs)
+                }
+            }
+            _ => Some(self.eliminate(stmt)),
+        }
+    }
+
+    fn eliminate(&self, ast: &Ast) -> Ast {
+        let loc = ast.loc;
+        let eliminate_stmts = |stmts: &Vec<Ast>| {
+            stmts
+                .iter()
+                .filter_map(|stmt| self.eliminate_stmt(stmt))
+                .collect()
+        };
+        match &ast.value {
+            CompStmt { stmts } => Ast::comp_stmt(eliminate_stmts(stmts), loc),
+            Func {
+                name,
+                params,
+                body,
+                doc,
+            } => Ast::func(name.clone(), params.clone(), self.eliminate(body), loc)
+                .with_doc(doc.clone()),
+            If { cond, then, els } => Ast::if_stmt(
+                *cond.clone(),
+                self.eliminate(then),
+                els.as_deref().map(|els| self.eliminate(els)),
+                loc,
+            ),
+            Label { name, stmt } => Ast::label(name.clone(), self.eliminate(stmt), loc),
+            Switch {
+                cond,
+                cases,
+                default,
+            } => {
+                let cases = cases
+                    .iter()
+                    .map(|(value, stmts)| (*value, eliminate_stmts(stmts)))
+                    .collect();
+                Ast::switch(
+                    *cond.clone(),
+                    cases,
+                    default.as_ref().map(eliminate_stmts),
+                    loc,
+                )
+            }
+            While { cond, body, els } => Ast::while_stmt(
+                *cond.clone(),
+                self.eliminate(body),
+                els.as_deref().map(|els| self.eliminate(els)),
+                loc,
+            ),
+            For {
+                init,
+                cond,
+                step,
+                body,
+                els,
+            } => Ast::for_stmt(
+                init.as_deref().cloned(),
+                cond.as_deref().cloned(),
+                step.as_deref().cloned(),
+                self.eliminate(body),
+                els.as_deref().map(|els| self.eliminate(els)),
+                loc,
+            ),
+            _ => ast.clone(),
+        }
+    }
+}
+
+/// Name of the variable a declaration or an assignment stores into.
+fn var_name(ast: &Ast) -> Option<&String> {
+    match &ast.value {
+        Decl { lhs, .. } | Assignment { lhs, .. } => match &lhs.value {
+            Variable(name) => Some(name),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opt::dce::eliminate_dead_stores;
+    use crate::parse::parser::Parser;
+    use crate::parse::{Ast, AstKind};
+    use crate::token::lexer::Lexer;
+
+    fn eliminate_body(source_code: &str) -> Vec<Ast> {
+        let mut lexer = Lexer::new(source_code);
+        let tokens = lexer.lex().unwrap();
+        let mut parser = Parser::new(tokens);
+        let asts = eliminate_dead_stores(&parser.parse().unwrap());
+        match &asts[0].value {
+            AstKind::Func { body, .. } => match &body.value {
+                AstKind::CompStmt { stmts } => stmts.clone(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_dead_store() {
+        let stmts = eliminate_body("func main() { let a: u64 = 1; a = 2; return 0; }");
+        assert_eq!(stmts.len(), 1);
+        assert!(matches!(stmts[0].value, AstKind::Return { .. }));
+
+        // A store into an undeclared variable is kept to be reported.
+        let stmts = eliminate_body("func main() { b = 2; return 0; }");
+        assert_eq!(stmts.len(), 2);
+    }
+
+    #[test]
+    fn test_volatile_store() {
+        let stmts = eliminate_body("func main() { let a: volatile u64 = 1; a = 2; return 0; }");
+        assert_eq!(stmts.len(), 3);
+        assert!(matches!(stmts[1].value, AstKind::Assignment { .. }));
+    }
+
+    #[test]
+    fn test_keep_side_effect() {
+        let stmts = eliminate_body("func main() { let a: u64 = 1; a = f(); return 0; }");
+        assert_eq!(stmts.len(), 2);
+        assert!(matches!(stmts[0].value, AstKind::FuncCall { .. }));
+
+        // `a` is read after all.
+        let stmts = eliminate_body("func main() { let a: u64 = 1; a = 2; return a; }");
+        assert_eq!(stmts.len(), 3);
+    }
+}
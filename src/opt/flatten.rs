@@ -0,0 +1,121 @@
+use crate::parse::Ast;
+use crate::parse::AstKind::*;
+
+/// Flatten nested blocks such as `{{ ... }}` in each function definition.
+pub fn flatten_blocks(asts: &[Ast]) -> Vec<Ast> {
+    asts.iter().map(flatten).collect()
+}
+
+/// A block whose sole statement is another block is replaced by the inner one.
+/// The outer block declares nothing else, so variables of the inner block
+/// still get a scope of their own. Blocks among other statements are kept
+/// because their declarations must not leak into the enclosing scope.
+fn flatten(ast: &Ast) -> Ast {
+    let loc = ast.loc;
+    match &ast.value {
+        CompStmt { stmts } => {
+            let stmts: Vec<Ast> = stmts.iter().map(flatten).collect();
+            match stmts.as_slice() {
+                [Ast {
+                    value: CompStmt { stmts },
+                    ..
+                }] => Ast::comp_stmt(stmts.clone(), loc),
+                _ => Ast::comp_stmt(stmts, loc),
+            }
+        }
+        Func {
+            name,
+            params,
+            body,
+            doc,
+        } => Ast::func(name.clone(), params.clone(), flatten(body), loc).with_doc(doc.clone()),
+        If { cond, then, els } => Ast::if_stmt(
+            *cond.clone(),
+            flatten(then),
+            els.as_ref().map(|els| flatten(els)),
+            loc,
+        ),
+        Label { name, stmt } => Ast::label(name.clone(), flatten(stmt), loc),
+        Switch {
+            cond,
+            cases,
+            default,
+        } => {
+            let flatten_body = |stmts: &Vec<Ast>| stmts.iter().map(flatten).collect();
+            let cases = cases
+                .iter()
+                .map(|(value, stmts)| (*value, flatten_body(stmts)))
+                .collect();
+            Ast::switch(
+                *cond.clone(),
+                cases,
+                default.as_ref().map(flatten_body),
+                loc,
+            )
+        }
+        While { cond, body, els } => Ast::while_stmt(
+            *cond.clone(),
+            flatten(body),
+            els.as_deref().map(flatten),
+            loc,
+        ),
+        For {
+            init,
+            cond,
+            step,
+            body,
+            els,
+        } => Ast::for_stmt(
+            init.as_deref().cloned(),
+            cond.as_deref().cloned(),
+            step.as_deref().cloned(),
+            flatten(body),
+            els.as_deref().map(flatten),
+            loc,
+        ),
+        _ => ast.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opt::flatten::flatten_blocks;
+    use crate::parse::parser::Parser;
+    use crate::parse::{Ast, AstKind};
+    use crate::token::lexer::Lexer;
+    use crate::Loc;
+
+    fn flatten_body(source_code: &str) -> Ast {
+        let mut lexer = Lexer::new(source_code);
+        let tokens = lexer.lex().unwrap();
+        let mut parser = Parser::new(tokens);
+        let asts = flatten_blocks(&parser.parse().unwrap());
+        match &asts[0].value {
+            AstKind::Func { body, .. } => *body.clone(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_flatten_nested_block() {
+        let body = flatten_body("func main() {{ return 1; }}");
+        assert_eq!(
+            body.value,
+            AstKind::CompStmt {
+                stmts: vec![Ast::return_stmt(Ast::num(1, Loc(22, 23)), Loc(22, 23))]
+            }
+        );
+    }
+
+    #[test]
+    fn test_keep_block_among_statements() {
+        let body = flatten_body("func main() { { let a: u64 = 1; } let a: u64 = 2; return a; }");
+        match body.value {
+            AstKind::CompStmt { stmts } => {
+                assert_eq!(stmts.len(), 3);
+                assert!(matches!(stmts[0].value, AstKind::CompStmt { .. }));
+            }
+            _ => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,502 @@
+use std::collections::HashMap;
+
+use crate::opt::is_pure;
+use crate::parse::AstKind::*;
+use crate::parse::{Ast, BinOpKind, UniOpKind};
+use crate::target::Target;
+use crate::{Loc, Warning};
+
+/// Fold constant expressions and propagate constant values of local variables
+/// for each function definition.
+/// Returns warnings on folded operations which overflow `int` of `target`.
+pub fn fold_constants(asts: &[Ast], target: &Target) -> (Vec<Ast>, Vec<Warning>) {
+    let mut folder = Folder {
+        int_bits: target.int_size * 8,
+        warnings: Vec::new(),
+    };
+    let asts = asts
+        .iter()
+        .map(|ast| folder.fold(ast, &mut HashMap::new()))
+        .collect();
+    (asts, folder.warnings)
+}
+
+/// State shared while folding a program.
+struct Folder {
+    // Width of `int` in bits.
+    int_bits: usize,
+    // Warnings found while folding.
+    warnings: Vec<Warning>,
+}
+
+impl Folder {
+    /// Fold an AST.
+    /// `consts` maps a variable onto its value known at this point of a straight-line block.
+    fn fold(&mut self, ast: &Ast, consts: &mut HashMap<String, usize>) -> Ast {
+        let loc = ast.loc;
+        match &ast.value {
+            Num(_) | StrLit(_) | Param { .. } | ExternFunc { .. } => ast.clone(),
+            Variable(var) => match consts.get(var) {
+                Some(n) => Ast::num(*n, loc),
+                None => ast.clone(),
+            },
+            Decl {
+                lhs,
+                rhs,
+                ty,
+                is_const,
+                is_volatile,
+                is_static,
+                doc,
+            } => {
+                let rhs = rhs.as_ref().map(|rhs| self.fold(rhs, consts));
+                let var_name = ident_val!(&lhs.value);
+                match rhs.as_ref().map(|rhs| &rhs.value) {
+                    // Arithmetic on a pointer depends on its type, so it is not propagated.
+                    // A volatile variable must be read every time.
+                    // A static variable is initialized only once, not every time it is declared.
+                    Some(Num(n)) if ty.pointee().is_none() && !*is_volatile && !*is_static => {
+                        consts.insert(var_name, ty.truncate(*n))
+                    }
+                    _ => consts.remove(&var_name),
+                };
+                Ast::decl(
+                    *lhs.clone(),
+                    rhs,
+                    ty.clone(),
+                    *is_const,
+                    *is_volatile,
+                    *is_static,
+                    loc,
+                )
+                .with_doc(doc.clone())
+            }
+            Cast { ty, expr } => {
+                let expr = self.fold(expr, consts);
+                match expr.value {
+                    Num(n) => Ast::num(ty.truncate(n), loc),
+                    _ => Ast::cast(ty.clone(), expr, loc),
+                }
+            }
+            BinOp { op, lhs, rhs } => {
+                let lhs = self.fold(lhs, consts);
+                let rhs = self.fold(rhs, consts);
+                self.fold_binops(op, lhs, rhs, loc)
+            }
+            UniOp { op, node } => {
+                let node = self.fold(node, consts);
+                match (op, &node.value) {
+                    // `+e` is just `e`.
+                    (UniOpKind::Plus, _) => node,
+                    (_, Num(n)) => match op.eval(self.sign_extend(*n)) {
+                        Ok(value) => Ast::num(self.wrap(value), loc),
+                        // `*1` is left for IR generation to reject.
+                        Err(_) => Ast::uniop(op.clone(), node, loc),
+                    },
+                    _ => Ast::uniop(op.clone(), node, loc),
+                }
+            }
+            Func {
+                name,
+                params,
+                body,
+                doc,
+            } => Ast::func(name.clone(), params.clone(), self.fold(body, consts), loc)
+                .with_doc(doc.clone()),
+            FuncCall { name, args } => {
+                let args = args.iter().map(|arg| self.fold(arg, consts)).collect();
+                Ast::func_call(name.clone(), args, loc)
+            }
+            If { cond, then, els } => {
+                let cond = self.fold(cond, consts);
+                let then = self.fold(then, consts);
+                let els = els.as_ref().map(|els| self.fold(els, consts));
+                Ast::if_stmt(cond, then, els, loc)
+            }
+            Ternary { cond, then, els } => {
+                let cond = self.fold(cond, consts);
+                // A constant condition selects the branch, and the other one never runs.
+                if let Num(n) = cond.value {
+                    return self.fold(if n != 0 { then } else { els }, consts);
+                }
+                // Only one of the branches runs, so values agreed by both are kept.
+                let mut then_consts = consts.clone();
+                let then = self.fold(then, &mut then_consts);
+                let els = self.fold(els, consts);
+                consts.retain(|var, n| then_consts.get(var) == Some(n));
+                Ast::ternary(cond, then, els, loc)
+            }
+            CompStmt { stmts } => {
+                // Values are propagated only inside a single block.
+                let mut inner_consts = HashMap::new();
+                let stmts = stmts
+                    .iter()
+                    .map(|stmt| self.fold(stmt, &mut inner_consts))
+                    .collect();
+                // The block may reassign variables of the outer scope.
+                consts.clear();
+                Ast::comp_stmt(stmts, loc)
+            }
+            Assignment { lhs, rhs } => {
+                let rhs = self.fold(rhs, consts);
+                // Pointers never point to local variables, so a store through one keeps them.
+                let lhs = match &lhs.value {
+                    Variable(name) => {
+                        consts.remove(name);
+                        *lhs.clone()
+                    }
+                    _ => self.fold(lhs, consts),
+                };
+                Ast::assignment(lhs, rhs, loc)
+            }
+            // The operand is not evaluated, so it is kept as it is for its type.
+            SizeOf { .. } => ast.clone(),
+            PostIncDec { lhs, .. } => {
+                consts.remove(&ident_val!(&lhs.value));
+                ast.clone()
+            }
+            Return { expr } => Ast::return_stmt(self.fold(expr, consts), loc),
+            Goto { .. } => {
+                consts.clear();
+                ast.clone()
+            }
+            Label { name, stmt } => {
+                // Control can reach a label from anywhere, so nothing is known here.
+                consts.clear();
+                Ast::label(name.clone(), self.fold(stmt, consts), loc)
+            }
+            Switch {
+                cond,
+                cases,
+                default,
+            } => {
+                let cond = self.fold(cond, consts);
+                // Each case can be entered by a jump, so values are propagated only inside it.
+                let mut fold_body = |stmts: &Vec<Ast>| {
+                    let mut inner_consts = HashMap::new();
+                    stmts
+                        .iter()
+                        .map(|stmt| self.fold(stmt, &mut inner_consts))
+                        .collect()
+                };
+                let cases = cases
+                    .iter()
+                    .map(|(value, stmts)| (*value, fold_body(stmts)))
+                    .collect();
+                let default = default.as_ref().map(fold_body);
+                consts.clear();
+                Ast::switch(cond, cases, default, loc)
+            }
+            While { cond, body, els } => {
+                // The body may reassign variables which the condition reads.
+                consts.clear();
+                let cond = self.fold(cond, consts);
+                let body = self.fold(body, consts);
+                consts.clear();
+                let els = els.as_ref().map(|els| self.fold(els, consts));
+                Ast::while_stmt(cond, body, els, loc)
+            }
+            For {
+                init,
+                cond,
+                step,
+                body,
+                els,
+            } => {
+                let init = init.as_ref().map(|init| self.fold(init, consts));
+                consts.clear();
+                let cond = cond.as_ref().map(|cond| self.fold(cond, consts));
+                let body = self.fold(body, consts);
+                let step = step
+                    .as_ref()
+                    .map(|step| self.fold(step, &mut HashMap::new()));
+                consts.clear();
+                let els = els.as_ref().map(|els| self.fold(els, consts));
+                Ast::for_stmt(init, cond, step, body, els, loc)
+            }
+            Break | Continue => ast.clone(),
+        }
+    }
+
+    /// Value of `int` in `n` as a signed integer.
+    fn sign_extend(&self, n: usize) -> i64 {
+        let shift = 64 - self.int_bits.min(64);
+        ((n as i64) << shift) >> shift
+    }
+
+    /// `n` wrapped into `int`, whose upper bits are zero as 32-bit operations leave them.
+    fn wrap(&self, n: i64) -> usize {
+        match self.int_bits {
+            64 => n as usize,
+            bits => n as usize & ((1 << bits) - 1),
+        }
+    }
+
+    /// Fold a binary operation whose operands are already folded.
+    /// A chain of an associative operator such as `a + 1 + 2` is reassociated
+    /// into `a + (1 + 2)` to fold its constants together.
+    fn fold_binops(&mut self, op: &BinOpKind, lhs: Ast, rhs: Ast, loc: Loc) -> Ast {
+        if let (Num(l), Num(r)) = (&lhs.value, &rhs.value) {
+            let (l, r) = (self.sign_extend(*l), self.sign_extend(*r));
+            if let Ok(n) = op.eval(l, r) {
+                if overflows(op, l, r, self.int_bits) {
+                    self.warnings.push(Warning::ConstantOverflow(loc));
+                }
+                return Ast::num(self.wrap(n), loc);
+            }
+        }
+        if is_associative(op) {
+            // All associative operators are also commutative,
+            // so a constant on either side can be moved.
+            let (expr, n) = match (&lhs.value, &rhs.value) {
+                (_, Num(n)) => (&lhs, *n),
+                (Num(n), _) => (&rhs, *n),
+                _ => return Ast::binop(op.clone(), lhs, rhs, loc),
+            };
+            if let BinOp {
+                op: inner_op,
+                lhs: inner_lhs,
+                rhs: inner_rhs,
+            } = &expr.value
+            {
+                let inner = match (&inner_lhs.value, &inner_rhs.value) {
+                    (_, Num(m)) => Some((inner_lhs, *m)),
+                    (Num(m), _) => Some((inner_rhs, *m)),
+                    _ => None,
+                };
+                // The operation on `inner_expr` is not of `int`, so the constants are combined
+                // in 64 bits without a warning, as the operation on them would be.
+                match inner {
+                    // Reassociation keeps the number of reads, so volatile ones are fine.
+                    Some((inner_expr, m)) if inner_op == op && is_pure(inner_expr, &|_| false) => {
+                        if let Ok(constant) = op.eval(m as i64, n as i64) {
+                            let constant = Ast::num(constant as usize, loc);
+                            return Ast::binop(op.clone(), *inner_expr.clone(), constant, loc);
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+        Ast::binop(op.clone(), lhs, rhs, loc)
+    }
+}
+
+/// Whether `(a op b) op c` equals `a op (b op c)` for an operator.
+fn is_associative(op: &BinOpKind) -> bool {
+    matches!(
+        op,
+        BinOpKind::Add | BinOpKind::Mul | BinOpKind::BitAnd | BinOpKind::BitOr | BinOpKind::BitXor
+    )
+}
+
+/// Whether applying an arithmetic operator to signed `int` values of `bits` width overflows.
+fn overflows(op: &BinOpKind, lhs: i64, rhs: i64, bits: usize) -> bool {
+    let (lhs, rhs) = (lhs as i128, rhs as i128);
+    let n = match op {
+        BinOpKind::Add => lhs + rhs,
+        BinOpKind::Sub => lhs - rhs,
+        BinOpKind::Mul => lhs * rhs,
+        BinOpKind::Div if rhs != 0 => lhs / rhs,
+        _ => return false,
+    };
+    let min = -(1 << (bits - 1));
+    let max = (1 << (bits - 1)) - 1;
+    n < min || max < n
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opt::fold::fold_constants;
+    use crate::parse::parser::Parser;
+    use crate::parse::{Ast, AstKind, BinOpKind};
+    use crate::target::Target;
+    use crate::token::lexer::Lexer;
+    use crate::{Loc, Warning};
+
+    fn fold_body(source_code: &str) -> Vec<Ast> {
+        let mut lexer = Lexer::new(source_code);
+        let tokens = lexer.lex().unwrap();
+        let mut parser = Parser::new(tokens);
+        let (asts, _) = fold_constants(&parser.parse().unwrap(), &Target::default());
+        match &asts[0].value {
+            AstKind::Func { body, .. } => match &body.value {
+                AstKind::CompStmt { stmts } => stmts.clone(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_propagate_constant() {
+        let stmts = fold_body("func main() { let a: u64 = 2; let b: u64 = a + 3; return b; }");
+        match &stmts[1].value {
+            AstKind::Decl { rhs: Some(rhs), .. } => assert_eq!(rhs.value, AstKind::Num(5)),
+            _ => unreachable!(),
+        }
+        match &stmts[2].value {
+            AstKind::Return { expr } => assert_eq!(expr.value, AstKind::Num(5)),
+            _ => unreachable!(),
+        }
+    }
+
+    fn fold_return(source_code: &str) -> i64 {
+        match &fold_body(source_code)[0].value {
+            AstKind::Return { expr } => match expr.value {
+                AstKind::Num(n) => n as i64,
+                _ => panic!("return value is not folded: {:?}", expr),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_signed_arithmetic() {
+        assert_eq!(fold_return("func main() { return 0 - 1; }"), -1);
+        assert_eq!(fold_return("func main() { return -5 * -3; }"), 15);
+        assert_eq!(fold_return("func main() { return -6 / 2; }"), -3);
+        assert_eq!(fold_return("func main() { return -7 % 3; }"), -1);
+        assert_eq!(fold_return("func main() { return -8 >> 1; }"), -4);
+        assert_eq!(
+            fold_return("func main() { return 6 & 3 | 8 ^ 1 << 4; }"),
+            26
+        );
+    }
+
+    #[test]
+    fn test_consecutive_minus() {
+        assert_eq!(fold_return("func main() { return 3 - -10; }"), 13);
+        assert_eq!(fold_return("func main() { return - -10; }"), 10);
+        assert_eq!(fold_return("func main() { return -(-10); }"), 10);
+    }
+
+    #[test]
+    fn test_constant_ternary() {
+        assert_eq!(fold_return("func main() { return 1 ? 2 : 3; }"), 2);
+        assert_eq!(fold_return("func main() { return 0 ? 2 : 3; }"), 3);
+        // The selected branch is folded as well.
+        assert_eq!(fold_return("func main() { return 2 > 1 ? 4 * 5 : a; }"), 20);
+        match &fold_body("func main() { return a ? 2 : 3; }")[0].value {
+            AstKind::Return { expr } => assert!(matches!(expr.value, AstKind::Ternary { .. })),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_strip_unary_plus() {
+        match &fold_body("func main() { return +a; }")[0].value {
+            AstKind::Return { expr } => assert_eq!(expr.value, AstKind::Variable("a".to_string())),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_reassignment_blocks_propagation() {
+        let stmts =
+            fold_body("func main() { let a: u64 = 2; a = 4; let b: u64 = a + 3; return b; }");
+        match &stmts[2].value {
+            AstKind::Decl { rhs: Some(rhs), .. } => match &rhs.value {
+                AstKind::BinOp { op, lhs, .. } => {
+                    assert_eq!(op, &BinOpKind::Add);
+                    assert_eq!(lhs.value, AstKind::Variable("a".to_string()));
+                }
+                _ => panic!("`a + 3` must not be folded: {:?}", rhs),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_reassociate_constants() {
+        let fold_expr = |source_code: &str| match &fold_body(source_code)[0].value {
+            AstKind::Return { expr } => *expr.clone(),
+            _ => unreachable!(),
+        };
+        let expr = fold_expr("func main() { return a + 1 + 2; }");
+        match &expr.value {
+            AstKind::BinOp { op, lhs, rhs } => {
+                assert_eq!(op, &BinOpKind::Add);
+                assert_eq!(lhs.value, AstKind::Variable("a".to_string()));
+                assert_eq!(rhs.value, AstKind::Num(3));
+            }
+            _ => panic!("`a + 1 + 2` is not reassociated: {:?}", expr),
+        }
+        let expr = fold_expr("func main() { return 2 * (3 * a) * 4; }");
+        match &expr.value {
+            AstKind::BinOp { lhs, rhs, .. } => {
+                assert_eq!(lhs.value, AstKind::Variable("a".to_string()));
+                assert_eq!(rhs.value, AstKind::Num(24));
+            }
+            _ => panic!("`2 * (3 * a) * 4` is not reassociated: {:?}", expr),
+        }
+        // `-` is not associative.
+        let expr = fold_expr("func main() { return a - 1 - 2; }");
+        assert!(
+            matches!(&expr.value, AstKind::BinOp { lhs, .. } if matches!(lhs.value, AstKind::BinOp { .. }))
+        );
+        // A call is not reassociated as it has side effects.
+        let expr = fold_expr("func main() { return f() + 1 + 2; }");
+        assert!(
+            matches!(&expr.value, AstKind::BinOp { lhs, .. } if matches!(lhs.value, AstKind::BinOp { .. }))
+        );
+        // A conditional expression without side effects is.
+        let expr = fold_expr("func main() { return (a ? b : c) + 1 + 2; }");
+        match &expr.value {
+            AstKind::BinOp { lhs, rhs, .. } => {
+                assert!(matches!(lhs.value, AstKind::Ternary { .. }));
+                assert_eq!(rhs.value, AstKind::Num(3));
+            }
+            _ => panic!("`(a ? b : c) + 1 + 2` is not reassociated: {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn test_constant_overflow() {
+        let mut lexer = Lexer::new("func main() { return 2000000000 + 2000000000; }");
+        let tokens = lexer.lex().unwrap();
+        let mut parser = Parser::new(tokens);
+        let asts = parser.parse().unwrap();
+        let target = Target {
+            int_size: 4,
+            ..Target::x86_64_sysv()
+        };
+        let (asts, warnings) = fold_constants(&asts, &target);
+        assert_eq!(warnings, [Warning::ConstantOverflow(Loc(21, 44))]);
+        // The sum wraps around in 32 bits.
+        match &asts[0].value {
+            AstKind::Func { body, .. } => {
+                assert!(format!("{:?}", body).contains("Num(4000000000)"))
+            }
+            _ => unreachable!(),
+        }
+        // The sum fits in 64-bit int.
+        let (_, warnings) = fold_constants(&asts, &Target::x86_64_sysv());
+        assert!(warnings.is_empty());
+
+        let fold_int32 = |source_code: &str| {
+            let mut lexer = Lexer::new(source_code);
+            let tokens = lexer.lex().unwrap();
+            let asts = Parser::new(tokens).parse().unwrap();
+            fold_constants(&asts, &target)
+        };
+        // `-1` wraps into 32 bits and still compares as negative.
+        let (asts, _) = fold_int32("func main() { return 3000000000 * 2 + (-1 < 0); }");
+        match &asts[0].value {
+            AstKind::Func { body, .. } => {
+                assert!(format!("{:?}", body).contains("Num(1705032705)"))
+            }
+            _ => unreachable!(),
+        }
+        // `a` is `u64`, so the reassociated constants are not of `int`.
+        let (asts, warnings) =
+            fold_int32("func main() { let a: u64 = f(); return a + 2000000000 + 2000000000; }");
+        assert!(warnings.is_empty());
+        match &asts[0].value {
+            AstKind::Func { body, .. } => {
+                assert!(format!("{:?}", body).contains("Num(4000000000)"))
+            }
+            _ => unreachable!(),
+        }
+    }
+}
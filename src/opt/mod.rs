@@ -0,0 +1,34 @@
+use crate::parse::Ast;
+use crate::parse::AstKind::*;
+
+pub mod dce;
+pub mod flatten;
+pub mod fold;
+
+/// Which optimization passes run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub enum OptLevel {
+    /// No pass.
+    O0,
+    /// Block flattening, constant folding with propagation and dead store elimination.
+    #[default]
+    O1,
+    /// All passes, which adds immediate deduplication on IR.
+    O2,
+}
+
+/// Whether evaluating an expression has no side effect.
+/// Reading a variable for which `is_volatile` holds counts as a side effect.
+pub(crate) fn is_pure(ast: &Ast, is_volatile: &dyn Fn(&str) -> bool) -> bool {
+    match &ast.value {
+        Num(_) | StrLit(_) | SizeOf { .. } => true,
+        Variable(name) => !is_volatile(name),
+        Cast { expr, .. } => is_pure(expr, is_volatile),
+        UniOp { node, .. } => is_pure(node, is_volatile),
+        BinOp { lhs, rhs, .. } => is_pure(lhs, is_volatile) && is_pure(rhs, is_volatile),
+        Ternary { cond, then, els } => {
+            is_pure(cond, is_volatile) && is_pure(then, is_volatile) && is_pure(els, is_volatile)
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,132 @@
+use crate::parse::Ast;
+use crate::parse::AstKind::*;
+use crate::Warning;
+
+/// Warn about each function whose control can reach the closing brace without `return`,
+/// which leaves the return value undefined.
+pub fn check_returns(asts: &[Ast]) -> Vec<Warning> {
+    asts.iter()
+        .filter_map(|ast| match &ast.value {
+            Func { body, .. } if can_complete(body) => Some(Warning::MissingReturn(ast.loc)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether control can leave a statement by reaching its end,
+/// rather than by `return`, `goto`, `break`, `continue` or running forever.
+fn can_complete(stmt: &Ast) -> bool {
+    match &stmt.value {
+        Return { .. } | Goto { .. } | Break | Continue => false,
+        CompStmt { stmts } => can_complete_stmts(stmts),
+        Label { stmt, .. } => can_complete(stmt),
+        If {
+            then,
+            els: Some(els),
+            ..
+        } => can_complete(then) || can_complete(els),
+        // The loop ends either by `break` or by the condition, after which `els` runs.
+        While { cond, body, els } => {
+            has_break(body) || (!is_true(Some(cond)) && els.as_deref().is_none_or(can_complete))
+        }
+        For {
+            cond, body, els, ..
+        } => {
+            has_break(body)
+                || (!is_true(cond.as_deref()) && els.as_deref().is_none_or(can_complete))
+        }
+        // Every case is a jump target, so only the last one decides whether control
+        // reaches the end by falling through, and it is `default` if any.
+        Switch {
+            cases,
+            default: Some(default),
+            ..
+        } => {
+            cases
+                .iter()
+                .flat_map(|(_, stmts)| stmts)
+                .chain(default)
+                .any(has_break)
+                || can_complete_stmts(default)
+        }
+        _ => true,
+    }
+}
+
+/// Whether control can reach the end of statements run in order.
+/// A labeled statement is reachable by `goto` even after a `return`.
+fn can_complete_stmts(stmts: &[Ast]) -> bool {
+    stmts.iter().fold(true, |reachable, stmt| match stmt.value {
+        Label { .. } => can_complete(stmt),
+        _ => reachable && can_complete(stmt),
+    })
+}
+
+/// Whether a loop condition is always true. A missing condition of `for` counts as true.
+fn is_true(cond: Option<&Ast>) -> bool {
+    match cond.map(|cond| &cond.value) {
+        None => true,
+        Some(Num(n)) => *n != 0,
+        Some(_) => false,
+    }
+}
+
+/// Whether `break` leaving the enclosing loop or switch appears in a statement.
+fn has_break(stmt: &Ast) -> bool {
+    match &stmt.value {
+        Break => true,
+        CompStmt { stmts } => stmts.iter().any(has_break),
+        Label { stmt, .. } => has_break(stmt),
+        If { then, els, .. } => has_break(then) || els.as_deref().is_some_and(has_break),
+        // `break` inside a nested loop or switch leaves that one.
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::flow::check_returns;
+    use crate::parse::parser::Parser;
+    use crate::token::lexer::Lexer;
+    use crate::{Loc, Warning};
+
+    fn check(source_code: &str) -> Vec<Warning> {
+        let mut lexer = Lexer::new(source_code);
+        let tokens = lexer.lex().unwrap();
+        let mut parser = Parser::new(tokens);
+        check_returns(&parser.parse().unwrap())
+    }
+
+    #[test]
+    fn test_always_return() {
+        let sources = [
+            "func f(a: u64) { if a { return 1; } else { return 2; } }",
+            "func f(a: u64) { if a { return 1; } else if a > 1 { return 2; } else { return 3; } }",
+            "func f(a: u64) { while 1 { if a { return 1; } } }",
+            "func f(a: u64) { for (;;) { while a { break; } } }",
+            "func f(a: u64) { switch a { case 1: a = 2; default: return a; } }",
+            "func f(a: u64) { while a { a = a - 1; } else { return 0; } return 1; }",
+        ];
+        for source_code in sources.iter() {
+            assert_eq!(check(source_code), [], "{}", source_code);
+        }
+    }
+
+    #[test]
+    fn test_missing_return() {
+        assert_eq!(
+            check("func f(a: u64) { if a { return 1; } }"),
+            [Warning::MissingReturn(Loc(0, 37))]
+        );
+        let sources = [
+            "func f(a: u64) { if a { return 1; } else { a = 2; } }",
+            "func f(a: u64) { while 1 { if a { break; } } }",
+            "func f(a: u64) { switch a { case 1: return 1; } }",
+            "func f(a: u64) { switch a { case 1: break; default: return 1; } }",
+            "func f(a: u64) { return 1; end: a = 2; }",
+        ];
+        for source_code in sources.iter() {
+            assert_eq!(check(source_code).len(), 1, "{}", source_code);
+        }
+    }
+}
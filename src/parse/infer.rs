@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::parse::{Ast, AstKind, BinOpKind, Type, UniOpKind};
+use crate::Loc;
+
+/// Types of variables visible from a point of a program.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SymbolTable {
+    // Scopes of variables. The innermost one places last.
+    scopes: Vec<HashMap<String, Type>>,
+}
+
+impl SymbolTable {
+    /// Generate a table with a single empty scope.
+    pub fn new() -> Self {
+        SymbolTable {
+            scopes: vec![HashMap::new()],
+        }
+    }
+
+    /// Enter a new scope, whose variables shadow outer ones.
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    /// Leave the innermost scope and forget its variables.
+    pub fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Declare a variable in the innermost scope.
+    pub fn insert(&mut self, name: &str, ty: Type) {
+        if self.scopes.is_empty() {
+            self.push_scope();
+        }
+        self.scopes.last_mut().unwrap().insert(name.to_string(), ty);
+    }
+
+    /// Type of the visible variable named `name`.
+    pub fn lookup(&self, name: &str) -> Option<&Type> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+}
+
+/// Data type that represents an error found while inferring a type.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeError {
+    UndefinedVariable {
+        name: String,
+        loc: Loc,
+    },
+    /// Binary operator which cannot be applied to operands of the types.
+    InvalidOperands {
+        op: BinOpKind,
+        lhs: Type,
+        rhs: Type,
+        loc: Loc,
+    },
+    /// Unary operator which cannot be applied to an operand of the type.
+    InvalidOperand {
+        op: UniOpKind,
+        ty: Type,
+        loc: Loc,
+    },
+    /// Statement which yields no value.
+    NotExpression(Loc),
+}
+
+impl TypeError {
+    /// Location of the expression which has the error.
+    pub fn loc(&self) -> Loc {
+        match self {
+            TypeError::UndefinedVariable { loc, .. }
+            | TypeError::InvalidOperands { loc, .. }
+            | TypeError::InvalidOperand { loc, .. }
+            | TypeError::NotExpression(loc) => *loc,
+        }
+    }
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeError::UndefinedVariable { name, loc } => {
+                write!(f, "{}: Undefined variable '{}'", loc, name)
+            }
+            TypeError::InvalidOperands { op, lhs, rhs, loc } => write!(
+                f,
+                "{}: Operator '{}' cannot be applied to '{}' and '{}'",
+                loc, op, lhs, rhs
+            ),
+            TypeError::InvalidOperand { op, ty, loc } => write!(
+                f,
+                "{}: Operator '{}' cannot be applied to '{}'",
+                loc, op, ty
+            ),
+            TypeError::NotExpression(loc) => write!(f, "{}: Not expression", loc),
+        }
+    }
+}
+
+/// Static type of a value an expression yields.
+/// Arithmetic on integers yields `u64` as operands are promoted to it,
+/// and arithmetic on a pointer and an integer yields the pointer type.
+/// An array yields a pointer to its first element.
+pub fn infer_type(ast: &Ast, symbols: &SymbolTable) -> Result<Type, TypeError> {
+    match &ast.value {
+        AstKind::Num(_) | AstKind::SizeOf { .. } | AstKind::FuncCall { .. } => Ok(Type::U64),
+        AstKind::StrLit(_) => Ok(Type::ptr(Type::U8)),
+        AstKind::Variable(name) => {
+            symbols
+                .lookup(name)
+                .map(Type::decay)
+                .ok_or_else(|| TypeError::UndefinedVariable {
+                    name: name.clone(),
+                    loc: ast.loc,
+                })
+        }
+        AstKind::Cast { ty, expr } => {
+            infer_type(expr, symbols)?;
+            Ok(ty.decay())
+        }
+        AstKind::UniOp { op, node } => {
+            let ty = infer_type(node, symbols)?;
+            match (op, ty.pointee().cloned()) {
+                (UniOpKind::Deref, Some(pointee)) => Ok(pointee.decay()),
+                (UniOpKind::Deref, None) | (UniOpKind::Minus, Some(_)) => {
+                    Err(TypeError::InvalidOperand {
+                        op: op.clone(),
+                        ty,
+                        loc: ast.loc,
+                    })
+                }
+                (_, None) => Ok(Type::U64),
+                (UniOpKind::Plus, Some(_)) => Ok(ty),
+            }
+        }
+        AstKind::BinOp { op, lhs, rhs } => {
+            let lhs = infer_type(lhs, symbols)?;
+            let rhs = infer_type(rhs, symbols)?;
+            binop_type(op, lhs, rhs, ast.loc)
+        }
+        AstKind::Assignment { lhs, rhs } => {
+            infer_type(rhs, symbols)?;
+            infer_type(lhs, symbols)
+        }
+        AstKind::PostIncDec { lhs, .. } => infer_type(lhs, symbols),
+        AstKind::Ternary { cond, then, els } => {
+            infer_type(cond, symbols)?;
+            let then = infer_type(then, symbols)?;
+            let els = infer_type(els, symbols)?;
+            // Integers of different widths are both promoted to `u64`.
+            match (then.pointee(), els.pointee()) {
+                (None, None) if then != els => Ok(Type::U64),
+                _ => Ok(then),
+            }
+        }
+        _ => Err(TypeError::NotExpression(ast.loc)),
+    }
+}
+
+fn binop_type(op: &BinOpKind, lhs: Type, rhs: Type, loc: Loc) -> Result<Type, TypeError> {
+    let is_comparison = matches!(
+        op,
+        BinOpKind::Eq
+            | BinOpKind::Ne
+            | BinOpKind::Lt
+            | BinOpKind::Le
+            | BinOpKind::Gt
+            | BinOpKind::Ge
+    );
+    match (op, lhs.pointee(), rhs.pointee()) {
+        (_, None, None) | (BinOpKind::LogAnd | BinOpKind::LogOr, _, _) => return Ok(Type::U64),
+        (BinOpKind::Add, Some(_), None) | (BinOpKind::Sub, Some(_), None) => return Ok(lhs),
+        (BinOpKind::Add, None, Some(_)) => return Ok(rhs),
+        // Difference of pointers counts elements between them.
+        (BinOpKind::Sub, Some(_), Some(_)) if lhs == rhs => return Ok(Type::U64),
+        (_, Some(_), Some(_)) if is_comparison && lhs == rhs => return Ok(Type::U64),
+        _ => (),
+    }
+    Err(TypeError::InvalidOperands {
+        op: op.clone(),
+        lhs,
+        rhs,
+        loc,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::infer::{infer_type, SymbolTable, TypeError};
+    use crate::parse::parser::Parser;
+    use crate::parse::{Ast, AstKind, BinOpKind, Type};
+    use crate::token::lexer::Lexer;
+    use crate::Loc;
+
+    /// Parse `return <expr>;` in a function and infer the type of `<expr>`.
+    fn infer(expr: &str, symbols: &SymbolTable) -> Result<Type, TypeError> {
+        let source_code = format!("func main() {{ return {}; }}", expr);
+        let mut lexer = Lexer::new(&source_code);
+        let tokens = lexer.lex().unwrap();
+        let mut parser = Parser::new(tokens);
+        let asts: Vec<Ast> = parser.parse().unwrap();
+        let body = match &asts[0].value {
+            AstKind::Func { body, .. } => body,
+            _ => unreachable!(),
+        };
+        match &body.value {
+            AstKind::CompStmt { stmts } => match &stmts[0].value {
+                AstKind::Return { expr } => infer_type(expr, symbols),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_infer_type() {
+        let mut symbols = SymbolTable::new();
+        symbols.insert("c", Type::U8);
+        symbols.insert("p", Type::ptr(Type::U64));
+        symbols.insert("s", Type::ptr(Type::U8));
+
+        assert_eq!(infer("1 + 2", &symbols), Ok(Type::U64));
+        assert_eq!(infer("c", &symbols), Ok(Type::U8));
+        assert_eq!(infer("c + 1", &symbols), Ok(Type::U64));
+        assert_eq!(infer("(u8)300", &symbols), Ok(Type::U8));
+        assert_eq!(infer("p + 1", &symbols), Ok(Type::ptr(Type::U64)));
+        assert_eq!(infer("1 + p", &symbols), Ok(Type::ptr(Type::U64)));
+        assert_eq!(infer("p - p", &symbols), Ok(Type::U64));
+        assert_eq!(infer("p == p", &symbols), Ok(Type::U64));
+        assert_eq!(infer("\"hi\"", &symbols), Ok(Type::ptr(Type::U8)));
+        assert_eq!(infer("*s", &symbols), Ok(Type::U8));
+        assert_eq!(infer("*(p + 1) * 2", &symbols), Ok(Type::U64));
+
+        assert_eq!(
+            infer("p + p", &symbols),
+            Err(TypeError::InvalidOperands {
+                op: BinOpKind::Add,
+                lhs: Type::ptr(Type::U64),
+                rhs: Type::ptr(Type::U64),
+                loc: Loc(21, 26)
+            })
+        );
+        assert!(infer("p - s", &symbols).is_err());
+        assert!(infer("p * 2", &symbols).is_err());
+        assert!(infer("-p", &symbols).is_err());
+        assert!(infer("*c", &symbols).is_err());
+        assert_eq!(
+            infer("x", &symbols),
+            Err(TypeError::UndefinedVariable {
+                name: "x".to_string(),
+                loc: Loc(21, 22)
+            })
+        );
+    }
+
+    #[test]
+    fn test_symbol_table_scope() {
+        let mut symbols = SymbolTable::new();
+        symbols.insert("a", Type::U64);
+        symbols.push_scope();
+        symbols.insert("a", Type::U8);
+        assert_eq!(symbols.lookup("a"), Some(&Type::U8));
+        symbols.pop_scope();
+        assert_eq!(symbols.lookup("a"), Some(&Type::U64));
+        assert_eq!(symbols.lookup("b"), None);
+    }
+}
@@ -1,3 +1,5 @@
+pub mod flow;
+pub mod infer;
 pub mod parser;
 
 use crate::token::{Token, TokenKind};
@@ -8,10 +10,29 @@ use std::fmt;
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum AstKind {
     Num(usize),
+    StrLit(String),
     Variable(String),
+    Param {
+        name: String,
+        ty: Type,
+    },
+    /// `doc` is the `///` comment just before the declaration.
+    /// `lhs` is always a `Variable`, which IR generation checks.
+    /// `rhs` is `None` only for an array, which has no initializer.
     Decl {
         lhs: Box<Ast>,
-        rhs: Box<Ast>,
+        rhs: Option<Box<Ast>>,
+        ty: Type,
+        is_const: bool,
+        // Whether every read and write of the variable must be kept.
+        is_volatile: bool,
+        // Whether the variable lives in data and keeps its value across calls.
+        is_static: bool,
+        doc: Option<String>,
+    },
+    Cast {
+        ty: Type,
+        expr: Box<Ast>,
     },
     UniOp {
         op: UniOpKind,
@@ -22,10 +43,19 @@ pub enum AstKind {
         lhs: Box<Ast>,
         rhs: Box<Ast>,
     },
+    /// `doc` is the `///` comment just before the definition.
     Func {
         name: String,
         params: Vec<Ast>,
         body: Box<Ast>,
+        doc: Option<String>,
+    },
+    /// Declaration of a function defined outside, such as in libc.
+    /// `is_variadic` is true if its parameters end with `...`.
+    ExternFunc {
+        name: String,
+        params: Vec<Ast>,
+        is_variadic: bool,
     },
     FuncCall {
         name: String,
@@ -36,6 +66,12 @@ pub enum AstKind {
         then: Box<Ast>,
         els: Option<Box<Ast>>,
     },
+    /// Conditional expression `cond ? then : els`.
+    Ternary {
+        cond: Box<Ast>,
+        then: Box<Ast>,
+        els: Box<Ast>,
+    },
     CompStmt {
         stmts: Vec<Ast>,
     },
@@ -43,27 +79,119 @@ pub enum AstKind {
         lhs: Box<Ast>,
         rhs: Box<Ast>,
     },
+    /// `sizeof expr`, whose operand is not evaluated.
+    SizeOf {
+        expr: Box<Ast>,
+    },
+    /// `a++` or `a--`, where `op` is `Add` or `Sub`.
+    /// Yields the value of `lhs` before it is updated.
+    PostIncDec {
+        op: BinOpKind,
+        lhs: Box<Ast>,
+    },
     Return {
         expr: Box<Ast>,
     },
+    Goto {
+        label: String,
+    },
+    Label {
+        name: String,
+        stmt: Box<Ast>,
+    },
+    /// Each case is a pair of its value and statements.
+    /// Statements of a case fall through to the next one unless they `break`.
+    Switch {
+        cond: Box<Ast>,
+        cases: Vec<(usize, Vec<Ast>)>,
+        default: Option<Vec<Ast>>,
+    },
+    Break,
+    /// `els` runs when the loop finishes without `break`.
+    While {
+        cond: Box<Ast>,
+        body: Box<Ast>,
+        els: Option<Box<Ast>>,
+    },
+    /// Each of `init`, `cond` and `step` can be omitted.
+    /// A loop without `cond` runs until `break`.
+    /// `els` runs when the loop finishes without `break`.
+    For {
+        init: Option<Box<Ast>>,
+        cond: Option<Box<Ast>>,
+        step: Option<Box<Ast>>,
+        body: Box<Ast>,
+        els: Option<Box<Ast>>,
+    },
+    Continue,
 }
 
 pub type Ast = Annotation<AstKind>;
 
+/// Span covering all top-level ASTs, or an empty `Loc` for an empty program.
+pub fn program_span(asts: &[Ast]) -> Loc {
+    asts.iter()
+        .map(|ast| ast.loc)
+        .reduce(|span, loc| span.merge(&loc))
+        .unwrap_or(Loc(0, 0))
+}
+
 impl Ast {
     pub fn num(n: usize, loc: Loc) -> Self {
         Self::new(AstKind::Num(n), loc)
     }
 
+    pub fn str_lit(string: String, loc: Loc) -> Self {
+        Self::new(AstKind::StrLit(string), loc)
+    }
+
     pub fn variable(var: String, loc: Loc) -> Self {
         Self::new(AstKind::Variable(var), loc)
     }
 
-    pub fn decl(lhs: Ast, rhs: Ast, loc: Loc) -> Self {
+    pub fn param(name: String, ty: Type, loc: Loc) -> Self {
+        Self::new(AstKind::Param { name, ty }, loc)
+    }
+
+    /// Attach a doc comment to a declaration or a function definition.
+    /// Other nodes are returned as they are.
+    pub fn with_doc(mut self, doc: Option<String>) -> Self {
+        if let AstKind::Decl { doc: node_doc, .. } | AstKind::Func { doc: node_doc, .. } =
+            &mut self.value
+        {
+            *node_doc = doc;
+        }
+        self
+    }
+
+    pub fn decl(
+        lhs: Ast,
+        rhs: Option<Ast>,
+        ty: Type,
+        is_const: bool,
+        is_volatile: bool,
+        is_static: bool,
+        loc: Loc,
+    ) -> Self {
         Self::new(
             AstKind::Decl {
                 lhs: Box::new(lhs),
-                rhs: Box::new(rhs),
+                rhs: rhs.map(Box::new),
+                ty,
+                is_const,
+                is_volatile,
+                is_static,
+                doc: None,
+            },
+            loc,
+        )
+    }
+
+    pub fn cast(ty: Type, expr: Ast, loc: Loc) -> Self {
+        Self::new(
+            AstKind::Cast {
+                ty,
+                expr: Box::new(expr),
             },
             loc,
         )
@@ -96,6 +224,18 @@ impl Ast {
                 name,
                 params,
                 body: Box::new(body),
+                doc: None,
+            },
+            loc,
+        )
+    }
+
+    pub fn extern_func(name: String, params: Vec<Ast>, is_variadic: bool, loc: Loc) -> Self {
+        Self::new(
+            AstKind::ExternFunc {
+                name,
+                params,
+                is_variadic,
             },
             loc,
         )
@@ -106,10 +246,7 @@ impl Ast {
     }
 
     pub fn if_stmt(cond: Ast, then: Ast, els: Option<Ast>, loc: Loc) -> Self {
-        let els = match els {
-            Some(els) => Some(Box::new(els)),
-            None => None,
-        };
+        let els = els.map(Box::new);
         Self::new(
             AstKind::If {
                 cond: Box::new(cond),
@@ -120,6 +257,17 @@ impl Ast {
         )
     }
 
+    pub fn ternary(cond: Ast, then: Ast, els: Ast, loc: Loc) -> Self {
+        Self::new(
+            AstKind::Ternary {
+                cond: Box::new(cond),
+                then: Box::new(then),
+                els: Box::new(els),
+            },
+            loc,
+        )
+    }
+
     pub fn comp_stmt(stmts: Vec<Ast>, loc: Loc) -> Self {
         Self::new(AstKind::CompStmt { stmts }, loc)
     }
@@ -142,12 +290,224 @@ impl Ast {
             loc,
         )
     }
+
+    pub fn size_of(expr: Ast, loc: Loc) -> Self {
+        Self::new(
+            AstKind::SizeOf {
+                expr: Box::new(expr),
+            },
+            loc,
+        )
+    }
+
+    pub fn post_inc_dec(op: BinOpKind, lhs: Ast, loc: Loc) -> Self {
+        Self::new(
+            AstKind::PostIncDec {
+                op,
+                lhs: Box::new(lhs),
+            },
+            loc,
+        )
+    }
+
+    pub fn goto(label: String, loc: Loc) -> Self {
+        Self::new(AstKind::Goto { label }, loc)
+    }
+
+    pub fn label(name: String, stmt: Ast, loc: Loc) -> Self {
+        Self::new(
+            AstKind::Label {
+                name,
+                stmt: Box::new(stmt),
+            },
+            loc,
+        )
+    }
+
+    pub fn switch(
+        cond: Ast,
+        cases: Vec<(usize, Vec<Ast>)>,
+        default: Option<Vec<Ast>>,
+        loc: Loc,
+    ) -> Self {
+        Self::new(
+            AstKind::Switch {
+                cond: Box::new(cond),
+                cases,
+                default,
+            },
+            loc,
+        )
+    }
+
+    pub fn break_stmt(loc: Loc) -> Self {
+        Self::new(AstKind::Break, loc)
+    }
+
+    pub fn while_stmt(cond: Ast, body: Ast, els: Option<Ast>, loc: Loc) -> Self {
+        Self::new(
+            AstKind::While {
+                cond: Box::new(cond),
+                body: Box::new(body),
+                els: els.map(Box::new),
+            },
+            loc,
+        )
+    }
+
+    pub fn for_stmt(
+        init: Option<Ast>,
+        cond: Option<Ast>,
+        step: Option<Ast>,
+        body: Ast,
+        els: Option<Ast>,
+        loc: Loc,
+    ) -> Self {
+        Self::new(
+            AstKind::For {
+                init: init.map(Box::new),
+                cond: cond.map(Box::new),
+                step: step.map(Box::new),
+                body: Box::new(body),
+                els: els.map(Box::new),
+            },
+            loc,
+        )
+    }
+
+    pub fn continue_stmt(loc: Loc) -> Self {
+        Self::new(AstKind::Continue, loc)
+    }
+}
+
+/// Data type of a value.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Type {
+    U64,
+    U8,
+    /// Pointer to a value of the type.
+    Ptr(Box<Type>),
+    /// Fixed number of elements of the type placed next to each other.
+    Array(Box<Type>, usize),
+}
+
+impl Type {
+    pub fn ptr(pointee: Type) -> Self {
+        Type::Ptr(Box::new(pointee))
+    }
+
+    pub fn array(elem: Type, len: usize) -> Self {
+        Type::Array(Box::new(elem), len)
+    }
+
+    /// Size of the type in bytes.
+    pub fn size(&self) -> usize {
+        match self {
+            Type::U64 | Type::Ptr(_) => 8,
+            Type::U8 => 1,
+            Type::Array(elem, len) => elem.size() * len,
+        }
+    }
+
+    /// Type of a value of this type: an array is used as a pointer to its first element.
+    pub fn decay(&self) -> Type {
+        match self {
+            Type::Array(elem, _) => Type::Ptr(elem.clone()),
+            ty => ty.clone(),
+        }
+    }
+
+    /// Type which a pointer points to, or `None` if the type is not a pointer.
+    pub fn pointee(&self) -> Option<&Type> {
+        match self {
+            Type::Ptr(pointee) => Some(pointee),
+            _ => None,
+        }
+    }
+
+    /// Truncate `n` into the range this type can represent.
+    pub fn truncate(&self, n: usize) -> usize {
+        match self {
+            Type::U64 | Type::Ptr(_) | Type::Array(..) => n,
+            Type::U8 => n & 0xff,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::U64 => write!(f, "u64"),
+            Type::U8 => write!(f, "u8"),
+            Type::Ptr(pointee) => write!(f, "{}*", pointee),
+            Type::Array(elem, len) => write!(f, "{}[{}]", elem, len),
+        }
+    }
+}
+
+/// Flatten an expression tree into reverse Polish notation: operands, then an operator.
+/// Unary plus and minus are written as `pos` and `neg` to be distinguished from binary ones.
+/// A conditional expression is written as `?:` after its three operands.
+pub fn to_postfix(ast: &Ast) -> Vec<String> {
+    match &ast.value {
+        AstKind::Num(n) => vec![n.to_string()],
+        AstKind::StrLit(string) => vec![format!("{:?}", string)],
+        AstKind::Variable(name) => vec![name.clone()],
+        AstKind::UniOp { op, node } => {
+            let mut postfix = to_postfix(node);
+            postfix.push(op.to_string());
+            postfix
+        }
+        AstKind::BinOp { op, lhs, rhs } => {
+            let mut postfix = to_postfix(lhs);
+            postfix.extend(to_postfix(rhs));
+            postfix.push(op.to_string());
+            postfix
+        }
+        AstKind::Cast { ty, expr } => {
+            let mut postfix = to_postfix(expr);
+            postfix.push(format!("({})", ty));
+            postfix
+        }
+        AstKind::SizeOf { expr } => {
+            let mut postfix = to_postfix(expr);
+            postfix.push("sizeof".to_string());
+            postfix
+        }
+        AstKind::PostIncDec { op, lhs } => {
+            let mut postfix = to_postfix(lhs);
+            postfix.push(format!("post{}{}", op, op));
+            postfix
+        }
+        AstKind::FuncCall { name, args } => {
+            let mut postfix: Vec<String> = args.iter().flat_map(to_postfix).collect();
+            postfix.push(format!("{}/{}", name, args.len()));
+            postfix
+        }
+        AstKind::Ternary { cond, then, els } => {
+            let mut postfix = to_postfix(cond);
+            postfix.extend(to_postfix(then));
+            postfix.extend(to_postfix(els));
+            postfix.push("?:".to_string());
+            postfix
+        }
+        AstKind::Assignment { lhs, rhs } => {
+            let mut postfix = to_postfix(lhs);
+            postfix.extend(to_postfix(rhs));
+            postfix.push("=".to_string());
+            postfix
+        }
+        _ => panic!("Not an expression: {:?}", ast),
+    }
 }
 
 /// Data type of unary operator.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum UniOpKind {
+    Plus,
     Minus,
+    /// Value at the address held by a pointer.
+    Deref,
 }
 
 /// Data type of binary operator.
@@ -157,17 +517,168 @@ pub enum BinOpKind {
     Sub,
     Mul,
     Div,
+    Rem,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    LogAnd,
+    LogOr,
+}
+
+impl fmt::Display for UniOpKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UniOpKind::Plus => write!(f, "pos"),
+            UniOpKind::Minus => write!(f, "neg"),
+            UniOpKind::Deref => write!(f, "deref"),
+        }
+    }
+}
+
+impl fmt::Display for BinOpKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            BinOpKind::Add => "+",
+            BinOpKind::Sub => "-",
+            BinOpKind::Mul => "*",
+            BinOpKind::Div => "/",
+            BinOpKind::Rem => "%",
+            BinOpKind::BitAnd => "&",
+            BinOpKind::BitOr => "|",
+            BinOpKind::BitXor => "^",
+            BinOpKind::Shl => "<<",
+            BinOpKind::Shr => ">>",
+            BinOpKind::Eq => "==",
+            BinOpKind::Ne => "!=",
+            BinOpKind::Lt => "<",
+            BinOpKind::Le => "<=",
+            BinOpKind::Gt => ">",
+            BinOpKind::Ge => ">=",
+            BinOpKind::LogAnd => "&&",
+            BinOpKind::LogOr => "||",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+impl UniOpKind {
+    /// Apply the operator to a constant. A constant is not dereferenced.
+    pub fn eval(&self, operand: i64) -> Result<i64, ArithError> {
+        match self {
+            UniOpKind::Plus => Ok(operand),
+            UniOpKind::Minus => Ok(operand.wrapping_neg()),
+            UniOpKind::Deref => Err(ArithError::Deref),
+        }
+    }
+}
+
+impl BinOpKind {
+    /// Apply the operator to two constants as the generated code does.
+    /// Arithmetic wraps around, and comparisons and logical operators yield `1` or `0`.
+    pub fn eval(&self, lhs: i64, rhs: i64) -> Result<i64, ArithError> {
+        let n = match self {
+            BinOpKind::Add => lhs.wrapping_add(rhs),
+            BinOpKind::Sub => lhs.wrapping_sub(rhs),
+            BinOpKind::Mul => lhs.wrapping_mul(rhs),
+            BinOpKind::Div | BinOpKind::Rem if rhs == 0 => return Err(ArithError::DivByZero),
+            // `idiv` traps on this as well as on zero.
+            BinOpKind::Div | BinOpKind::Rem if lhs == i64::MIN && rhs == -1 => {
+                return Err(ArithError::Overflow)
+            }
+            BinOpKind::Div => lhs / rhs,
+            BinOpKind::Rem => lhs % rhs,
+            BinOpKind::BitAnd => lhs & rhs,
+            BinOpKind::BitOr => lhs | rhs,
+            BinOpKind::BitXor => lhs ^ rhs,
+            // Shift count is masked to 6 bits as x86-64 does.
+            BinOpKind::Shl => lhs.wrapping_shl(rhs as u32),
+            BinOpKind::Shr => lhs.wrapping_shr(rhs as u32),
+            BinOpKind::Eq => (lhs == rhs) as i64,
+            BinOpKind::Ne => (lhs != rhs) as i64,
+            BinOpKind::Lt => (lhs < rhs) as i64,
+            BinOpKind::Le => (lhs <= rhs) as i64,
+            BinOpKind::Gt => (lhs > rhs) as i64,
+            BinOpKind::Ge => (lhs >= rhs) as i64,
+            BinOpKind::LogAnd => (lhs != 0 && rhs != 0) as i64,
+            BinOpKind::LogOr => (lhs != 0 || rhs != 0) as i64,
+        };
+        Ok(n)
+    }
+}
+
+/// Error of evaluating an operator on constants.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ArithError {
+    DivByZero,
+    /// `i64::MIN / -1`, whose result is not representable.
+    Overflow,
+    /// Dereference, which needs memory rather than a constant.
+    Deref,
+}
+
+impl fmt::Display for ArithError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArithError::DivByZero => write!(f, "Division by zero"),
+            ArithError::Overflow => write!(f, "Division overflow"),
+            ArithError::Deref => write!(f, "Dereference of a constant"),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ParseError {
     UnexpectedToken(TokenKind, Token),
+    /// A specific token is required but another one is found.
+    Expected {
+        expected: TokenKind,
+        found: Token,
+    },
     NotExpression(Token),
     NotOperator(Token),
-    UnclosedOpenParen(Token),
+    /// Location of the open parenthesis which is never closed.
+    UnclosedOpenParen(Loc),
     RedundantExpression(Token),
     NoSemicolon(Token),
-    Eof,
+    /// Left hand side of an assignment which is not a variable.
+    InvalidAssignTarget(Loc),
+    /// Value of a `case` which already appears in the same switch.
+    DuplicateCase(Token),
+    /// Location just after the last token.
+    Eof(Loc),
+    /// Errors are omitted after this.
+    TooManyErrors,
+}
+
+impl ParseError {
+    pub fn expected(expected: TokenKind, found: Token) -> Self {
+        ParseError::Expected { expected, found }
+    }
+
+    /// Location where the error is found, or `None` for `TooManyErrors`.
+    pub fn loc(&self) -> Option<Loc> {
+        match self {
+            ParseError::UnexpectedToken(_, token)
+            | ParseError::Expected { found: token, .. }
+            | ParseError::NotExpression(token)
+            | ParseError::NotOperator(token)
+            | ParseError::RedundantExpression(token)
+            | ParseError::NoSemicolon(token)
+            | ParseError::DuplicateCase(token) => Some(token.loc()),
+            ParseError::UnclosedOpenParen(loc)
+            | ParseError::InvalidAssignTarget(loc)
+            | ParseError::Eof(loc) => Some(*loc),
+            ParseError::TooManyErrors => None,
+        }
+    }
 }
 
 impl fmt::Display for ParseError {
@@ -176,18 +687,77 @@ impl fmt::Display for ParseError {
             ParseError::UnexpectedToken(expected, actual) => write!(
                 f,
                 "{}: Unexpected token '{}', expected {}",
-                actual.loc, actual.value, expected
+                actual.loc(),
+                actual.kind(),
+                expected
+            ),
+            ParseError::Expected { expected, found } => write!(
+                f,
+                "{}: expected `{}`, found `{}`",
+                found.loc(),
+                expected,
+                found.kind()
             ),
-            ParseError::NotExpression(t) => write!(f, "{}: Not expression '{}'", t.loc, t.value),
-            ParseError::NotOperator(t) => write!(f, "{}: Not operator '{}'", t.loc, t.value),
-            ParseError::UnclosedOpenParen(t) => {
-                write!(f, "{}: Unclosed open parenthesis '{}'", t.loc, t.value)
+            ParseError::NotExpression(t) => write!(f, "{}: Not expression '{}'", t.loc(), t.kind()),
+            ParseError::NotOperator(t) => write!(f, "{}: Not operator '{}'", t.loc(), t.kind()),
+            ParseError::UnclosedOpenParen(loc) => {
+                write!(f, "{}: Unclosed open parenthesis '('", loc)
             }
             ParseError::RedundantExpression(t) => {
-                write!(f, "{}: Redundant expression '{}'", t.loc, t.value)
+                write!(f, "{}: Redundant expression '{}'", t.loc(), t.kind())
+            }
+            ParseError::NoSemicolon(t) => {
+                write!(f, "{}: Missing semicolon '{:}'", t.loc(), t.kind())
+            }
+            ParseError::InvalidAssignTarget(loc) => {
+                write!(f, "{}: Cannot assign to this expression", loc)
+            }
+            ParseError::DuplicateCase(t) => {
+                write!(f, "{}: Duplicate case value '{}'", t.loc(), t.kind())
             }
-            ParseError::NoSemicolon(t) => write!(f, "{}: Missing semicolon '{:}'", t.loc, t.value),
-            ParseError::Eof => write!(f, "End of file"),
+            ParseError::TooManyErrors => write!(f, "Too many errors, stop reporting"),
+            ParseError::Eof(loc) => write!(f, "{}: Unexpected end of file", loc),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::{ArithError, BinOpKind, UniOpKind};
+
+    #[test]
+    fn test_eval() {
+        let cases = [
+            (BinOpKind::Add, 7, -3, 4),
+            (BinOpKind::Sub, 7, 10, -3),
+            (BinOpKind::Mul, -7, 3, -21),
+            (BinOpKind::Div, -7, 2, -3),
+            (BinOpKind::Rem, -7, 2, -1),
+            (BinOpKind::BitAnd, 0b1100, 0b1010, 0b1000),
+            (BinOpKind::BitOr, 0b1100, 0b1010, 0b1110),
+            (BinOpKind::BitXor, 0b1100, 0b1010, 0b0110),
+            (BinOpKind::Shl, 1, 65, 2),
+            (BinOpKind::Shr, -8, 1, -4),
+            (BinOpKind::Eq, 1, 1, 1),
+            (BinOpKind::Ne, 1, 1, 0),
+            (BinOpKind::Lt, -1, 0, 1),
+            (BinOpKind::Le, 0, 0, 1),
+            (BinOpKind::Gt, -1, 0, 0),
+            (BinOpKind::Ge, 0, 1, 0),
+            (BinOpKind::LogAnd, 2, -1, 1),
+            (BinOpKind::LogOr, 0, 0, 0),
+        ];
+        for (op, lhs, rhs, expected) in cases.iter() {
+            assert_eq!(op.eval(*lhs, *rhs), Ok(*expected), "{} {} {}", lhs, op, rhs);
+        }
+        assert_eq!(BinOpKind::Add.eval(i64::MAX, 1), Ok(i64::MIN));
+        assert_eq!(BinOpKind::Div.eval(1, 0), Err(ArithError::DivByZero));
+        assert_eq!(BinOpKind::Rem.eval(1, 0), Err(ArithError::DivByZero));
+        assert_eq!(BinOpKind::Div.eval(i64::MIN, -1), Err(ArithError::Overflow));
+
+        assert_eq!(UniOpKind::Plus.eval(-5), Ok(-5));
+        assert_eq!(UniOpKind::Minus.eval(-5), Ok(5));
+        assert_eq!(UniOpKind::Minus.eval(i64::MIN), Ok(i64::MIN));
+        assert_eq!(UniOpKind::Deref.eval(1), Err(ArithError::Deref));
+    }
+}
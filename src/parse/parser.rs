@@ -1,6 +1,8 @@
-use crate::parse::{Ast, AstKind, BinOpKind, ParseError, UniOpKind};
-use crate::token::{Token, TokenKind};
-use crate::Loc;
+use std::collections::HashMap;
+
+use crate::parse::{Ast, AstKind, BinOpKind, ParseError, Type, UniOpKind};
+use crate::token::{IntSuffix, Token, TokenKind};
+use crate::{Annotation, Loc};
 
 #[derive(Debug, Clone)]
 pub struct Parser<'a> {
@@ -8,11 +10,131 @@ pub struct Parser<'a> {
     tokens: &'a Vec<Token>,
     // Current position of a token stream.
     pos: usize,
+    // Whether to continue parsing after an error.
+    recovering: bool,
+    // Errors collected while recovering.
+    errors: Vec<ParseError>,
+    // Maximum number of errors collected while recovering.
+    max_errors: usize,
+    // Type aliases defined by `typedef`.
+    type_aliases: HashMap<String, Type>,
+    // Doc comments given from `Lexer`, attached to declarations.
+    doc_comments: &'a [Annotation<String>],
+    // Whether statements are allowed at the top level for REPL,
+    // where the last expression may omit `;` to be returned.
+    allow_trailing_expr: bool,
+    // Binary operators with their kind and precedence.
+    binops: HashMap<TokenKind, (BinOpKind, u8)>,
 }
 
+/// Binary operators of the language. Higher precedence binds tighter.
+const DEFAULT_BINOPS: [(TokenKind, BinOpKind, u8); 18] = [
+    (TokenKind::LogOr, BinOpKind::LogOr, 1),
+    (TokenKind::LogAnd, BinOpKind::LogAnd, 2),
+    (TokenKind::Pipe, BinOpKind::BitOr, 3),
+    (TokenKind::Caret, BinOpKind::BitXor, 4),
+    (TokenKind::Ampersand, BinOpKind::BitAnd, 5),
+    (TokenKind::Equal, BinOpKind::Eq, 6),
+    (TokenKind::NotEqual, BinOpKind::Ne, 6),
+    (TokenKind::Lt, BinOpKind::Lt, 7),
+    (TokenKind::Le, BinOpKind::Le, 7),
+    (TokenKind::Gt, BinOpKind::Gt, 7),
+    (TokenKind::Ge, BinOpKind::Ge, 7),
+    (TokenKind::Shl, BinOpKind::Shl, 8),
+    (TokenKind::Shr, BinOpKind::Shr, 8),
+    (TokenKind::Plus, BinOpKind::Add, 9),
+    (TokenKind::Minus, BinOpKind::Sub, 9),
+    (TokenKind::Asterisk, BinOpKind::Mul, 10),
+    (TokenKind::Slash, BinOpKind::Div, 10),
+    (TokenKind::Percent, BinOpKind::Rem, 10),
+];
+
 impl<'a> Parser<'a> {
     pub fn new(tokens: &'a Vec<Token>) -> Self {
-        Parser { tokens, pos: 0 }
+        Parser {
+            tokens,
+            pos: 0,
+            recovering: false,
+            errors: Vec::new(),
+            max_errors: 20,
+            type_aliases: HashMap::new(),
+            doc_comments: &[],
+            allow_trailing_expr: false,
+            binops: DEFAULT_BINOPS
+                .iter()
+                .map(|(token, kind, prec)| (token.clone(), (kind.clone(), *prec)))
+                .collect(),
+        }
+    }
+
+    /// Repoint the parser at `tokens` of another program and parse it from the beginning.
+    /// State of the previous program, such as type aliases, is discarded.
+    /// Only `max_errors` and registered binary operators are kept.
+    pub fn reset(&mut self, tokens: &'a Vec<Token>) {
+        *self = Parser {
+            max_errors: self.max_errors,
+            binops: std::mem::take(&mut self.binops),
+            ..Parser::new(tokens)
+        };
+    }
+
+    /// Parse `token` as a binary operator of `kind`, which binds tighter than
+    /// operators of lower `precedence`. `||` has 1 and `*` has 10.
+    /// The existing meaning of `token` as a binary operator is replaced.
+    pub fn register_binop(&mut self, token: TokenKind, kind: BinOpKind, precedence: u8) {
+        self.binops.insert(token, (kind, precedence));
+    }
+
+    /// Set the maximum number of errors `parse_recovering` reports.
+    pub fn with_max_errors(self, max_errors: usize) -> Self {
+        Parser { max_errors, ..self }
+    }
+
+    /// Attach `doc_comments` to declarations and function definitions which follow them.
+    pub fn with_doc_comments(self, doc_comments: &'a [Annotation<String>]) -> Self {
+        Parser {
+            doc_comments,
+            ..self
+        }
+    }
+
+    /// Accept statements at the top level as a REPL does.
+    /// An expression at the end without `;` is wrapped in an implicit `return`.
+    pub fn allow_trailing_expr(self) -> Self {
+        Parser {
+            allow_trailing_expr: true,
+            ..self
+        }
+    }
+
+    /// Doc comments between the previous token and the current one, joined by newlines.
+    fn doc_comment(&self) -> Option<String> {
+        let start = match self.pos {
+            0 => 0,
+            pos => self.tokens[pos - 1].loc().1,
+        };
+        let end = self.current_loc().0;
+        let lines: Vec<&str> = self
+            .doc_comments
+            .iter()
+            .filter(|comment| start <= comment.loc.0 && comment.loc.1 <= end)
+            .map(|comment| comment.value.as_str())
+            .collect();
+        if lines.is_empty() {
+            None
+        } else {
+            Some(lines.join("\n"))
+        }
+    }
+
+    /// Record an error found while recovering.
+    /// Errors beyond `max_errors` are replaced by a single `TooManyErrors`.
+    fn push_error(&mut self, error: ParseError) {
+        if self.errors.len() < self.max_errors {
+            self.errors.push(error);
+        } else if self.errors.len() == self.max_errors {
+            self.errors.push(ParseError::TooManyErrors);
+        }
     }
 
     /// Take a look at a next token and return its kind.
@@ -20,12 +142,23 @@ impl<'a> Parser<'a> {
         if self.tokens.len() == self.pos {
             return None;
         }
-        Some(&self.tokens[self.pos].value)
+        Some(self.tokens[self.pos].kind())
+    }
+
+    /// Take a look at a token after the next one and return its kind.
+    fn peek_second(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos + 1).map(Token::kind)
+    }
+
+    /// Whether no token is left except `Eof`.
+    fn is_eof(&self) -> bool {
+        matches!(self.peek(), None | Some(&TokenKind::Eof))
     }
 
     /// Return current token and move `pos` forward.
+    /// `Eof` is never consumed.
     fn next(&mut self) -> Option<Token> {
-        if self.tokens.len() == self.pos {
+        if self.is_eof() {
             return None;
         }
         let token = self.tokens[self.pos].clone();
@@ -33,142 +166,377 @@ impl<'a> Parser<'a> {
         Some(token)
     }
 
+    /// Return current token and move `pos` forward, or `Eof` if tokens are exhausted.
+    fn next_or_eof(&mut self) -> Result<Token, ParseError> {
+        let loc = self.current_loc();
+        self.next().ok_or(ParseError::Eof(loc))
+    }
+
+    /// Location of the current token, or an empty location just after the last token
+    /// if tokens are exhausted.
+    fn current_loc(&self) -> Loc {
+        match self.tokens.get(self.pos) {
+            Some(token) => token.loc(),
+            None => {
+                let end = self.tokens.last().map_or(0, |token| token.loc().1);
+                Loc(end, end)
+            }
+        }
+    }
+
     /// Check if a current token has expected type and proceed to next one.
     fn expect_token(&mut self, token_kind: TokenKind) -> Result<(), ParseError> {
-        self.next().ok_or(ParseError::Eof).and_then(|token| {
-            if token.value == token_kind {
+        self.next_or_eof().and_then(|token| {
+            if *token.kind() == token_kind {
+                Ok(())
+            } else {
+                Err(ParseError::expected(token_kind, token))
+            }
+        })
+    }
+
+    /// Check if a current token is `;` and proceed to next one.
+    fn expect_semicolon(&mut self) -> Result<(), ParseError> {
+        self.next_or_eof().and_then(|token| {
+            if *token.kind() == TokenKind::Semicolon {
                 Ok(())
             } else {
-                Err(ParseError::UnexpectedToken(token_kind, token))
+                Err(ParseError::NoSemicolon(token))
             }
         })
     }
 
+    /// Check if a current token is an identifier and return its name and location.
+    fn expect_identifier(&mut self) -> Result<(String, Loc), ParseError> {
+        self.next_or_eof().and_then(|token| match token.kind() {
+            TokenKind::Identifier(name) => Ok((name.clone(), token.loc())),
+            _ => Err(ParseError::UnexpectedToken(
+                TokenKind::Identifier("identifier".to_string()),
+                token,
+            )),
+        })
+    }
+
     /// Parse tokens and build AST.
     pub fn parse(&mut self) -> Result<Vec<Ast>, ParseError> {
         let mut asts = Vec::new();
-        loop {
-            let ast = self.parse_func_def()?;
-            asts.push(ast);
-            if self.peek().is_none() {
-                break;
+        while !self.is_eof() {
+            if let Some(ast) = self.parse_top_level()? {
+                asts.push(ast);
             }
         }
         Ok(asts)
     }
 
+    /// Parse tokens and build AST, recovering from errors to report as many of them as possible.
+    pub fn parse_recovering(&mut self) -> (Vec<Ast>, Vec<ParseError>) {
+        self.recovering = true;
+        let mut asts = Vec::new();
+        while !self.is_eof() && self.errors.len() <= self.max_errors {
+            let start = self.pos;
+            match self.parse_top_level() {
+                Ok(Some(ast)) => asts.push(ast),
+                Ok(None) => (),
+                Err(error) => {
+                    self.push_error(error);
+                    if self.pos == start {
+                        self.next();
+                    }
+                    while !self.is_eof()
+                        && !matches!(
+                            self.peek(),
+                            Some(&TokenKind::Func) | Some(&TokenKind::Typedef)
+                        )
+                    {
+                        self.next();
+                    }
+                }
+            }
+        }
+        self.recovering = false;
+        (asts, std::mem::take(&mut self.errors))
+    }
+
+    /// Skip tokens until a point where parsing a statement can restart:
+    /// after `;`, or before `}` or a keyword which starts a statement.
+    fn synchronize(&mut self) {
+        while let Some(token_kind) = self.peek() {
+            match token_kind {
+                TokenKind::Semicolon => {
+                    self.next();
+                    return;
+                }
+                TokenKind::RBrace
+                | TokenKind::Let
+                | TokenKind::Const
+                | TokenKind::Static
+                | TokenKind::If
+                | TokenKind::Else
+                | TokenKind::Return
+                | TokenKind::Goto
+                | TokenKind::Switch
+                | TokenKind::Break
+                | TokenKind::While
+                | TokenKind::For
+                | TokenKind::Continue
+                | TokenKind::Func
+                | TokenKind::Eof => return,
+                _ => {
+                    self.next();
+                }
+            }
+        }
+    }
+
     /// BNF:
     ///     FUNC_DEF ::= "func" IDENTIFIER "(" PARAMS ")" COMP_STMT
     fn parse_func_def(&mut self) -> Result<Ast, ParseError> {
+        let doc = self.doc_comment();
+        let func_loc = self.current_loc();
         self.expect_token(TokenKind::Func)?;
-        let func_name = self
-            .next()
-            .ok_or(ParseError::Eof)
-            .and_then(|token| match token.value {
-                TokenKind::Identifier(name) => Ok(name),
-                _ => Err(ParseError::UnexpectedToken(
-                    TokenKind::Identifier("func".to_string()),
-                    token,
-                )),
-            })?;
-
-        let params = self.parse_params()?;
+        let (func_name, _) = self.expect_identifier()?;
+
+        let (params, _) = self.parse_params(false)?;
         let body = self.parse_comp_stmt()?;
-        let loc = body.loc.clone();
-        Ok(Ast::func(func_name, params, body, loc))
+        // From `func` to the closing brace.
+        let loc = func_loc.merge(&self.tokens[self.pos - 1].loc());
+        Ok(Ast::func(func_name, params, body, loc).with_doc(doc))
+    }
+
+    /// BNF:
+    ///     EXTERN_FUNC ::= "extern" "func" IDENTIFIER "(" PARAMS ")" ";"
+    /// `PARAMS` may end with `...` to accept any number of arguments.
+    fn parse_extern_func(&mut self) -> Result<Ast, ParseError> {
+        let extern_loc = self.current_loc();
+        self.expect_token(TokenKind::Extern)?;
+        self.expect_token(TokenKind::Func)?;
+        let (func_name, _) = self.expect_identifier()?;
+        let (params, is_variadic) = self.parse_params(true)?;
+        let loc = extern_loc.merge(&self.current_loc());
+        self.expect_semicolon()?;
+        Ok(Ast::extern_func(func_name, params, is_variadic, loc))
+    }
+
+    /// BNF:
+    ///     TOP_LEVEL ::= TYPEDEF | FUNC_DEF | EXTERN_FUNC
+    /// `typedef` only registers an alias and produces no AST.
+    /// With `allow_trailing_expr`, `STMT` and `ASSIGN` at the end are also accepted.
+    fn parse_top_level(&mut self) -> Result<Option<Ast>, ParseError> {
+        match self.peek() {
+            Some(&TokenKind::Typedef) => {
+                self.parse_typedef()?;
+                Ok(None)
+            }
+            Some(&TokenKind::Func) => self.parse_func_def().map(Some),
+            Some(&TokenKind::Extern) => self.parse_extern_func().map(Some),
+            _ if self.allow_trailing_expr => self.parse_trailing_expr().map(Some),
+            _ => self.parse_func_def().map(Some),
+        }
+    }
+
+    /// Parse a statement, or an expression without `;` at the end of tokens
+    /// as a `return` of it.
+    fn parse_trailing_expr(&mut self) -> Result<Ast, ParseError> {
+        let start = self.pos;
+        match self.parse_assign() {
+            Ok(expr) if self.is_eof() => {
+                let loc = expr.loc;
+                Ok(Ast::return_stmt(expr, loc))
+            }
+            // Not an expression statement, or followed by `;`.
+            _ => {
+                self.pos = start;
+                self.parse_stmt()
+            }
+        }
     }
 
     /// BNF:
-    ///     PARAMS ::= PARAM*
-    fn parse_params(&mut self) -> Result<Vec<Ast>, ParseError> {
+    ///     TYPEDEF ::= "typedef" TYPE IDENTIFIER ";"
+    fn parse_typedef(&mut self) -> Result<(), ParseError> {
+        self.expect_token(TokenKind::Typedef)?;
+        let ty = self.parse_type()?;
+        let (name, _) = self.expect_identifier()?;
+        self.expect_semicolon()?;
+        self.type_aliases.insert(name, ty);
+        Ok(())
+    }
+
+    /// BNF:
+    ///     PARAMS ::= "(" (PARAM ("," PARAM)* ("," "...")?)? ")"
+    /// `...` is accepted only if `allow_variadic`, and whether it appears is returned.
+    fn parse_params(&mut self, allow_variadic: bool) -> Result<(Vec<Ast>, bool), ParseError> {
         self.expect_token(TokenKind::LParen)?;
         let mut vec_param: Vec<Ast> = Vec::new();
         // Todo: more simple way to extract `TokenKind`
         if self.peek() == Some(&TokenKind::RParen) {
             self.next();
-            return Ok(vec_param);
+            return Ok((vec_param, false));
         }
 
         vec_param.push(self.parse_param()?);
+        let mut is_variadic = false;
         while self.peek() == Some(&TokenKind::Comma) {
             self.next();
+            if allow_variadic && self.peek() == Some(&TokenKind::Ellipsis) {
+                self.next();
+                is_variadic = true;
+                break;
+            }
             vec_param.push(self.parse_param()?);
         }
         self.expect_token(TokenKind::RParen)?;
-        Ok(vec_param)
+        Ok((vec_param, is_variadic))
     }
 
+    /// BNF:
+    ///     PARAM ::= IDENTIFIER ":" TYPE
     fn parse_param(&mut self) -> Result<Ast, ParseError> {
-        self.next()
-            .ok_or(ParseError::Eof)
-            .and_then(|token| match token.value {
-                TokenKind::Identifier(var) => {
-                    self.expect_token(TokenKind::Colon)?;
-                    self.expect_token(TokenKind::U64)?;
-                    Ok(Ast::new(AstKind::Variable(var), token.loc))
+        let (var, loc) = self.expect_identifier()?;
+        self.expect_token(TokenKind::Colon)?;
+        let ty = self.parse_type()?;
+        Ok(Ast::param(var, ty, loc))
+    }
+
+    /// BNF:
+    ///     TYPE ::= ("u64" | "u8" | TYPE_ALIAS) "*"* ("[" NUM "]")*
+    fn parse_type(&mut self) -> Result<Type, ParseError> {
+        let mut ty = self.next_or_eof().and_then(|token| match token.kind() {
+            TokenKind::U64 => Ok(Type::U64),
+            TokenKind::U8 => Ok(Type::U8),
+            TokenKind::Identifier(name) if self.type_aliases.contains_key(name) => {
+                Ok(self.type_aliases[name].clone())
+            }
+            _ => Err(ParseError::UnexpectedToken(TokenKind::U64, token)),
+        })?;
+        while self.peek() == Some(&TokenKind::Asterisk) {
+            self.next();
+            ty = Type::ptr(ty);
+            // `restrict` only promises no aliasing, which nothing relies on.
+            while self.peek() == Some(&TokenKind::Restrict) {
+                self.next();
+            }
+        }
+        // `u64[2][3]` is two arrays of three `u64`, as the outer length is written first.
+        let mut lens = Vec::new();
+        while self.peek() == Some(&TokenKind::LBracket) {
+            self.next();
+            let token = self.next_or_eof()?;
+            match *token.kind() {
+                TokenKind::Number(len, _) => lens.push(len),
+                _ => {
+                    return Err(ParseError::UnexpectedToken(
+                        TokenKind::Number(0, IntSuffix::None),
+                        token,
+                    ))
                 }
-                _ => Err(ParseError::UnexpectedToken(
-                    TokenKind::Identifier("variable".to_string()),
-                    token.clone(),
-                )),
-            })
+            }
+            self.expect_token(TokenKind::RBracket)?;
+        }
+        for len in lens.into_iter().rev() {
+            ty = Type::array(ty, len);
+        }
+        Ok(ty)
     }
 
     /// BNF:
-    ///     STMT ::= DECL_VAR | IF_STMT | COMP_STMT | RETURN_STMT | ASSIGN ";"
+    ///     QUALIFIER ::= "volatile" | "restrict"
+    /// Returns whether `volatile` is found.
+    fn parse_qualifiers(&mut self) -> bool {
+        let mut is_volatile = false;
+        loop {
+            match self.peek() {
+                Some(&TokenKind::Volatile) => is_volatile = true,
+                Some(&TokenKind::Restrict) => (),
+                _ => return is_volatile,
+            }
+            self.next();
+        }
+    }
+
+    /// Whether a type begins at `pos`.
+    fn is_type_at(&self, pos: usize) -> bool {
+        match self.tokens.get(pos).map(Token::kind) {
+            Some(TokenKind::U64) | Some(TokenKind::U8) => true,
+            Some(TokenKind::Identifier(name)) => self.type_aliases.contains_key(name),
+            _ => false,
+        }
+    }
+
+    /// BNF:
+    ///     STMT ::= DECL_VAR | IF_STMT | COMP_STMT | RETURN_STMT | GOTO_STMT | LABELED_STMT | ASSIGN ";"
     fn parse_stmt(&mut self) -> Result<Ast, ParseError> {
         match self.peek() {
-            Some(&TokenKind::Let) => self.parse_decl_var(),
+            Some(&TokenKind::Let) | Some(&TokenKind::Const) | Some(&TokenKind::Static) => {
+                self.parse_decl_var()
+            }
             Some(&TokenKind::If) => self.parse_if(),
             Some(&TokenKind::LBrace) => self.parse_comp_stmt(),
             Some(&TokenKind::Return) => self.parse_return(),
+            Some(&TokenKind::Goto) => self.parse_goto(),
+            Some(&TokenKind::Switch) => self.parse_switch(),
+            Some(&TokenKind::Break) => self.parse_break(),
+            Some(&TokenKind::While) => self.parse_while(),
+            Some(&TokenKind::For) => self.parse_for(),
+            Some(&TokenKind::Continue) => self.parse_continue(),
+            Some(&TokenKind::Identifier(_)) if self.peek_second() == Some(&TokenKind::Colon) => {
+                self.parse_labeled_stmt()
+            }
             _ => {
                 let ast = self.parse_assign()?;
-                self.expect_token(TokenKind::Semicolon)?;
+                self.expect_semicolon()?;
                 Ok(ast)
             }
         }
     }
 
     /// BNF:
-    ///     DECL_VAR ::= "let" VARIABLE ":" TYPE "=" ADD
-    ///     TYPE     ::= "u64"
+    ///     DECL_VAR ::= ("let" | "const" | "static") VARIABLE ":" QUALIFIER* TYPE ("=" TERNARY)?
     fn parse_decl_var(&mut self) -> Result<Ast, ParseError> {
-        self.next();
-        self.next()
-            .ok_or(ParseError::Eof)
-            .and_then(|token| match token.value {
-                TokenKind::Identifier(var) => {
-                    let lhs = Ast::new(AstKind::Variable(var), token.loc);
-                    self.expect_token(TokenKind::Colon)?;
-                    self.expect_token(TokenKind::U64)?;
-                    self.expect_token(TokenKind::Assignment)?;
-                    let rhs = self.parse_add()?;
-                    let loc = lhs.loc.merge(&rhs.loc);
-                    self.expect_token(TokenKind::Semicolon)?;
-                    Ok(Ast::new(
-                        AstKind::Decl {
-                            lhs: Box::new(lhs),
-                            rhs: Box::new(rhs),
-                        },
-                        loc,
-                    ))
-                }
-                _ => Err(ParseError::UnexpectedToken(
-                    TokenKind::Identifier("variable".to_string()),
-                    token.clone(),
-                )),
-            })
+        let doc = self.doc_comment();
+        let keyword = self.next().map(|token| token.kind().clone());
+        let is_const = keyword == Some(TokenKind::Const);
+        let is_static = keyword == Some(TokenKind::Static);
+        let (var, var_loc) = self.expect_identifier()?;
+        let lhs = Ast::variable(var, var_loc);
+        self.expect_token(TokenKind::Colon)?;
+        let is_volatile = self.parse_qualifiers();
+        let ty = self.parse_type()?;
+        // An array has no initializer, and its elements are assigned one by one.
+        let rhs = match ty {
+            Type::Array(..) => None,
+            _ => {
+                self.expect_token(TokenKind::Assignment)?;
+                Some(self.parse_ternary()?)
+            }
+        };
+        let loc = rhs.as_ref().map_or(lhs.loc, |rhs| lhs.loc.merge(&rhs.loc));
+        self.expect_semicolon()?;
+        Ok(Ast::decl(lhs, rhs, ty, is_const, is_volatile, is_static, loc).with_doc(doc))
     }
 
     ///BNF:
-    ///    IF_STMT ::= "if" ASSIGN COMP_STMT
+    ///    IF_STMT ::= "if" ASSIGN COMP_STMT ("else" (IF_STMT | COMP_STMT))?
     fn parse_if(&mut self) -> Result<Ast, ParseError> {
         self.next();
         let cond = self.parse_assign()?;
         let then = self.parse_comp_stmt()?;
-        let loc = cond.loc.merge(&then.loc);
-        Ok(Ast::if_stmt(cond, then, None, loc))
+        // Bodies are always braced, so `else` belongs to the nearest `if` without ambiguity.
+        let els = if self.peek() == Some(&TokenKind::Else) {
+            self.next();
+            if self.peek() == Some(&TokenKind::If) {
+                Some(self.parse_if()?)
+            } else {
+                Some(self.parse_comp_stmt()?)
+            }
+        } else {
+            None
+        };
+        let loc = match &els {
+            Some(els) => cond.loc.merge(&els.loc),
+            None => cond.loc.merge(&then.loc),
+        };
+        Ok(Ast::if_stmt(cond, then, els, loc))
     }
 
     /// BNF:
@@ -176,9 +544,34 @@ impl<'a> Parser<'a> {
     fn parse_comp_stmt(&mut self) -> Result<Ast, ParseError> {
         self.expect_token(TokenKind::LBrace)?;
         let mut vec_stmt = Vec::new();
-        let mut loc = Loc(std::usize::MAX, 0);
+        // An empty block has nothing to point at.
+        let mut loc = Loc::dummy();
         while self.peek() != Some(&TokenKind::RBrace) {
-            let stmt = self.parse_stmt()?;
+            if self.is_eof() {
+                return Err(ParseError::Eof(self.current_loc()));
+            }
+            if self.recovering && self.peek() == Some(&TokenKind::Func) {
+                // Next function begins before this block is closed.
+                let token = self.tokens[self.pos].clone();
+                self.push_error(ParseError::UnexpectedToken(TokenKind::RBrace, token));
+                return Ok(Ast::comp_stmt(vec_stmt, loc));
+            }
+            let start = self.pos;
+            let stmt = match self.parse_stmt() {
+                Ok(stmt) => stmt,
+                Err(error) if self.recovering && !matches!(error, ParseError::Eof(_)) => {
+                    self.push_error(error);
+                    if self.pos == start {
+                        self.next();
+                    }
+                    // The statement may have ended at the token which caused the error.
+                    if *self.tokens[self.pos - 1].kind() != TokenKind::Semicolon {
+                        self.synchronize();
+                    }
+                    continue;
+                }
+                Err(error) => return Err(error),
+            };
             loc = loc.merge(&stmt.loc);
             vec_stmt.push(stmt);
         }
@@ -191,133 +584,1160 @@ impl<'a> Parser<'a> {
     fn parse_return(&mut self) -> Result<Ast, ParseError> {
         self.next();
         let expr = self.parse_assign()?;
-        let loc = expr.loc.clone();
-        self.expect_token(TokenKind::Semicolon)?;
+        let loc = expr.loc;
+        self.expect_semicolon()?;
         Ok(Ast::return_stmt(expr, loc))
     }
 
     /// BNF:
-    ///     ASSIGN ::= ADD ("=" ASSIGN)?
-    fn parse_assign(&mut self) -> Result<Ast, ParseError> {
-        let lhs = self.parse_add()?;
-        match self.peek() {
-            Some(&TokenKind::Assignment) => {
-                self.next();
-                let rhs = self.parse_assign()?;
-                let loc = lhs.loc.merge(&rhs.loc);
-                Ok(Ast::assignment(lhs, rhs, loc))
+    ///     GOTO_STMT ::= "goto" IDENTIFIER ";"
+    fn parse_goto(&mut self) -> Result<Ast, ParseError> {
+        self.next();
+        let (label, loc) = self.expect_identifier()?;
+        self.expect_semicolon()?;
+        Ok(Ast::goto(label, loc))
+    }
+
+    /// BNF:
+    ///     SWITCH_STMT ::= "switch" ASSIGN "{" ("case" NUMBER ":" STMT*)* ("default" ":" STMT*)? "}"
+    fn parse_switch(&mut self) -> Result<Ast, ParseError> {
+        let switch_loc = self.current_loc();
+        self.next();
+        let cond = self.parse_assign()?;
+        self.expect_token(TokenKind::LBrace)?;
+        let mut cases = Vec::new();
+        while self.peek() == Some(&TokenKind::Case) {
+            self.next();
+            let token = self.next_or_eof()?;
+            let value = match *token.kind() {
+                TokenKind::Number(n, _) => n,
+                _ => {
+                    return Err(ParseError::UnexpectedToken(
+                        TokenKind::Number(0, IntSuffix::None),
+                        token,
+                    ))
+                }
+            };
+            if cases.iter().any(|(case, _)| *case == value) {
+                return Err(ParseError::DuplicateCase(token));
             }
-            _ => Ok(lhs),
+            self.expect_token(TokenKind::Colon)?;
+            cases.push((value, self.parse_case_body()?));
         }
+        let default = if self.peek() == Some(&TokenKind::Default) {
+            self.next();
+            self.expect_token(TokenKind::Colon)?;
+            Some(self.parse_case_body()?)
+        } else {
+            None
+        };
+        let end_loc = self.current_loc();
+        self.expect_token(TokenKind::RBrace)?;
+        Ok(Ast::switch(
+            cond,
+            cases,
+            default,
+            switch_loc.merge(&end_loc),
+        ))
     }
 
-    /// BNF:
-    ///     ADD ::= MUL ("+" MUL | "-" MUL)*
-    fn parse_add(&mut self) -> Result<Ast, ParseError> {
-        let mut lhs = self.parse_mul()?;
+    /// Statements of a case continue until the next case label or the end of the switch.
+    fn parse_case_body(&mut self) -> Result<Vec<Ast>, ParseError> {
+        let mut stmts = Vec::new();
         loop {
-            if self.peek() == Some(&TokenKind::Plus) {
-                self.next();
-                let rhs = self.parse_mul()?;
-                let loc = lhs.loc.merge(&rhs.loc);
-                lhs = Ast::binop(BinOpKind::Add, lhs, rhs, loc);
-            } else if self.peek() == Some(&TokenKind::Minus) {
-                self.next();
-                let rhs = self.parse_mul()?;
-                let loc = lhs.loc.merge(&rhs.loc);
-                lhs = Ast::binop(BinOpKind::Sub, lhs, rhs, loc);
-            } else {
-                break;
+            match self.peek() {
+                Some(&TokenKind::Case) | Some(&TokenKind::Default) | Some(&TokenKind::RBrace) => {
+                    return Ok(stmts)
+                }
+                None | Some(&TokenKind::Eof) => return Err(ParseError::Eof(self.current_loc())),
+                _ => stmts.push(self.parse_stmt()?),
             }
         }
-        Ok(lhs)
     }
 
     /// BNF:
-    ///     MUL ::= UNARY ("*" UNARY | "/" UNARY)*
+    ///     BREAK_STMT ::= "break" ";"
+    fn parse_break(&mut self) -> Result<Ast, ParseError> {
+        let loc = self.current_loc();
+        self.next();
+        self.expect_semicolon()?;
+        Ok(Ast::break_stmt(loc))
+    }
+
+    /// BNF:
+    ///     CONTINUE_STMT ::= "continue" ";"
+    fn parse_continue(&mut self) -> Result<Ast, ParseError> {
+        let loc = self.current_loc();
+        self.next();
+        self.expect_semicolon()?;
+        Ok(Ast::continue_stmt(loc))
+    }
+
+    /// BNF:
+    ///     WHILE_STMT ::= "while" ASSIGN COMP_STMT
+    fn parse_while(&mut self) -> Result<Ast, ParseError> {
+        let while_loc = self.current_loc();
+        self.next();
+        let cond = self.parse_assign()?;
+        let body = self.parse_comp_stmt()?;
+        let els = self.parse_loop_else()?;
+        let loc = while_loc.merge(&els.as_ref().unwrap_or(&body).loc);
+        Ok(Ast::while_stmt(cond, body, els, loc))
+    }
+
+    /// BNF:
+    ///     FOR_STMT ::= "for" "(" (DECL_VAR | ASSIGN? ";") ASSIGN? ";" ASSIGN? ")" COMP_STMT
+    fn parse_for(&mut self) -> Result<Ast, ParseError> {
+        let for_loc = self.current_loc();
+        self.next();
+        self.expect_token(TokenKind::LParen)?;
+        let init = match self.peek() {
+            Some(&TokenKind::Let) | Some(&TokenKind::Const) => Some(self.parse_decl_var()?),
+            _ => {
+                let init = self.parse_optional_expr(TokenKind::Semicolon)?;
+                self.expect_semicolon()?;
+                init
+            }
+        };
+        let cond = self.parse_optional_expr(TokenKind::Semicolon)?;
+        self.expect_semicolon()?;
+        let step = self.parse_optional_expr(TokenKind::RParen)?;
+        self.expect_token(TokenKind::RParen)?;
+        let body = self.parse_comp_stmt()?;
+        let els = self.parse_loop_else()?;
+        let loc = for_loc.merge(&els.as_ref().unwrap_or(&body).loc);
+        Ok(Ast::for_stmt(init, cond, step, body, els, loc))
+    }
+
+    /// BNF:
+    ///     LOOP_ELSE ::= ("else" COMP_STMT)?
+    fn parse_loop_else(&mut self) -> Result<Option<Ast>, ParseError> {
+        if self.peek() == Some(&TokenKind::Else) {
+            self.next();
+            self.parse_comp_stmt().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Parse an expression unless the current token is `terminator`.
+    fn parse_optional_expr(&mut self, terminator: TokenKind) -> Result<Option<Ast>, ParseError> {
+        if self.peek() == Some(&terminator) {
+            Ok(None)
+        } else {
+            self.parse_assign().map(Some)
+        }
+    }
+
+    /// BNF:
+    ///     LABELED_STMT ::= IDENTIFIER ":" STMT
+    fn parse_labeled_stmt(&mut self) -> Result<Ast, ParseError> {
+        let (name, name_loc) = self.expect_identifier()?;
+        self.expect_token(TokenKind::Colon)?;
+        let stmt = self.parse_stmt()?;
+        let loc = name_loc.merge(&stmt.loc);
+        Ok(Ast::label(name, stmt, loc))
+    }
+
+    /// BNF:
+    ///     ASSIGN ::= TERNARY (("=" | "+=" | "-=" | "*=" | "/=" | "%=" | "&=" | "|=" | "^=" | "<<=" | ">>=") ASSIGN)?
+    /// `a op= b` is desugared into `a = a op b`.
+    /// The left hand side must be a variable.
+    fn parse_assign(&mut self) -> Result<Ast, ParseError> {
+        let lhs = self.parse_ternary()?;
+        let op = match self.peek() {
+            Some(&TokenKind::Assignment) => None,
+            Some(&TokenKind::PlusAssign) => Some(BinOpKind::Add),
+            Some(&TokenKind::MinusAssign) => Some(BinOpKind::Sub),
+            Some(&TokenKind::AsteriskAssign) => Some(BinOpKind::Mul),
+            Some(&TokenKind::SlashAssign) => Some(BinOpKind::Div),
+            Some(&TokenKind::PercentAssign) => Some(BinOpKind::Rem),
+            Some(&TokenKind::AmpersandAssign) => Some(BinOpKind::BitAnd),
+            Some(&TokenKind::PipeAssign) => Some(BinOpKind::BitOr),
+            Some(&TokenKind::CaretAssign) => Some(BinOpKind::BitXor),
+            Some(&TokenKind::ShlAssign) => Some(BinOpKind::Shl),
+            Some(&TokenKind::ShrAssign) => Some(BinOpKind::Shr),
+            _ => return Ok(lhs),
+        };
+        let is_lval = matches!(
+            lhs.value,
+            AstKind::Variable(_)
+                | AstKind::UniOp {
+                    op: UniOpKind::Deref,
+                    ..
+                }
+        );
+        if !is_lval {
+            return Err(ParseError::InvalidAssignTarget(lhs.loc));
+        }
+        self.next();
+        let rhs = self.parse_assign()?;
+        let loc = lhs.loc.merge(&rhs.loc);
+        // `a += b` is desugared into `a = a + b`, whose `+` is not written in the source.
+        let rhs = match op {
+            Some(op) => Ast::binop(op, lhs.clone(), rhs, Loc::dummy()),
+            None => rhs,
+        };
+        Ok(Ast::assignment(lhs, rhs, loc))
+    }
+
+    /// BNF:
+    ///     TERNARY ::= BINARY ("?" ASSIGN ":" TERNARY)?
+    /// The false branch is parsed recursively, so `a ? b : c ? d : e` is `a ? b : (c ? d : e)`.
+    fn parse_ternary(&mut self) -> Result<Ast, ParseError> {
+        let cond = self.parse_binary(0)?;
+        if self.peek() != Some(&TokenKind::Question) {
+            return Ok(cond);
+        }
+        self.next();
+        let then = self.parse_assign()?;
+        self.expect_token(TokenKind::Colon)?;
+        let els = self.parse_ternary()?;
+        let loc = cond.loc.merge(&els.loc);
+        Ok(Ast::ternary(cond, then, els, loc))
+    }
+
+    /// BNF:
+    ///     BINARY ::= UNARY (BINOP UNARY)*
     ///
-    fn parse_mul(&mut self) -> Result<Ast, ParseError> {
+    /// Operators of `binops` with higher precedence bind tighter than lower ones.
+    /// All of them are left-associative.
+    fn parse_binary(&mut self, min_prec: u8) -> Result<Ast, ParseError> {
         let mut lhs = self.parse_unary()?;
-        loop {
-            if self.peek() == Some(&TokenKind::Asterisk) {
-                self.next();
-                let rhs = self.parse_unary()?;
-                let loc = lhs.loc.merge(&rhs.loc);
-                lhs = Ast::binop(BinOpKind::Mul, lhs, rhs, loc);
-            } else if self.peek() == Some(&TokenKind::Slash) {
-                self.next();
-                let rhs = self.parse_unary()?;
-                let loc = lhs.loc.merge(&rhs.loc);
-                lhs = Ast::binop(BinOpKind::Div, lhs, rhs, loc);
-            } else {
+        while let Some((op, prec)) = self.peek().and_then(|kind| self.binops.get(kind).cloned()) {
+            if prec < min_prec {
                 break;
             }
+            self.next();
+            // Nothing binds tighter than the highest precedence but unary operators.
+            let rhs = match prec.checked_add(1) {
+                Some(prec) => self.parse_binary(prec)?,
+                None => self.parse_unary()?,
+            };
+            let loc = lhs.loc.merge(&rhs.loc);
+            lhs = Ast::binop(op, lhs, rhs, loc);
         }
         Ok(lhs)
     }
 
     /// BNF:
-    ///     UNARY ::= ("+" | "-") PRIMARY | PRIMARY
+    ///     UNARY ::= ("+" | "-" | "*") UNARY | ("++" | "--") IDENTIFIER | "(" TYPE ")" UNARY
+    ///             | "sizeof" "(" TYPE ")" | "sizeof" UNARY | POSTFIX
     fn parse_unary(&mut self) -> Result<Ast, ParseError> {
         match self.peek() {
-            Some(&TokenKind::Minus) => {
-                let op = match self.next() {
-                    Some(Token {
-                        value: TokenKind::Minus,
-                        loc: _loc,
-                    }) => UniOpKind::Minus,
+            // A parenthesized type, a keyword or an alias by `typedef`, is always a cast.
+            Some(&TokenKind::LParen) if self.is_type_at(self.pos + 1) => {
+                let lparen = self.next().unwrap();
+                let ty = self.parse_type()?;
+                self.expect_token(TokenKind::RParen)?;
+                let expr = self.parse_unary()?;
+                let loc = lparen.loc().merge(&expr.loc);
+                Ok(Ast::cast(ty, expr, loc))
+            }
+            Some(&TokenKind::Plus) | Some(&TokenKind::Minus) | Some(&TokenKind::Asterisk) => {
+                let op = match self.next().as_ref().map(Token::kind) {
+                    Some(TokenKind::Plus) => UniOpKind::Plus,
+                    Some(TokenKind::Minus) => UniOpKind::Minus,
+                    Some(TokenKind::Asterisk) => UniOpKind::Deref,
                     _ => unreachable!(),
                 };
-                let node = self.parse_primary()?;
-                let loc = node.loc.clone();
+                let node = self.parse_unary()?;
+                let loc = node.loc;
                 Ok(Ast::uniop(op, node, loc))
             }
-            _ => self.parse_primary(),
+            // Size of a type is known here, while size of an expression is known in IR generation.
+            Some(&TokenKind::Sizeof) => {
+                let token = self.next().unwrap();
+                if self.peek() == Some(&TokenKind::LParen) && self.is_type_at(self.pos + 1) {
+                    self.next();
+                    let ty = self.parse_type()?;
+                    let rparen_loc = self.current_loc();
+                    self.expect_token(TokenKind::RParen)?;
+                    return Ok(Ast::num(ty.size(), token.loc().merge(&rparen_loc)));
+                }
+                let expr = self.parse_unary()?;
+                let loc = token.loc().merge(&expr.loc);
+                Ok(Ast::size_of(expr, loc))
+            }
+            // `++a` is desugared into `a = a + 1`.
+            Some(&TokenKind::Increment) | Some(&TokenKind::Decrement) => {
+                let token = self.next().unwrap();
+                let op = match token.kind() {
+                    TokenKind::Increment => BinOpKind::Add,
+                    _ => BinOpKind::Sub,
+                };
+                let var = self.parse_primary()?;
+                if !matches!(var.value, AstKind::Variable(_)) {
+                    return Err(ParseError::NotExpression(token));
+                }
+                let loc = token.loc().merge(&var.loc);
+                let one = Ast::num(1, token.loc());
+                let rhs = Ast::binop(op, var.clone(), one, loc);
+                Ok(Ast::assignment(var, rhs, loc))
+            }
+            _ => self.parse_postfix(),
+        }
+    }
+
+    /// BNF:
+    ///     POSTFIX ::= IDENTIFIER ("++" | "--") | PRIMARY
+    fn parse_postfix(&mut self) -> Result<Ast, ParseError> {
+        let node = self.parse_primary()?;
+        if !matches!(node.value, AstKind::Variable(_)) {
+            return Ok(node);
         }
+        let op = match self.peek() {
+            Some(&TokenKind::Increment) => BinOpKind::Add,
+            Some(&TokenKind::Decrement) => BinOpKind::Sub,
+            _ => return Ok(node),
+        };
+        let token = self.next().unwrap();
+        let loc = node.loc.merge(&token.loc());
+        Ok(Ast::post_inc_dec(op, node, loc))
     }
 
     /// BNF:
-    ///     PRIMARY ::= DIGIT* | IDENTIFIER | IDENTIFIER "(" ASSIGN? ")" | "(" ASSIGN ")"
+    ///     PRIMARY ::= DIGIT* | STRING | IDENTIFIER | IDENTIFIER "(" ARGS? ")" | "(" TERNARY ")"
+    ///     ARGS ::= ASSIGN ("," ASSIGN)* ","?
     ///     DIGIT  ::= "0" | "1" | "2" | "3" | "4" | "5" | "6" | "7" | "8" | "9" |
     fn parse_primary(&mut self) -> Result<Ast, ParseError> {
-        self.next()
-            .ok_or(ParseError::Eof)
-            .and_then(|token| match token.value {
-                TokenKind::Number(n) => Ok(Ast::new(AstKind::Num(n), token.loc)),
+        self.next_or_eof()
+            .and_then(|token| match token.kind().clone() {
+                // Every integer is 64-bit, so the suffix does not change the type.
+                TokenKind::Number(n, _) => Ok(Ast::new(AstKind::Num(n), token.loc())),
+                TokenKind::Str(string) => Ok(Ast::str_lit(string, token.loc())),
                 TokenKind::Identifier(var) => {
                     // Function call.
                     if self.peek() == Some(&TokenKind::LParen) {
                         self.next();
                         let mut args = Vec::new();
                         if self.peek() == Some(&TokenKind::RParen) {
-                            return Ok(Ast::func_call(var, args, token.loc));
+                            self.next();
+                            return Ok(Ast::func_call(var, args, token.loc()));
                         }
 
                         args.push(self.parse_assign()?);
                         while self.peek() == Some(&TokenKind::Comma) {
                             self.next();
+                            // Trailing comma.
+                            if self.peek() == Some(&TokenKind::RParen) {
+                                break;
+                            }
                             args.push(self.parse_assign()?);
                         }
                         self.expect_token(TokenKind::RParen)?;
-                        Ok(Ast::func_call(var, args, token.loc))
+                        Ok(Ast::func_call(var, args, token.loc()))
                     }
                     // Access to local variable.
                     else {
-                        Ok(Ast::new(AstKind::Variable(var), token.loc))
+                        Ok(Ast::new(AstKind::Variable(var), token.loc()))
                     }
                 }
                 TokenKind::LParen => {
-                    let node = self.parse_add()?;
+                    let open_loc = token.loc();
+                    let node = self.parse_ternary()?;
                     match self.next() {
-                        Some(Token {
-                            value: TokenKind::RParen,
-                            ..
-                        }) => Ok(node),
+                        Some(t) if *t.kind() == TokenKind::RParen => Ok(node),
                         Some(t) => Err(ParseError::RedundantExpression(t)),
-                        _ => Err(ParseError::UnclosedOpenParen(token)),
+                        _ => Err(ParseError::UnclosedOpenParen(open_loc)),
                     }
                 }
                 _ => Err(ParseError::NotExpression(token)),
             })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::parser::Parser;
+    use crate::parse::{program_span, to_postfix, Ast, AstKind, BinOpKind, ParseError, Type};
+    use crate::token::lexer::Lexer;
+    use crate::token::{IntSuffix, Token, TokenKind};
+    use crate::Loc;
+
+    fn parse(source_code: &str) -> Result<Vec<Ast>, ParseError> {
+        let mut lexer = Lexer::new(source_code);
+        let tokens = lexer.lex().unwrap();
+        let mut parser = Parser::new(tokens);
+        parser.parse()
+    }
+
+    #[test]
+    fn test_empty_source() {
+        assert_eq!(parse(""), Ok(vec![]));
+        assert_eq!(parse(" \n\t \n"), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_expect_identifier() {
+        let tokens = vec![
+            token!(Identifier("a".to_string()), 0, 1),
+            token!(Number(1, IntSuffix::None), 2, 3),
+        ];
+        let mut parser = Parser::new(&tokens);
+        assert_eq!(parser.expect_identifier(), Ok(("a".to_string(), Loc(0, 1))));
+        assert_eq!(
+            parser.expect_identifier(),
+            Err(ParseError::UnexpectedToken(
+                TokenKind::Identifier("identifier".to_string()),
+                token!(Number(1, IntSuffix::None), 2, 3)
+            ))
+        );
+        assert_eq!(parser.expect_identifier(), Err(ParseError::Eof(Loc(3, 3))));
+    }
+
+    #[test]
+    fn test_extern_variadic() {
+        let asts = parse("extern func printf(fmt: u8*, ...); func main() { return 0; }").unwrap();
+        match &asts[0].value {
+            AstKind::ExternFunc {
+                name,
+                params,
+                is_variadic,
+            } => {
+                assert_eq!(name, "printf");
+                assert_eq!(
+                    params,
+                    &[Ast::param(
+                        "fmt".to_string(),
+                        Type::ptr(Type::U8),
+                        Loc(19, 22)
+                    )]
+                );
+                assert!(is_variadic);
+            }
+            _ => panic!("not an extern function: {:?}", asts[0]),
+        }
+        let asts = parse("extern func f(a: u64);").unwrap();
+        assert!(matches!(
+            asts[0].value,
+            AstKind::ExternFunc {
+                is_variadic: false,
+                ..
+            }
+        ));
+        // A definition cannot take variable arguments.
+        assert!(parse("func f(a: u64, ...) { return a; }").is_err());
+        assert!(parse("extern func f(...);").is_err());
+    }
+
+    #[test]
+    fn test_trailing_expr() {
+        let parse_repl = |source_code: &str| {
+            let mut lexer = Lexer::new(source_code);
+            let tokens = lexer.lex().unwrap();
+            let mut parser = Parser::new(tokens).allow_trailing_expr();
+            parser.parse()
+        };
+        let asts = parse_repl("let a: u64 = 1; a + 2").unwrap();
+        assert_eq!(asts.len(), 2);
+        assert!(matches!(asts[0].value, AstKind::Decl { .. }));
+        assert!(matches!(asts[1].value, AstKind::Return { .. }));
+        // An expression with `;` is not returned.
+        let asts = parse_repl("a = 2;").unwrap();
+        assert!(matches!(asts[0].value, AstKind::Assignment { .. }));
+        assert!(parse_repl("1 + 2 3").is_err());
+        // Without the flag, only definitions are allowed at the top level.
+        assert!(parse("1 + 2").is_err());
+    }
+
+    #[test]
+    fn test_nested_ternary() {
+        let ternary = |ast: &Ast| match &ast.value {
+            AstKind::Ternary { cond, then, els } => (*cond.clone(), *then.clone(), *els.clone()),
+            _ => panic!("not ternary: {:?}", ast),
+        };
+        let asts = parse("func main() { return a ? b : c ? d : e; }").unwrap();
+        let expr = match &asts[0].value {
+            AstKind::Func { body, .. } => match &body.value {
+                AstKind::CompStmt { stmts } => match &stmts[0].value {
+                    AstKind::Return { expr } => *expr.clone(),
+                    _ => unreachable!(),
+                },
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        // `a ? b : (c ? d : e)`
+        let (cond, then, els) = ternary(&expr);
+        assert_eq!(cond.value, AstKind::Variable("a".to_string()));
+        assert_eq!(then.value, AstKind::Variable("b".to_string()));
+        let (cond, then, els) = ternary(&els);
+        assert_eq!(cond.value, AstKind::Variable("c".to_string()));
+        assert_eq!(then.value, AstKind::Variable("d".to_string()));
+        assert_eq!(els.value, AstKind::Variable("e".to_string()));
+
+        // The true branch is a full expression, even an assignment.
+        let asts = parse("func main() { return a ? b = 1 : c; }").unwrap();
+        assert_eq!(asts.len(), 1);
+        assert!(parse("func main() { return a ? b; }").is_err());
+    }
+
+    #[test]
+    fn test_invalid_assign_target() {
+        assert!(parse("func main() { let a: u64 = 0; a = 1; a += 1; }").is_ok());
+        assert!(parse("func f(p: u64*) { *p = 1; *(p + 1) += 1; }").is_ok());
+        assert_eq!(
+            parse("func main() { 1 = a; }"),
+            Err(ParseError::InvalidAssignTarget(Loc(14, 15)))
+        );
+        assert_eq!(
+            parse("func main() { (a + b) += c; }"),
+            Err(ParseError::InvalidAssignTarget(Loc(15, 20)))
+        );
+    }
+
+    #[test]
+    fn test_eof_token() {
+        assert_eq!(
+            parse("func main() { return 1; }").map(|asts| asts.len()),
+            Ok(1)
+        );
+        // `Eof` is located at the end of the input, not of the last token.
+        assert_eq!(
+            parse("func main() { return 1;  "),
+            Err(ParseError::Eof(Loc(25, 25)))
+        );
+        assert_eq!(
+            parse("func main() { switch 1 { case 1: "),
+            Err(ParseError::Eof(Loc(33, 33)))
+        );
+    }
+
+    #[test]
+    fn test_truncated_input() {
+        let sources = [
+            "func main() { let",
+            "func main() { let a:",
+            "func main() { let a: volatile",
+            "func main() { let a: u64*",
+            "func main() { let a: u64 =",
+            "func main() { let a: u64 = 1",
+            "func main() { let a: u64 = 1 ? 2 :",
+            "func main() { let a: u64 = (u8)",
+            "func main() { return 1 +",
+            "func main() { f(1,",
+            "func main() { a +=",
+            "func main() { if",
+            "func main() { goto",
+        ];
+        for source_code in sources.iter() {
+            let end = source_code.len();
+            assert_eq!(
+                parse(source_code),
+                Err(ParseError::Eof(Loc(end, end))),
+                "{}",
+                source_code
+            );
+        }
+        // Trailing whitespace is part of the input.
+        assert_eq!(
+            parse("func main() { let a: u64 =  \n"),
+            Err(ParseError::Eof(Loc(29, 29)))
+        );
+    }
+
+    #[test]
+    fn test_expected_token() {
+        let error = parse("func main() { for (;; a++ { } }").unwrap_err();
+        assert_eq!(
+            error,
+            ParseError::expected(TokenKind::RParen, token!(LBrace, 26, 27))
+        );
+        assert_eq!(error.to_string(), "26-27: expected `)`, found `{`");
+    }
+
+    #[test]
+    fn test_current_loc() {
+        let tokens = vec![
+            token!(Return, 0, 6),
+            token!(Number(1, IntSuffix::None), 7, 8),
+            token!(Semicolon, 8, 9),
+        ];
+        let mut parser = Parser::new(&tokens);
+        parser.next();
+        assert_eq!(parser.current_loc(), Loc(7, 8));
+        parser.next();
+        parser.next();
+        assert_eq!(parser.current_loc(), Loc(9, 9));
+        assert_eq!(Parser::new(&vec![]).current_loc(), Loc(0, 0));
+    }
+
+    #[test]
+    fn test_program_span() {
+        let asts = parse("func f() { return 1; }\nfunc main() { return f(); }").unwrap();
+        assert_eq!(program_span(&asts), Loc(0, 50));
+        assert_eq!(program_span(&[]), Loc(0, 0));
+    }
+
+    #[test]
+    fn test_zero_arg_call() {
+        // `)` of a call without arguments is consumed, so `+ 1` applies to the call.
+        let expr = parse_return_expr("func main() { return f() + 1; }");
+        assert_eq!(to_postfix(&expr), ["f/0", "1", "+"]);
+        // A function spans from `func` to its closing brace, not only its body.
+        let asts = parse("func f() { return 1; }").unwrap();
+        assert_eq!(asts[0].loc, Loc(0, 22));
+    }
+
+    fn if_branches(ast: &Ast) -> (&Ast, Option<&Ast>) {
+        match &ast.value {
+            AstKind::If { then, els, .. } => (then, els.as_deref()),
+            _ => panic!("Not if statement: {:?}", ast),
+        }
+    }
+
+    fn first_stmt(ast: &Ast) -> &Ast {
+        match &ast.value {
+            AstKind::CompStmt { stmts } => &stmts[0],
+            AstKind::Func { body, .. } => first_stmt(body),
+            _ => panic!("Not compound statement: {:?}", ast),
+        }
+    }
+
+    #[test]
+    fn test_else_if_chain() {
+        let asts =
+            parse("func main() { if a { return 1; } else if b { return 2; } else { return 3; } }")
+                .unwrap();
+        let (_, els) = if_branches(first_stmt(&asts[0]));
+        let (then, els) = if_branches(els.unwrap());
+        assert_eq!(
+            first_stmt(then).value,
+            AstKind::Return {
+                expr: Box::new(Ast::num(2, Loc(52, 53)))
+            }
+        );
+        assert_eq!(
+            first_stmt(els.unwrap()).value,
+            AstKind::Return {
+                expr: Box::new(Ast::num(3, Loc(71, 72)))
+            }
+        );
+    }
+
+    #[test]
+    fn test_switch() {
+        let asts =
+            parse("func main() { switch a { case 1: x; case 2: y; break; default: z; } }").unwrap();
+        let (cases, default) = match &first_stmt(&asts[0]).value {
+            AstKind::Switch { cases, default, .. } => (cases, default),
+            ast => panic!("Not a switch: {:?}", ast),
+        };
+        let values: Vec<usize> = cases.iter().map(|(value, _)| *value).collect();
+        assert_eq!(values, [1, 2]);
+        assert_eq!(cases[1].1[1].value, AstKind::Break);
+        assert_eq!(default.as_ref().map(|stmts| stmts.len()), Some(1));
+        // `0x1` is the same value as `1`.
+        assert_eq!(
+            parse("func main() { switch a { case 1: x; case 0x1: y; } }"),
+            Err(ParseError::DuplicateCase(token!(
+                Number(1, IntSuffix::None),
+                41,
+                44
+            )))
+        );
+    }
+
+    #[test]
+    fn test_loop_else() {
+        let asts = parse("func main() { while (a) { x; } else { y; } }").unwrap();
+        match &first_stmt(&asts[0]).value {
+            AstKind::While { els, .. } => assert!(els.is_some()),
+            ast => panic!("Not a while: {:?}", ast),
+        }
+        let asts = parse("func main() { for (;;) { x; } else { y; } }").unwrap();
+        match &first_stmt(&asts[0]).value {
+            AstKind::For { els, .. } => assert!(els.is_some()),
+            ast => panic!("Not a for: {:?}", ast),
+        }
+        let asts = parse("func main() { while a { x; } }").unwrap();
+        match &first_stmt(&asts[0]).value {
+            AstKind::While { els, .. } => assert!(els.is_none()),
+            ast => panic!("Not a while: {:?}", ast),
+        }
+    }
+
+    #[test]
+    fn test_doc_comment() {
+        let source_code = "/// entry
+func main() {
+    /// counter
+    let c: u64 = 0;
+    // not a doc
+    let d: u64 = 0;
+    return c;
+}";
+        let mut lexer = Lexer::new(source_code);
+        lexer.lex().unwrap();
+        let mut parser = Parser::new(&lexer.tokens).with_doc_comments(&lexer.doc_comments);
+        let asts = parser.parse().unwrap();
+        let (doc, stmts) = match &asts[0].value {
+            AstKind::Func { doc, body, .. } => match &body.value {
+                AstKind::CompStmt { stmts } => (doc, stmts),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        assert_eq!(doc.as_deref(), Some("entry"));
+        let docs: Vec<Option<&str>> = stmts
+            .iter()
+            .map(|stmt| match &stmt.value {
+                AstKind::Decl { doc, .. } => doc.as_deref(),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(docs, [Some("counter"), None, None]);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut first_lexer = Lexer::new("typedef u8 byte; func main() { return 1; }");
+        let first = first_lexer.lex().unwrap();
+        let mut second_lexer = Lexer::new("func f() { return byte; }");
+        let second = second_lexer.lex().unwrap();
+        let mut parser = Parser::new(first);
+        let asts = parser.parse().unwrap();
+        assert!(matches!(&asts[0].value, AstKind::Func { name, .. } if name == "main"));
+
+        parser.reset(second);
+        let asts = parser.parse().unwrap();
+        assert!(matches!(&asts[0].value, AstKind::Func { name, .. } if name == "f"));
+        // `byte` of the first program is no longer a type.
+        assert_eq!(
+            first_stmt(&asts[0]).value,
+            AstKind::Return {
+                expr: Box::new(Ast::variable("byte".to_string(), Loc(18, 22)))
+            }
+        );
+    }
+
+    #[test]
+    fn test_increment() {
+        assert_eq!(
+            parse_return_expr("func main() { return a++; }"),
+            Ast::post_inc_dec(
+                BinOpKind::Add,
+                Ast::variable("a".to_string(), Loc(21, 22)),
+                Loc(21, 24)
+            )
+        );
+        let a = Ast::variable("a".to_string(), Loc(23, 24));
+        assert_eq!(
+            parse_return_expr("func main() { return ++a; }"),
+            Ast::assignment(
+                a.clone(),
+                Ast::binop(BinOpKind::Add, a, Ast::num(1, Loc(21, 23)), Loc(21, 24)),
+                Loc(21, 24)
+            )
+        );
+        assert!(matches!(
+            parse_return_expr("func main() { return a--; }").value,
+            AstKind::PostIncDec {
+                op: BinOpKind::Sub,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_pointer_type_and_sizeof() {
+        let asts =
+            parse("func f(p: u8**) { return sizeof(u64*) + sizeof(u8) + sizeof p; }").unwrap();
+        match &asts[0].value {
+            AstKind::Func { params, .. } => assert_eq!(
+                params[0].value,
+                AstKind::Param {
+                    name: "p".to_string(),
+                    ty: Type::ptr(Type::ptr(Type::U8))
+                }
+            ),
+            _ => unreachable!(),
+        }
+        assert_eq!(
+            to_postfix(&parse_return_expr(
+                "func f(p: u8*) { return sizeof(u64*) + sizeof(u8) + sizeof p; }"
+            )),
+            ["8", "1", "+", "p", "sizeof", "+"]
+        );
+    }
+
+    #[test]
+    fn test_array_type() {
+        let asts = parse("func main() { let a: u64[2][3]; return sizeof(u8[10]); }").unwrap();
+        match &first_stmt(&asts[0]).value {
+            AstKind::Decl { ty, rhs, .. } => {
+                assert_eq!(*ty, Type::array(Type::array(Type::U64, 3), 2));
+                assert_eq!(ty.size(), 48);
+                assert!(rhs.is_none());
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(
+            parse_return_expr("func main() { return sizeof(u8[10]); }"),
+            Ast::num(10, Loc(21, 35))
+        );
+        assert_eq!(
+            parse("func main() { let a: u64[n]; return 0; }"),
+            Err(ParseError::UnexpectedToken(
+                TokenKind::Number(0, IntSuffix::None),
+                token!(Identifier("n".to_string()), 25, 26)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_dangling_else() {
+        // `else` after the inner block belongs to the inner `if`.
+        let asts = parse("func main() { if a { if b { x; } else { y; } } }").unwrap();
+        let (then, els) = if_branches(first_stmt(&asts[0]));
+        assert!(els.is_none());
+        assert!(if_branches(first_stmt(then)).1.is_some());
+
+        // `else` after the outer block belongs to the outer `if`.
+        let asts = parse("func main() { if a { if b { x; } } else { y; } }").unwrap();
+        let (then, els) = if_branches(first_stmt(&asts[0]));
+        assert!(els.is_some());
+        assert!(if_branches(first_stmt(then)).1.is_none());
+    }
+
+    #[test]
+    fn test_typedef() {
+        let asts = parse(
+            "typedef u8 byte; typedef byte octet; func main() { let x: octet = 5; return x; }",
+        )
+        .unwrap();
+        assert_eq!(asts.len(), 1);
+        match &first_stmt(&asts[0]).value {
+            AstKind::Decl { ty, .. } => assert_eq!(*ty, Type::U8),
+            _ => unreachable!(),
+        }
+        assert_eq!(
+            parse("func main() { let x: word = 5; return x; }"),
+            Err(ParseError::UnexpectedToken(
+                TokenKind::U64,
+                token!(Identifier("word".to_string()), 21, 25)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_goto_and_label() {
+        let asts = parse("func main() { goto end; end: return 0; }").unwrap();
+        assert_eq!(
+            asts[0].value,
+            Ast::func(
+                "main".to_string(),
+                vec![],
+                Ast::comp_stmt(
+                    vec![
+                        Ast::goto("end".to_string(), Loc(19, 22)),
+                        Ast::label(
+                            "end".to_string(),
+                            Ast::return_stmt(Ast::num(0, Loc(36, 37)), Loc(36, 37)),
+                            Loc(24, 37)
+                        ),
+                    ],
+                    Loc(19, 37)
+                ),
+                Loc(19, 37)
+            )
+            .value
+        );
+    }
+
+    #[test]
+    fn test_comparison_precedence() {
+        let asts = parse("func main() { return 1 + 2 < 4 == 1; }").unwrap();
+        let expected = Ast::binop(
+            BinOpKind::Eq,
+            Ast::binop(
+                BinOpKind::Lt,
+                Ast::binop(
+                    BinOpKind::Add,
+                    Ast::num(1, Loc(21, 22)),
+                    Ast::num(2, Loc(25, 26)),
+                    Loc(21, 26),
+                ),
+                Ast::num(4, Loc(29, 30)),
+                Loc(21, 30),
+            ),
+            Ast::num(1, Loc(34, 35)),
+            Loc(21, 35),
+        );
+        assert_eq!(
+            asts[0].value,
+            Ast::func(
+                "main".to_string(),
+                vec![],
+                Ast::comp_stmt(vec![Ast::return_stmt(expected, Loc(21, 35))], Loc(21, 35)),
+                Loc(21, 35)
+            )
+            .value
+        );
+    }
+
+    fn parse_return_expr(source_code: &str) -> Ast {
+        let asts = parse(source_code).unwrap();
+        match &asts[0].value {
+            AstKind::Func { body, .. } => match &body.value {
+                AstKind::CompStmt { stmts } => match &stmts.last().unwrap().value {
+                    AstKind::Return { expr } => *expr.clone(),
+                    _ => unreachable!(),
+                },
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_to_postfix() {
+        let expr = parse_return_expr("func main() { return (5 + 2) * 31 - -10; }");
+        assert_eq!(
+            to_postfix(&expr),
+            ["5", "2", "+", "31", "*", "10", "neg", "-"]
+        );
+        let expr = parse_return_expr("func main() { return f(a, (u8)b) <= 1; }");
+        assert_eq!(to_postfix(&expr), ["a", "b", "(u8)", "f/2", "1", "<="]);
+        let expr = parse_return_expr("func main() { return a = b ? c : d + 1; }");
+        assert_eq!(to_postfix(&expr), ["a", "b", "c", "d", "1", "+", "?:", "="]);
+    }
+
+    #[test]
+    fn test_consecutive_minus() {
+        let expr = parse_return_expr("func f(a: u64) { return a - -10; }");
+        assert_eq!(to_postfix(&expr), ["a", "10", "neg", "-"]);
+        let expr = parse_return_expr("func main() { return - -10; }");
+        assert_eq!(to_postfix(&expr), ["10", "neg", "neg"]);
+        let expr = parse_return_expr("func main() { return -(-10); }");
+        assert_eq!(to_postfix(&expr), ["10", "neg", "neg"]);
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        let expr = parse_return_expr("func main() { return a <<= 2; }");
+        let a = Ast::variable("a".to_string(), Loc(21, 22));
+        assert_eq!(
+            expr,
+            Ast::assignment(
+                a.clone(),
+                Ast::binop(BinOpKind::Shl, a, Ast::num(2, Loc(27, 28)), Loc::dummy()),
+                Loc(21, 28)
+            )
+        );
+    }
+
+    #[test]
+    fn test_bitwise_precedence() {
+        let expr = parse_return_expr("func main() { return a | b ^ c & d == e << 1 + f % 2; }");
+        assert_eq!(
+            to_postfix(&expr),
+            ["a", "b", "c", "d", "e", "1", "f", "2", "%", "+", "<<", "==", "&", "^", "|"]
+        );
+    }
+
+    #[test]
+    fn test_register_binop() {
+        let parse_with = |source_code: &str, token: TokenKind, kind: BinOpKind, prec: u8| {
+            let mut lexer = Lexer::new(source_code);
+            let tokens = lexer.lex().unwrap();
+            let mut parser = Parser::new(tokens).allow_trailing_expr();
+            parser.register_binop(token, kind, prec);
+            match parser.parse().unwrap().remove(0).value {
+                AstKind::Return { expr } => to_postfix(&expr),
+                _ => unreachable!(),
+            }
+        };
+        // `...` adds operands, binding tighter than `*`.
+        assert_eq!(
+            parse_with("a * b ... c - d", TokenKind::Ellipsis, BinOpKind::Add, 11),
+            ["a", "b", "c", "+", "*", "d", "-"]
+        );
+        // `-` now binds looser than `==`.
+        assert_eq!(
+            parse_with("a - b == c", TokenKind::Minus, BinOpKind::Sub, 5),
+            ["a", "b", "c", "==", "-"]
+        );
+        // The highest precedence is still left-associative.
+        assert_eq!(
+            parse_with(
+                "a ... b ... c * d",
+                TokenKind::Ellipsis,
+                BinOpKind::Add,
+                u8::MAX
+            ),
+            ["a", "b", "+", "c", "+", "d", "*"]
+        );
+    }
+
+    #[test]
+    fn test_cast() {
+        assert_eq!(
+            parse_return_expr("func f(x: u64) { return (u8)x; }"),
+            Ast::cast(
+                Type::U8,
+                Ast::variable("x".to_string(), Loc(28, 29)),
+                Loc(24, 29)
+            )
+        );
+        assert_eq!(
+            parse_return_expr("func f(c: u8) { return (u64)c + 1; }"),
+            Ast::binop(
+                BinOpKind::Add,
+                Ast::cast(
+                    Type::U64,
+                    Ast::variable("c".to_string(), Loc(28, 29)),
+                    Loc(23, 29)
+                ),
+                Ast::num(1, Loc(32, 33)),
+                Loc(23, 33)
+            )
+        );
+        assert_eq!(
+            parse_return_expr("typedef u8 byte; func f(x: u64) { return (byte)x; }"),
+            Ast::cast(
+                Type::U8,
+                Ast::variable("x".to_string(), Loc(47, 48)),
+                Loc(41, 48)
+            )
+        );
+        assert_eq!(
+            parse_return_expr("func f(c: u8) { return (c); }"),
+            Ast::variable("c".to_string(), Loc(24, 25))
+        );
+    }
+
+    #[test]
+    fn test_assignment_is_right_associative() {
+        let asts = parse("func f(a: u64, b: u64) { a = b = 1; }").unwrap();
+        let expected = Ast::assignment(
+            Ast::variable("a".to_string(), Loc(25, 26)),
+            Ast::assignment(
+                Ast::variable("b".to_string(), Loc(29, 30)),
+                Ast::num(1, Loc(33, 34)),
+                Loc(29, 34),
+            ),
+            Loc(25, 34),
+        );
+        match &asts[0].value {
+            AstKind::Func { body, .. } => {
+                assert_eq!(
+                    body.value,
+                    AstKind::CompStmt {
+                        stmts: vec![expected]
+                    }
+                )
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_trailing_comma_in_call() {
+        assert_eq!(
+            parse("func main() { return f(a, b,); }").map(|asts| asts[0].value.clone()),
+            parse("func main() { return f(a, b); }").map(|asts| asts[0].value.clone())
+        );
+        assert_eq!(
+            parse("func main() { return f(,); }"),
+            Err(ParseError::NotExpression(token!(Comma, 23, 24)))
+        );
+        assert_eq!(
+            parse("func main() { return f(a,,b); }"),
+            Err(ParseError::NotExpression(token!(Comma, 25, 26)))
+        );
+    }
+
+    #[test]
+    fn test_unclosed_paren() {
+        assert_eq!(
+            parse("func main() { return (1 + 2"),
+            Err(ParseError::UnclosedOpenParen(Loc(21, 22)))
+        );
+    }
+
+    #[test]
+    fn test_recover_at_keyword() {
+        let source_code = "func main() {
+    let = 5 5
+    return 1;
+}";
+        let mut lexer = Lexer::new(source_code);
+        let tokens = lexer.lex().unwrap();
+        let mut parser = Parser::new(tokens);
+        let (asts, errors) = parser.parse_recovering();
+        assert_eq!(
+            errors,
+            vec![ParseError::UnexpectedToken(
+                TokenKind::Identifier("identifier".to_string()),
+                token!(Assignment, 22, 23)
+            )]
+        );
+        assert_eq!(
+            asts,
+            vec![Ast::func(
+                "main".to_string(),
+                vec![],
+                Ast::comp_stmt(
+                    vec![Ast::return_stmt(Ast::num(1, Loc(39, 40)), Loc(39, 40))],
+                    Loc(39, 40)
+                ),
+                Loc(0, 43)
+            )]
+        );
+    }
+
+    #[test]
+    fn test_max_errors() {
+        let source_code = format!("func main() {{ {} return 0; }}", "a = ; ".repeat(30));
+        let mut lexer = Lexer::new(&source_code);
+        let tokens = lexer.lex().unwrap();
+        let (_, errors) = Parser::new(tokens).parse_recovering();
+        assert_eq!(errors.len(), 21);
+        assert_eq!(errors[20], ParseError::TooManyErrors);
+
+        let (_, errors) = Parser::new(tokens).with_max_errors(3).parse_recovering();
+        assert_eq!(errors.len(), 4);
+        assert_eq!(errors[3], ParseError::TooManyErrors);
+    }
+
+    #[test]
+    fn test_recover_multiple_errors() {
+        let source_code = "func main() { a = ; return 1 }
+func f() { ) ; return 2; }
+func";
+        let mut lexer = Lexer::new(source_code);
+        let tokens = lexer.lex().unwrap();
+        let mut parser = Parser::new(tokens);
+        let (asts, errors) = parser.parse_recovering();
+        assert_eq!(
+            errors[..3],
+            [
+                ParseError::NotExpression(token!(Semicolon, 18, 19)),
+                ParseError::NoSemicolon(token!(RBrace, 29, 30)),
+                ParseError::UnexpectedToken(TokenKind::RBrace, token!(Func, 31, 35)),
+            ]
+        );
+        assert_eq!(errors[3], ParseError::NotExpression(token!(RParen, 42, 43)));
+        assert_eq!(errors[4], ParseError::Eof(Loc(62, 62)));
+        assert_eq!(asts.len(), 2);
+    }
+
+    #[test]
+    fn test_recover_at_semicolon() {
+        // The error consumes `;` of `a = ;`, so recovery does not skip `b = ;` after it.
+        let source_code = "func main() { a = ; b = ; return 0; }";
+        let mut lexer = Lexer::new(source_code);
+        let tokens = lexer.lex().unwrap();
+        let (asts, errors) = Parser::new(tokens).parse_recovering();
+        assert_eq!(
+            errors,
+            [
+                ParseError::NotExpression(token!(Semicolon, 18, 19)),
+                ParseError::NotExpression(token!(Semicolon, 24, 25)),
+            ]
+        );
+        assert_eq!(asts.len(), 1);
+    }
+}
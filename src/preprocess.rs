@@ -0,0 +1,210 @@
+use std::fmt;
+use std::path::Path;
+
+/// Name of the file given to `preprocess` in `SourceMap`.
+pub const MAIN_FILE: &str = "<main>";
+
+/// Expand `#include "name"` lines by contents of files `resolve` returns.
+/// A name in an included file is relative to the directory of that file,
+/// so `#include "f.pr"` in `lib/g.pr` asks `resolve` for `lib/f.pr`.
+pub fn preprocess(
+    main_source: &str,
+    resolve: impl Fn(&str) -> Option<String>,
+) -> Result<String, PreprocessError> {
+    preprocess_with_map(main_source, resolve).map(|(source, _)| source)
+}
+
+/// Same as `preprocess`, but also returns a map from positions in the expanded source
+/// to positions in the original files.
+pub fn preprocess_with_map(
+    main_source: &str,
+    resolve: impl Fn(&str) -> Option<String>,
+) -> Result<(String, SourceMap), PreprocessError> {
+    let mut preprocessor = Preprocessor {
+        resolve: &resolve,
+        output: String::new(),
+        source_map: SourceMap::default(),
+        include_stack: vec![MAIN_FILE.to_string()],
+    };
+    preprocessor.expand(MAIN_FILE, main_source)?;
+    Ok((preprocessor.output, preprocessor.source_map))
+}
+
+struct Preprocessor<'a> {
+    resolve: &'a dyn Fn(&str) -> Option<String>,
+    output: String,
+    source_map: SourceMap,
+    // Files being expanded, the outermost first.
+    include_stack: Vec<String>,
+}
+
+impl Preprocessor<'_> {
+    fn expand(&mut self, file: &str, source: &str) -> Result<(), PreprocessError> {
+        let mut offset = 0;
+        self.source_map.push(self.output.len(), file, offset);
+        for (line_number, line) in source.split_inclusive('\n').enumerate() {
+            let directive = line.trim_start();
+            if directive.starts_with("#include") {
+                let name =
+                    include_name(directive).ok_or_else(|| PreprocessError::InvalidInclude {
+                        file: file.to_string(),
+                        line: line_number + 1,
+                    })?;
+                let name = relative_to(file, name);
+                if self.include_stack.contains(&name) {
+                    let mut chain = self.include_stack.clone();
+                    chain.push(name);
+                    return Err(PreprocessError::IncludeCycle(chain));
+                }
+                let included = (self.resolve)(&name)
+                    .ok_or_else(|| PreprocessError::FileNotFound(name.clone()))?;
+
+                self.include_stack.push(name.clone());
+                self.expand(&name, &included)?;
+                self.include_stack.pop();
+                if !included.is_empty() && !included.ends_with('\n') {
+                    self.output.push('\n');
+                }
+                offset += line.len();
+                self.source_map.push(self.output.len(), file, offset);
+            } else {
+                self.output.push_str(line);
+                offset += line.len();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Path of `name` included from `file`. Names in the main file are used as they are.
+fn relative_to(file: &str, name: &str) -> String {
+    match Path::new(file).parent() {
+        Some(dir) if file != MAIN_FILE => dir.join(name).to_string_lossy().into_owned(),
+        _ => name.to_string(),
+    }
+}
+
+/// Extract `name` from `#include "name"`.
+fn include_name(directive: &str) -> Option<&str> {
+    let rest = directive.strip_prefix("#include")?.trim();
+    let name = rest.strip_prefix('"')?.strip_suffix('"')?;
+    if name.is_empty() || name.contains('"') {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+/// Map from positions in a preprocessed source to the files they come from.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SourceMap {
+    // Each segment starts at `.0` in the preprocessed source,
+    // which corresponds to `.2` in the file `.1`.
+    segments: Vec<(usize, String, usize)>,
+}
+
+impl SourceMap {
+    fn push(&mut self, start: usize, file: &str, offset: usize) {
+        // A later segment starting at the same position overrides an empty one.
+        if let Some(last) = self.segments.last() {
+            if last.0 == start {
+                self.segments.pop();
+            }
+        }
+        self.segments.push((start, file.to_string(), offset));
+    }
+
+    /// Return a file name and a position in the file for `pos` in the preprocessed source.
+    pub fn locate(&self, pos: usize) -> (&str, usize) {
+        let index = self.segments.partition_point(|segment| segment.0 <= pos) - 1;
+        let (start, file, offset) = &self.segments[index];
+        (file, offset + pos - start)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PreprocessError {
+    /// `resolve` returned nothing for the name.
+    FileNotFound(String),
+    /// Files including each other, from the main file to the one included again.
+    IncludeCycle(Vec<String>),
+    /// `#include` line which is not followed by a quoted name.
+    InvalidInclude { file: String, line: usize },
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreprocessError::FileNotFound(name) => write!(f, "File not found: \"{}\"", name),
+            PreprocessError::IncludeCycle(chain) => {
+                write!(f, "Include cycle: {}", chain.join(" -> "))
+            }
+            PreprocessError::InvalidInclude { file, line } => {
+                write!(f, "{}:{}: Invalid #include", file, line)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::preprocess::{preprocess, preprocess_with_map, PreprocessError, MAIN_FILE};
+
+    fn resolve(name: &str) -> Option<String> {
+        match name {
+            "f.pr" => Some("func f() { return 1; }\n".to_string()),
+            "g.pr" => Some("#include \"f.pr\"\nfunc g() { return f(); }".to_string()),
+            "a.pr" => Some("#include \"b.pr\"\n".to_string()),
+            "b.pr" => Some("#include \"a.pr\"\n".to_string()),
+            "lib/h.pr" => Some("#include \"f.pr\"\nfunc h() { return f(); }\n".to_string()),
+            "lib/f.pr" => Some("func f() { return 2; }\n".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_include() {
+        let main_source = "#include \"g.pr\"\nfunc main() { return g(); }\n";
+        let (source, source_map) = preprocess_with_map(main_source, resolve).unwrap();
+        assert_eq!(
+            source,
+            "func f() { return 1; }\nfunc g() { return f(); }\nfunc main() { return g(); }\n"
+        );
+        assert_eq!(source_map.locate(5), ("f.pr", 5));
+        assert_eq!(source_map.locate(28), ("g.pr", 21));
+        assert_eq!(source_map.locate(53), (MAIN_FILE, 21));
+    }
+
+    #[test]
+    fn test_include_relative() {
+        // `f.pr` in `lib/h.pr` is `lib/f.pr`, not `f.pr` next to the main file.
+        let (source, source_map) = preprocess_with_map("#include \"lib/h.pr\"\n", resolve).unwrap();
+        assert_eq!(source, "func f() { return 2; }\nfunc h() { return f(); }\n");
+        assert_eq!(source_map.locate(5), ("lib/f.pr", 5));
+        assert_eq!(source_map.locate(28), ("lib/h.pr", 21));
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        assert_eq!(
+            preprocess("#include \"a.pr\"\n", resolve),
+            Err(PreprocessError::IncludeCycle(vec![
+                MAIN_FILE.to_string(),
+                "a.pr".to_string(),
+                "b.pr".to_string(),
+                "a.pr".to_string(),
+            ]))
+        );
+        assert_eq!(
+            preprocess("#include \"c.pr\"\n", resolve),
+            Err(PreprocessError::FileNotFound("c.pr".to_string()))
+        );
+        assert_eq!(
+            preprocess("\n#include c.pr\n", resolve),
+            Err(PreprocessError::InvalidInclude {
+                file: MAIN_FILE.to_string(),
+                line: 2
+            })
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use crate::{ARG_REGISTER_COUNT, REGISTER_COUNT};
+
+const ARG_REGISTERS: [&str; ARG_REGISTER_COUNT] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
+const WIN64_ARG_REGISTERS: [&str; 4] = ["rcx", "rdx", "r8", "r9"];
+
+/// Description of a machine which code is generated for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Target {
+    /// Number of registers available for register allocation.
+    pub register_count: usize,
+    /// Size of an `int` value, such as an integer literal, in bytes.
+    pub int_size: usize,
+    pub call_conv: CallConv,
+}
+
+impl Target {
+    /// x86-64 on Linux and macOS.
+    pub fn x86_64_sysv() -> Self {
+        Target {
+            register_count: REGISTER_COUNT,
+            int_size: 8,
+            call_conv: CallConv::SysV,
+        }
+    }
+
+    /// x86-64 on Windows.
+    pub fn x86_64_win64() -> Self {
+        Target {
+            call_conv: CallConv::Win64,
+            ..Target::x86_64_sysv()
+        }
+    }
+
+    /// Whether `value` is representable in `int` either as unsigned or as two's complement.
+    pub fn fits_int(&self, value: usize) -> bool {
+        let bits = self.int_size * 8;
+        if bits >= usize::BITS as usize {
+            return true;
+        }
+        value >> bits == 0 || (value as i64) >> (bits - 1) == -1
+    }
+}
+
+impl Default for Target {
+    fn default() -> Self {
+        Target::x86_64_sysv()
+    }
+}
+
+/// Calling convention of generated functions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CallConv {
+    /// System V AMD64 ABI used on Linux and macOS.
+    #[default]
+    SysV,
+    /// Microsoft x64 calling convention.
+    Win64,
+}
+
+impl CallConv {
+    /// Registers which carry the first integer arguments.
+    pub fn arg_registers(self) -> &'static [&'static str] {
+        match self {
+            CallConv::SysV => &ARG_REGISTERS,
+            CallConv::Win64 => &WIN64_ARG_REGISTERS,
+        }
+    }
+
+    /// Bytes a caller reserves on the stack for the callee to spill arguments.
+    pub fn shadow_space(self) -> usize {
+        match self {
+            CallConv::SysV => 0,
+            CallConv::Win64 => 32,
+        }
+    }
+}
@@ -2,16 +2,33 @@ use std::collections::HashMap;
 use std::str::from_utf8;
 
 use crate::token::LexError;
-use crate::token::{Token, TokenKind};
-use crate::Loc;
+use crate::token::{IntSuffix, Token, TokenKind};
+use crate::{Annotation, Loc};
 
 fn reserve_keywords() -> HashMap<String, TokenKind> {
     let mut keywords = HashMap::new();
     keywords.insert("let".to_string(), TokenKind::Let);
+    keywords.insert("const".to_string(), TokenKind::Const);
+    keywords.insert("static".to_string(), TokenKind::Static);
     keywords.insert("u64".to_string(), TokenKind::U64);
+    keywords.insert("u8".to_string(), TokenKind::U8);
     keywords.insert("func".to_string(), TokenKind::Func);
+    keywords.insert("extern".to_string(), TokenKind::Extern);
+    keywords.insert("typedef".to_string(), TokenKind::Typedef);
     keywords.insert("if".to_string(), TokenKind::If);
+    keywords.insert("else".to_string(), TokenKind::Else);
     keywords.insert("return".to_string(), TokenKind::Return);
+    keywords.insert("goto".to_string(), TokenKind::Goto);
+    keywords.insert("switch".to_string(), TokenKind::Switch);
+    keywords.insert("case".to_string(), TokenKind::Case);
+    keywords.insert("default".to_string(), TokenKind::Default);
+    keywords.insert("break".to_string(), TokenKind::Break);
+    keywords.insert("while".to_string(), TokenKind::While);
+    keywords.insert("for".to_string(), TokenKind::For);
+    keywords.insert("continue".to_string(), TokenKind::Continue);
+    keywords.insert("sizeof".to_string(), TokenKind::Sizeof);
+    keywords.insert("volatile".to_string(), TokenKind::Volatile);
+    keywords.insert("restrict".to_string(), TokenKind::Restrict);
     keywords
 }
 
@@ -19,10 +36,22 @@ fn reserve_keywords() -> HashMap<String, TokenKind> {
 pub struct Lexer<'a> {
     /// Input code.
     input: &'a [u8],
+    /// Input code as text, to read characters on their boundaries.
+    source: &'a str,
     /// Position where an instance of `Lexer` is reading.
     pos: usize,
     /// `Vec` of processed tokens.
     pub tokens: Vec<Token>,
+    /// Text of `///` comments, which are not tokens but may be attached to declarations.
+    pub doc_comments: Vec<Annotation<String>>,
+    /// Maximum number of tokens.
+    max_tokens: usize,
+    /// Maximum length of input code in bytes.
+    max_input_len: usize,
+    /// Number of columns a tab character occupies.
+    tab_width: usize,
+    /// Maximum length of an identifier in bytes.
+    max_identifier_len: usize,
 }
 
 impl<'a> Lexer<'a> {
@@ -30,104 +59,295 @@ impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Self {
         Lexer {
             input: input.as_bytes(),
+            source: input,
             pos: 0,
             tokens: Vec::new(),
+            doc_comments: Vec::new(),
+            max_tokens: usize::MAX,
+            max_input_len: usize::MAX,
+            tab_width: 1,
+            max_identifier_len: usize::MAX,
+        }
+    }
+
+    /// Set the number of columns a tab character occupies.
+    pub fn with_tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = tab_width.max(1);
+        self
+    }
+
+    /// Set the maximum length of an identifier, which is unlimited by default.
+    pub fn with_max_identifier_len(mut self, max_identifier_len: usize) -> Self {
+        self.max_identifier_len = max_identifier_len;
+        self
+    }
+
+    /// Compute 1-origin line and column number of `pos` in the input code.
+    /// A tab character advances the column to the next tab stop.
+    pub fn line_col(&self, pos: usize) -> (usize, usize) {
+        let mut line = 1;
+        let mut col = 1;
+        for &b in self.input.iter().take(pos) {
+            match b {
+                b'\n' => {
+                    line += 1;
+                    col = 1;
+                }
+                b'\t' => col = ((col - 1) / self.tab_width + 1) * self.tab_width + 1,
+                _ => col += 1,
+            }
+        }
+        (line, col)
+    }
+
+    /// Generate new `Lexer` which rejects too many tokens or too long input.
+    pub fn with_limits(input: &'a str, max_tokens: usize, max_input_len: usize) -> Self {
+        Lexer {
+            max_tokens,
+            max_input_len,
+            ..Lexer::new(input)
         }
     }
 
     /// Read all characters in a input code and push token into `tokens`.
+    /// The tokens end with `Eof` located at the end of the input.
     pub fn lex(&mut self) -> Result<&Vec<Token>, LexError> {
         let keywords = reserve_keywords();
-        while self.pos < self.input.len() {
-            match self.input[self.pos] {
-                b'+' => self.lex_plus(),
-                b'-' => self.lex_minus(),
-                b'*' => self.lex_asterisk(),
-                b'/' => self.lex_slash(),
-                b'(' => self.lex_lparen(),
-                b')' => self.lex_rparen(),
-                b'{' => self.lex_lbrace(),
-                b'}' => self.lex_rbrace(),
-                b':' => self.lex_colon(),
-                b',' => self.lex_comma(),
-                b'0'..=b'9' => self.lex_number(),
-                b'a'..=b'z' | b'A'..=b'Z' | b'_' => self.lex_identifier(&keywords),
-                b';' => self.lex_semicolon(),
-                b'=' => self.lex_assignment(),
-                b' ' | b'\n' | b'\t' => self.skip_spaces(),
-                b => {
+        if self.input.len() > self.max_input_len {
+            return Err(LexError::input_too_large(Loc(
+                self.max_input_len,
+                self.input.len(),
+            )));
+        }
+        while let Some(c) = self.peek_char() {
+            match c {
+                '+' if self.peek_char2() == Some('+') => self.lex_double(TokenKind::Increment),
+                '-' if self.peek_char2() == Some('-') => self.lex_double(TokenKind::Decrement),
+                '+' => self.lex_operator(TokenKind::Plus, TokenKind::PlusAssign),
+                '-' => self.lex_operator(TokenKind::Minus, TokenKind::MinusAssign),
+                '*' => self.lex_operator(TokenKind::Asterisk, TokenKind::AsteriskAssign),
+                '/' if self.peek_char2() == Some('/') => self.lex_comment(),
+                '/' => self.lex_operator(TokenKind::Slash, TokenKind::SlashAssign),
+                '%' => self.lex_operator(TokenKind::Percent, TokenKind::PercentAssign),
+                '&' if self.peek_char2() == Some('&') => self.lex_double(TokenKind::LogAnd),
+                '&' => self.lex_operator(TokenKind::Ampersand, TokenKind::AmpersandAssign),
+                '|' if self.peek_char2() == Some('|') => self.lex_double(TokenKind::LogOr),
+                '|' => self.lex_operator(TokenKind::Pipe, TokenKind::PipeAssign),
+                '^' => self.lex_operator(TokenKind::Caret, TokenKind::CaretAssign),
+                '(' => self.lex_single(TokenKind::LParen),
+                ')' => self.lex_single(TokenKind::RParen),
+                '{' => self.lex_single(TokenKind::LBrace),
+                '}' => self.lex_single(TokenKind::RBrace),
+                '[' => self.lex_single(TokenKind::LBracket),
+                ']' => self.lex_single(TokenKind::RBracket),
+                ':' => self.lex_single(TokenKind::Colon),
+                '?' => self.lex_single(TokenKind::Question),
+                ',' => self.lex_single(TokenKind::Comma),
+                '.' if self.source[self.pos..].starts_with("...") => self.lex_ellipsis(),
+                ';' => self.lex_single(TokenKind::Semicolon),
+                '0'..='9' => self.lex_number()?,
+                '"' => self.lex_str()?,
+                'a'..='z' | 'A'..='Z' | '_' => self.lex_identifier(&keywords)?,
+                '=' => self.lex_assignment(),
+                '!' => self.lex_not_equal()?,
+                '<' => self.lex_lt(),
+                '>' => self.lex_gt(),
+                ' ' | '\n' | '\t' => self.skip_spaces(),
+                c => {
                     return Err(LexError::invalid_char(
-                        b as char,
-                        Loc(self.pos, self.pos + 1),
+                        c,
+                        Loc(self.pos, self.pos + c.len_utf8()),
                     ));
                 }
             }
+            if self.tokens.len() > self.max_tokens {
+                let loc = self.tokens.last().unwrap().loc;
+                return Err(LexError::input_too_large(loc));
+            }
         }
+        let end = self.input.len();
+        self.tokens.push(Token::new(TokenKind::Eof, Loc(end, end)));
         Ok(&self.tokens)
     }
 
-    fn lex_plus(&mut self) {
-        self.tokens.push(token!(Plus, self.pos, self.pos + 1));
-        self.pos += 1;
-    }
-
-    fn lex_minus(&mut self) {
-        self.tokens.push(token!(Minus, self.pos, self.pos + 1));
-        self.pos += 1;
+    /// Same as `lex`, but reserves capacity of `tokens` in advance
+    /// assuming a token appears every 4 bytes to reduce reallocation.
+    pub fn lex_presized(&mut self) -> Result<&Vec<Token>, LexError> {
+        self.tokens.reserve(self.input.len() / 4);
+        self.lex()
     }
 
-    fn lex_asterisk(&mut self) {
-        self.tokens.push(token!(Asterisk, self.pos, self.pos + 1));
-        self.pos += 1;
+    /// Character at the current position.
+    fn peek_char(&self) -> Option<char> {
+        self.source[self.pos..].chars().next()
     }
 
-    fn lex_slash(&mut self) {
-        self.tokens.push(token!(Slash, self.pos, self.pos + 1));
-        self.pos += 1;
+    /// Character next to the one at the current position.
+    fn peek_char2(&self) -> Option<char> {
+        self.source[self.pos..].chars().nth(1)
     }
 
-    fn lex_lparen(&mut self) {
-        self.tokens.push(token!(LParen, self.pos, self.pos + 1));
-        self.pos += 1;
+    /// Proceed to the next character and return the current one.
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek_char()?;
+        self.pos += c.len_utf8();
+        Some(c)
     }
 
-    fn lex_rparen(&mut self) {
-        self.tokens.push(token!(RParen, self.pos, self.pos + 1));
-        self.pos += 1;
+    /// Push a token of `kind` which starts at `start` and ends at the current position.
+    fn push_token(&mut self, kind: TokenKind, start: usize) {
+        self.tokens.push(Token::new(kind, Loc(start, self.pos)));
     }
 
-    fn lex_lbrace(&mut self) {
-        self.tokens.push(token!(LBrace, self.pos, self.pos + 1));
-        self.pos += 1;
+    /// Lex a single-character token.
+    fn lex_single(&mut self, kind: TokenKind) {
+        let start = self.pos;
+        self.bump();
+        self.push_token(kind, start);
     }
 
-    fn lex_rbrace(&mut self) {
-        self.tokens.push(token!(RBrace, self.pos, self.pos + 1));
-        self.pos += 1;
+    /// Lex a single-character operator, or the compound assignment if `=` follows.
+    fn lex_operator(&mut self, op: TokenKind, op_assign: TokenKind) {
+        let start = self.pos;
+        self.bump();
+        if self.peek_char() == Some('=') {
+            self.bump();
+            self.push_token(op_assign, start);
+        } else {
+            self.push_token(op, start);
+        }
     }
 
-    fn lex_colon(&mut self) {
-        self.tokens.push(token!(Colon, self.pos, self.pos + 1));
-        self.pos += 1;
+    /// Lex an operator of two same characters, such as `++`.
+    fn lex_double(&mut self, op: TokenKind) {
+        let start = self.pos;
+        self.bump();
+        self.bump();
+        self.push_token(op, start);
     }
 
-    fn lex_comma(&mut self) {
-        self.tokens.push(token!(Comma, self.pos, self.pos + 1));
-        self.pos += 1;
+    /// Lex `...`.
+    fn lex_ellipsis(&mut self) {
+        let start = self.pos;
+        self.pos += "...".len();
+        self.push_token(TokenKind::Ellipsis, start);
     }
 
-    fn lex_number(&mut self) {
+    /// Lex a number literal. A leading `0` is decided here:
+    /// `0x` and `0b` are hexadecimal and binary, `0.` starts a float,
+    /// `0` followed by digits is octal, and `0` alone is zero.
+    fn lex_number(&mut self) -> Result<(), LexError> {
         let start = self.pos;
-        let end = self.recognize_multiple_char(|b| b"0123456789".contains(&b));
-        let num = from_utf8(&self.input[start..end]).unwrap().parse().unwrap();
+        let (radix, digits_start) = match (self.input[start], self.input.get(start + 1)) {
+            (b'0', Some(b'x')) | (b'0', Some(b'X')) => (16, start + 2),
+            (b'0', Some(b'b')) | (b'0', Some(b'B')) => (2, start + 2),
+            (b'0', Some(b'0'..=b'9')) => (8, start + 1),
+            _ => (10, start),
+        };
+        if radix == 16 {
+            if let Some(float_end) = self.hex_float_end(digits_start) {
+                return Err(LexError::hex_float_not_supported(Loc(start, float_end)));
+            }
+        }
+        self.pos = digits_start;
+        let end = self.recognize_multiple_char(|b| b.is_ascii_alphanumeric());
 
-        self.tokens.push(token!(Number(num), start, end));
+        // `5.` is a float without a fraction, while `5...` is followed by an ellipsis.
+        if radix != 16
+            && self.input.get(end) == Some(&b'.')
+            && !self.input[end..].starts_with(b"...")
+        {
+            self.pos = end + 1;
+            let fraction_end = self.recognize_multiple_char(|b| b.is_ascii_alphanumeric());
+            return Err(LexError::float_not_supported(Loc(start, fraction_end)));
+        }
+
+        // Letters which cannot be a digit in the radix start a suffix.
+        let is_digit = |b: &u8| match radix {
+            16 => b.is_ascii_hexdigit(),
+            _ => b.is_ascii_digit(),
+        };
+        let digits_end = (digits_start..end)
+            .find(|&pos| !is_digit(&self.input[pos]))
+            .unwrap_or(end);
+        let digits = from_utf8(&self.input[digits_start..digits_end]).unwrap();
+        let num = usize::from_str_radix(digits, radix)
+            .map_err(|_| LexError::invalid_number(Loc(start, digits_end)))?;
+        let suffix = from_utf8(&self.input[digits_end..end]).unwrap();
+        let suffix = IntSuffix::parse(suffix)
+            .ok_or_else(|| LexError::invalid_suffix(Loc(digits_end, end)))?;
+        self.tokens.push(token!(Number(num, suffix), start, end));
         self.pos = end;
+        Ok(())
+    }
+
+    /// If a hexadecimal floating-point literal, such as `0x1.8p-3`, has its digits
+    /// from `pos`, return the end of it.
+    fn hex_float_end(&self, pos: usize) -> Option<usize> {
+        let skip_hex_digits = |mut pos: usize| {
+            while self.input.get(pos).is_some_and(u8::is_ascii_hexdigit) {
+                pos += 1;
+            }
+            pos
+        };
+        let mut pos = skip_hex_digits(pos);
+        if self.input.get(pos) == Some(&b'.') {
+            pos = skip_hex_digits(pos + 1);
+        }
+        if !matches!(self.input.get(pos), Some(b'p') | Some(b'P')) {
+            return None;
+        }
+        pos += 1;
+        if matches!(self.input.get(pos), Some(b'+') | Some(b'-')) {
+            pos += 1;
+        }
+        while self.input.get(pos).is_some_and(u8::is_ascii_alphanumeric) {
+            pos += 1;
+        }
+        Some(pos)
+    }
+
+    fn lex_str(&mut self) -> Result<(), LexError> {
+        let start = self.pos;
+        let mut bytes = Vec::new();
+        let mut pos = start + 1;
+        loop {
+            match self.input.get(pos) {
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    let escaped = match self.input.get(pos + 1) {
+                        Some(b'n') => b'\n',
+                        Some(b't') => b'\t',
+                        Some(b'0') => b'\0',
+                        Some(b'\\') => b'\\',
+                        Some(b'"') => b'"',
+                        Some(&b) => {
+                            return Err(LexError::invalid_escape(b as char, Loc(pos, pos + 2)))
+                        }
+                        None => return Err(LexError::unterminated_str(Loc(start, pos + 1))),
+                    };
+                    bytes.push(escaped);
+                    pos += 2;
+                }
+                Some(&b) => {
+                    bytes.push(b);
+                    pos += 1;
+                }
+                None => return Err(LexError::unterminated_str(Loc(start, pos))),
+            }
+        }
+        let string = String::from_utf8(bytes).unwrap();
+        self.tokens.push(token!(Str(string), start, pos + 1));
+        self.pos = pos + 1;
+        Ok(())
     }
 
-    fn lex_identifier(&mut self, keywords: &HashMap<String, TokenKind>) {
+    fn lex_identifier(&mut self, keywords: &HashMap<String, TokenKind>) -> Result<(), LexError> {
         let start = self.pos;
         let end = self.recognize_multiple_char(|b| b.is_ascii_alphanumeric() || b == b'_');
+        if end - start > self.max_identifier_len {
+            return Err(LexError::identifier_too_long(Loc(start, end)));
+        }
         let identifier = from_utf8(&self.input[start..end]).unwrap();
         let identifier = identifier.to_string();
         match keywords.get(&identifier) {
@@ -137,6 +357,7 @@ impl<'a> Lexer<'a> {
             None => self.tokens.push(token!(Identifier(identifier), start, end)),
         }
         self.pos = end;
+        Ok(())
     }
 
     /// Read a code while `f` returns `true` and return position of the end of fragment; each character in the fragment satisfies `f`.
@@ -148,14 +369,79 @@ impl<'a> Lexer<'a> {
         pos
     }
 
-    fn lex_semicolon(&mut self) {
-        self.tokens.push(token!(Semicolon, self.pos, self.pos + 1));
-        self.pos += 1;
+    fn lex_assignment(&mut self) {
+        self.lex_operator(TokenKind::Assignment, TokenKind::Equal);
     }
 
-    fn lex_assignment(&mut self) {
-        self.tokens.push(token!(Assignment, self.pos, self.pos + 1));
-        self.pos += 1;
+    fn lex_not_equal(&mut self) -> Result<(), LexError> {
+        if self.peek_char2() != Some('=') {
+            return Err(LexError::invalid_char('!', Loc(self.pos, self.pos + 1)));
+        }
+        self.lex_double(TokenKind::NotEqual);
+        Ok(())
+    }
+
+    /// Lex `<`, `<=`, `<<` or `<<=`.
+    fn lex_lt(&mut self) {
+        self.lex_shift_or_comparison(
+            '<',
+            [
+                TokenKind::Lt,
+                TokenKind::Le,
+                TokenKind::Shl,
+                TokenKind::ShlAssign,
+            ],
+        );
+    }
+
+    /// Lex `>`, `>=`, `>>` or `>>=`.
+    fn lex_gt(&mut self) {
+        self.lex_shift_or_comparison(
+            '>',
+            [
+                TokenKind::Gt,
+                TokenKind::Ge,
+                TokenKind::Shr,
+                TokenKind::ShrAssign,
+            ],
+        );
+    }
+
+    /// `kinds` are the comparison, the comparison with `=`, the shift
+    /// and the shift with `=`, in that order.
+    fn lex_shift_or_comparison(&mut self, c: char, kinds: [TokenKind; 4]) {
+        let [cmp, cmp_eq, shift, shift_assign] = kinds;
+        let start = self.pos;
+        self.bump();
+        let kind = if self.peek_char() == Some(c) {
+            self.bump();
+            if self.peek_char() == Some('=') {
+                self.bump();
+                shift_assign
+            } else {
+                shift
+            }
+        } else if self.peek_char() == Some('=') {
+            self.bump();
+            cmp_eq
+        } else {
+            cmp
+        };
+        self.push_token(kind, start);
+    }
+
+    /// Skip a comment to the end of the line.
+    /// A comment starting with exactly three slashes is kept as a doc comment.
+    fn lex_comment(&mut self) {
+        let start = self.pos;
+        let end = self.recognize_multiple_char(|b| b != b'\n');
+        let comment = &self.source[start..end];
+        if comment.starts_with("///") && !comment.starts_with("////") {
+            let doc = comment[3..].trim().to_string();
+            self.doc_comments
+                .push(Annotation::new(doc, Loc(start, end)));
+        }
+        self.pos = end;
     }
 
     fn skip_spaces(&mut self) {
@@ -169,6 +455,25 @@ mod tests {
     use crate::token::lexer::Lexer;
     use crate::Loc;
 
+    #[test]
+    fn test_empty_source() {
+        use crate::token::{Token, TokenKind};
+        assert_eq!(Lexer::new("").lex(), Ok(&vec![token!(Eof, 0, 0)]));
+        assert_eq!(Lexer::new(" \n\t \n").lex(), Ok(&vec![token!(Eof, 5, 5)]));
+    }
+
+    #[test]
+    fn test_eof_token() {
+        use crate::token::TokenKind;
+        for source_code in ["1", "func main() { return 0; }", "a // comment", "\"s\"\n"] {
+            let mut lexer = Lexer::new(source_code);
+            let tokens = lexer.lex().unwrap();
+            let last = tokens.last().unwrap();
+            assert_eq!(last.value, TokenKind::Eof);
+            assert_eq!(last.loc, Loc(source_code.len(), source_code.len()));
+        }
+    }
+
     #[test]
     fn test_lexer_error() {
         use crate::token::LexError;
@@ -176,4 +481,294 @@ mod tests {
         let tokens = lexer.lex();
         assert_eq!(tokens, Err(LexError::invalid_char('$', Loc(2, 3))),);
     }
+
+    #[test]
+    fn test_consecutive_minus() {
+        use crate::token::{IntSuffix, TokenKind};
+        let kinds = |source_code: &str| -> Vec<TokenKind> {
+            let mut lexer = Lexer::new(source_code);
+            let tokens = lexer.lex().unwrap();
+            tokens.iter().map(|token| token.value.clone()).collect()
+        };
+        let ten = TokenKind::Number(10, IntSuffix::None);
+        assert_eq!(
+            kinds("a - -10"),
+            [
+                TokenKind::Identifier("a".to_string()),
+                TokenKind::Minus,
+                TokenKind::Minus,
+                ten.clone(),
+                TokenKind::Eof
+            ]
+        );
+        assert_eq!(
+            kinds("- -10"),
+            [
+                TokenKind::Minus,
+                TokenKind::Minus,
+                ten.clone(),
+                TokenKind::Eof
+            ]
+        );
+        assert_eq!(
+            kinds("-(-10)"),
+            [
+                TokenKind::Minus,
+                TokenKind::LParen,
+                TokenKind::Minus,
+                ten,
+                TokenKind::RParen,
+                TokenKind::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scanner() {
+        use crate::token::LexError;
+        let mut lexer = Lexer::new("aé→b");
+        assert_eq!(lexer.peek_char(), Some('a'));
+        assert_eq!(lexer.peek_char2(), Some('é'));
+        assert_eq!(lexer.bump(), Some('a'));
+        assert_eq!(lexer.pos, 1);
+        assert_eq!(lexer.bump(), Some('é'));
+        assert_eq!(lexer.pos, 3);
+        assert_eq!(lexer.peek_char(), Some('→'));
+        assert_eq!(lexer.peek_char2(), Some('b'));
+        assert_eq!(lexer.bump(), Some('→'));
+        assert_eq!(lexer.pos, 6);
+        assert_eq!(lexer.bump(), Some('b'));
+        assert_eq!(lexer.peek_char2(), None);
+        assert_eq!(lexer.bump(), None);
+        assert_eq!(lexer.pos, 7);
+
+        let mut lexer = Lexer::new("1 → 2");
+        assert_eq!(lexer.lex(), Err(LexError::invalid_char('→', Loc(2, 5))));
+    }
+
+    #[test]
+    fn test_comment() {
+        use crate::token::{Token, TokenKind};
+        use crate::Annotation;
+        let mut lexer = Lexer::new("a // b\n/// doc\n//// c\n/");
+        assert_eq!(
+            lexer.lex(),
+            Ok(&vec![
+                token!(Identifier("a".to_string()), 0, 1),
+                token!(Slash, 22, 23),
+                token!(Eof, 23, 23),
+            ])
+        );
+        assert_eq!(
+            lexer.doc_comments,
+            vec![Annotation::new("doc".to_string(), Loc(7, 14))]
+        );
+    }
+
+    #[test]
+    fn test_number_prefix() {
+        use crate::token::{IntSuffix, LexError, TokenKind};
+        let lex_number = |source_code: &str| {
+            Lexer::new(source_code)
+                .lex()
+                .map(|tokens| tokens[0].value.clone())
+        };
+        assert_eq!(lex_number("0"), Ok(TokenKind::Number(0, IntSuffix::None)));
+        assert_eq!(
+            lex_number("0x10"),
+            Ok(TokenKind::Number(16, IntSuffix::None))
+        );
+        assert_eq!(
+            lex_number("0XfF"),
+            Ok(TokenKind::Number(255, IntSuffix::None))
+        );
+        assert_eq!(
+            lex_number("0b11"),
+            Ok(TokenKind::Number(3, IntSuffix::None))
+        );
+        assert_eq!(
+            lex_number("017"),
+            Ok(TokenKind::Number(15, IntSuffix::None))
+        );
+        assert_eq!(
+            lex_number("0.5"),
+            Err(LexError::float_not_supported(Loc(0, 3)))
+        );
+        assert_eq!(
+            lex_number("0."),
+            Err(LexError::float_not_supported(Loc(0, 2)))
+        );
+        assert_eq!(
+            lex_number("5.;"),
+            Err(LexError::float_not_supported(Loc(0, 2)))
+        );
+        assert_eq!(
+            lex_number("5..."),
+            Ok(TokenKind::Number(5, IntSuffix::None))
+        );
+        assert_eq!(lex_number("08"), Err(LexError::invalid_number(Loc(0, 2))));
+        assert_eq!(lex_number("0b12"), Err(LexError::invalid_number(Loc(0, 4))));
+        assert_eq!(lex_number("0x;"), Err(LexError::invalid_number(Loc(0, 2))));
+        assert_eq!(
+            lex_number("0x1p4"),
+            Err(LexError::hex_float_not_supported(Loc(0, 5)))
+        );
+        assert_eq!(
+            lex_number("0x1.8P-3;"),
+            Err(LexError::hex_float_not_supported(Loc(0, 8)))
+        );
+    }
+
+    #[test]
+    fn test_number_suffix() {
+        use crate::token::{IntSuffix, LexError, TokenKind};
+        let lex_number = |source_code: &str| {
+            Lexer::new(source_code)
+                .lex()
+                .map(|tokens| tokens[0].value.clone())
+        };
+        assert_eq!(
+            lex_number("10u"),
+            Ok(TokenKind::Number(10, IntSuffix::Unsigned))
+        );
+        assert_eq!(lex_number("5L"), Ok(TokenKind::Number(5, IntSuffix::Long)));
+        assert_eq!(
+            lex_number("3ULL"),
+            Ok(TokenKind::Number(3, IntSuffix::UnsignedLongLong))
+        );
+        assert_eq!(
+            lex_number("0xfflu"),
+            Ok(TokenKind::Number(255, IntSuffix::UnsignedLong))
+        );
+        assert_eq!(lex_number("10x"), Err(LexError::invalid_suffix(Loc(2, 3))));
+        assert_eq!(lex_number("1lL"), Err(LexError::invalid_suffix(Loc(1, 3))));
+        assert_eq!(lex_number("1uu"), Err(LexError::invalid_suffix(Loc(1, 3))));
+        // The location of a token covers its suffix.
+        assert_eq!(Lexer::new("7u").lex().unwrap()[0].loc, Loc(0, 2));
+    }
+
+    #[test]
+    fn test_compound_assignment_operators() {
+        use crate::token::testutil::token_diff;
+        use crate::token::{Token, TokenKind};
+        let mut lexer = Lexer::new("+= -= *= /= %= &= |= ^= <<= >>= % & | ^ << >> < > <= >=");
+        let tokens: Vec<Token> = lexer.lex().unwrap().clone();
+        let expected = vec![
+            token!(PlusAssign, 0, 2),
+            token!(MinusAssign, 3, 5),
+            token!(AsteriskAssign, 6, 8),
+            token!(SlashAssign, 9, 11),
+            token!(PercentAssign, 12, 14),
+            token!(AmpersandAssign, 15, 17),
+            token!(PipeAssign, 18, 20),
+            token!(CaretAssign, 21, 23),
+            token!(ShlAssign, 24, 27),
+            token!(ShrAssign, 28, 31),
+            token!(Percent, 32, 33),
+            token!(Ampersand, 34, 35),
+            token!(Pipe, 36, 37),
+            token!(Caret, 38, 39),
+            token!(Shl, 40, 42),
+            token!(Shr, 43, 45),
+            token!(Lt, 46, 47),
+            token!(Gt, 48, 49),
+            token!(Le, 50, 52),
+            token!(Ge, 53, 55),
+            token!(Eof, 55, 55),
+        ];
+        assert!(tokens == expected, "{}", token_diff(&expected, &tokens));
+    }
+
+    #[test]
+    fn test_comparison_operators() {
+        use crate::token::{Token, TokenKind};
+        let mut lexer = Lexer::new("a == b != c < d <= e > f >= g = h");
+        let kinds: Vec<TokenKind> = lexer
+            .lex()
+            .unwrap()
+            .iter()
+            .map(|token: &Token| token.value.clone())
+            .filter(|kind| !matches!(kind, TokenKind::Identifier(_)))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Equal,
+                TokenKind::NotEqual,
+                TokenKind::Lt,
+                TokenKind::Le,
+                TokenKind::Gt,
+                TokenKind::Ge,
+                TokenKind::Assignment,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_line_col_with_tab_width() {
+        let source_code = "func main() {\n\treturn 0;\n}";
+        let mut lexer = Lexer::new(source_code);
+        let pos = lexer.lex().unwrap()[5].loc.0;
+        assert_eq!(lexer.line_col(pos), (2, 2));
+
+        let mut lexer = Lexer::new(source_code).with_tab_width(4);
+        let pos = lexer.lex().unwrap()[5].loc.0;
+        assert_eq!(lexer.line_col(pos), (2, 5));
+    }
+
+    #[test]
+    fn test_str() {
+        use crate::token::{LexError, Token, TokenKind};
+        let mut lexer = Lexer::new(r#""hi\n""#);
+        assert_eq!(
+            lexer.lex(),
+            Ok(&vec![
+                token!(Str("hi\n".to_string()), 0, 6),
+                token!(Eof, 6, 6)
+            ])
+        );
+
+        let mut lexer = Lexer::new(r#""hi\q""#);
+        assert_eq!(lexer.lex(), Err(LexError::invalid_escape('q', Loc(3, 5))));
+
+        let mut lexer = Lexer::new(r#""hi"#);
+        assert_eq!(lexer.lex(), Err(LexError::unterminated_str(Loc(0, 3))));
+    }
+
+    #[test]
+    fn test_lex_presized() -> std::io::Result<()> {
+        let source_code = crate::read_file_content("examples/calc.pr")?;
+        let mut lexer = Lexer::new(&source_code);
+        let mut presized_lexer = Lexer::new(&source_code);
+        assert_eq!(presized_lexer.lex_presized(), lexer.lex());
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexer_limits() {
+        use crate::token::LexError;
+        let mut lexer = Lexer::with_limits("1 + 2 * 3", 3, 100);
+        assert_eq!(lexer.lex(), Err(LexError::input_too_large(Loc(6, 7))));
+
+        let mut lexer = Lexer::with_limits("1 + 2 * 3", 100, 5);
+        assert_eq!(lexer.lex(), Err(LexError::input_too_large(Loc(5, 9))));
+
+        let mut lexer = Lexer::with_limits("1 + 2 * 3", 5, 9);
+        // `Eof` is not counted in the limit.
+        assert_eq!(lexer.lex().map(|tokens| tokens.len()), Ok(6));
+    }
+
+    #[test]
+    fn test_max_identifier_len() {
+        use crate::token::LexError;
+        let mut lexer = Lexer::new("let abcd = abcde;").with_max_identifier_len(4);
+        assert_eq!(lexer.lex(), Err(LexError::identifier_too_long(Loc(11, 16))));
+
+        // Keywords are identifiers as well.
+        let mut lexer = Lexer::new("let abcd = abc;").with_max_identifier_len(4);
+        assert_eq!(lexer.lex().map(|tokens| tokens.len()), Ok(6));
+        let mut lexer = Lexer::new("const a = 1;").with_max_identifier_len(4);
+        assert_eq!(lexer.lex(), Err(LexError::identifier_too_long(Loc(0, 5))));
+    }
 }
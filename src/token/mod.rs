@@ -1,67 +1,287 @@
 pub mod lexer;
+#[cfg(test)]
+pub mod testutil;
 
 use crate::Annotation;
 use crate::Loc;
 use std::fmt;
 
+/// Suffix of an integer literal, such as `u` of `10u`, which specifies its type as in C.
+/// The letters are case-insensitive, but `ll` is not written in mixed case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum IntSuffix {
+    #[default]
+    None,
+    Unsigned,
+    Long,
+    UnsignedLong,
+    LongLong,
+    UnsignedLongLong,
+}
+
+impl IntSuffix {
+    /// Parse letters following the digits of an integer literal.
+    pub fn parse(letters: &str) -> Option<Self> {
+        if letters.contains("lL") || letters.contains("Ll") {
+            return None;
+        }
+        let suffix = match letters.to_ascii_lowercase().as_str() {
+            "" => IntSuffix::None,
+            "u" => IntSuffix::Unsigned,
+            "l" => IntSuffix::Long,
+            "ul" | "lu" => IntSuffix::UnsignedLong,
+            "ll" => IntSuffix::LongLong,
+            "ull" | "llu" => IntSuffix::UnsignedLongLong,
+            _ => return None,
+        };
+        Some(suffix)
+    }
+
+    pub fn is_unsigned(self) -> bool {
+        matches!(
+            self,
+            IntSuffix::Unsigned | IntSuffix::UnsignedLong | IntSuffix::UnsignedLongLong
+        )
+    }
+}
+
+impl fmt::Display for IntSuffix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let letters = match self {
+            IntSuffix::None => "",
+            IntSuffix::Unsigned => "u",
+            IntSuffix::Long => "l",
+            IntSuffix::UnsignedLong => "ul",
+            IntSuffix::LongLong => "ll",
+            IntSuffix::UnsignedLongLong => "ull",
+        };
+        write!(f, "{}", letters)
+    }
+}
+
 /// Data type that represents Token.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum TokenKind {
-    Number(usize),
+    /// Integer literal with the suffix written after its digits.
+    Number(usize, IntSuffix),
+    Str(String),
     Identifier(String),
     U64,
+    U8,
     Plus,
     Minus,
     Asterisk,
     Slash,
+    Percent,
+    Ampersand,
+    Pipe,
+    LogAnd,
+    LogOr,
+    Caret,
+    Shl,
+    Shr,
+    Increment,
+    Decrement,
     LParen,
     RParen,
     LBrace,
     RBrace,
+    LBracket,
+    RBracket,
     Colon,
+    Question,
     Comma,
+    Ellipsis,
     Let,
+    Const,
+    Static,
     Func,
+    Extern,
+    Typedef,
     If,
+    Else,
+    Goto,
+    Switch,
+    Case,
+    Default,
+    Break,
+    While,
+    For,
+    Continue,
+    Sizeof,
+    Volatile,
+    Restrict,
     Assignment,
+    PlusAssign,
+    MinusAssign,
+    AsteriskAssign,
+    SlashAssign,
+    PercentAssign,
+    AmpersandAssign,
+    PipeAssign,
+    CaretAssign,
+    ShlAssign,
+    ShrAssign,
+    Equal,
+    NotEqual,
+    Lt,
+    Le,
+    Gt,
+    Ge,
     Semicolon,
     Return,
+    /// End of the token stream.
+    Eof,
 }
 
 impl fmt::Display for TokenKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use TokenKind::*;
         match self {
-            Number(n) => n.fmt(f),
+            Number(n, suffix) => write!(f, "{}{}", n, suffix),
+            Str(string) => write!(f, "{:?}", string),
             Identifier(ident) => write!(f, "{}", ident),
             U64 => write!(f, "u64"),
+            U8 => write!(f, "u8"),
             Plus => write!(f, "+"),
             Minus => write!(f, "-"),
             Asterisk => write!(f, "*"),
             Slash => write!(f, "/"),
+            Percent => write!(f, "%"),
+            Ampersand => write!(f, "&"),
+            Pipe => write!(f, "|"),
+            LogAnd => write!(f, "&&"),
+            LogOr => write!(f, "||"),
+            Caret => write!(f, "^"),
+            Shl => write!(f, "<<"),
+            Shr => write!(f, ">>"),
+            Increment => write!(f, "++"),
+            Decrement => write!(f, "--"),
             LParen => write!(f, "("),
             RParen => write!(f, ")"),
             LBrace => write!(f, "{{"),
             RBrace => write!(f, "}}"),
+            LBracket => write!(f, "["),
+            RBracket => write!(f, "]"),
             Func => write!(f, "func"),
+            Extern => write!(f, "extern"),
+            Typedef => write!(f, "typedef"),
             Colon => write!(f, ":"),
+            Question => write!(f, "?"),
             Comma => write!(f, ","),
+            Ellipsis => write!(f, "..."),
             Let => write!(f, "let"),
+            Const => write!(f, "const"),
+            Static => write!(f, "static"),
             If => write!(f, "if"),
+            Else => write!(f, "else"),
+            Goto => write!(f, "goto"),
+            Switch => write!(f, "switch"),
+            Case => write!(f, "case"),
+            Default => write!(f, "default"),
+            Break => write!(f, "break"),
+            While => write!(f, "while"),
+            For => write!(f, "for"),
+            Continue => write!(f, "continue"),
+            Sizeof => write!(f, "sizeof"),
+            Volatile => write!(f, "volatile"),
+            Restrict => write!(f, "restrict"),
             Assignment => write!(f, "="),
+            PlusAssign => write!(f, "+="),
+            MinusAssign => write!(f, "-="),
+            AsteriskAssign => write!(f, "*="),
+            SlashAssign => write!(f, "/="),
+            PercentAssign => write!(f, "%="),
+            AmpersandAssign => write!(f, "&="),
+            PipeAssign => write!(f, "|="),
+            CaretAssign => write!(f, "^="),
+            ShlAssign => write!(f, "<<="),
+            ShrAssign => write!(f, ">>="),
+            Equal => write!(f, "=="),
+            NotEqual => write!(f, "!="),
+            Lt => write!(f, "<"),
+            Le => write!(f, "<="),
+            Gt => write!(f, ">"),
+            Ge => write!(f, ">="),
             Semicolon => write!(f, ";"),
             Return => write!(f, "return"),
+            Eof => write!(f, "end of file"),
+        }
+    }
+}
+
+/// Group of token kinds, used to describe what is expected in diagnostics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenCategory {
+    Operator,
+    Keyword,
+    Literal,
+    Identifier,
+    Punct,
+}
+
+impl fmt::Display for TokenCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TokenCategory::Operator => "operator",
+            TokenCategory::Keyword => "keyword",
+            TokenCategory::Literal => "literal",
+            TokenCategory::Identifier => "identifier",
+            TokenCategory::Punct => "punctuation",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl TokenKind {
+    /// Group which the kind belongs to. Type names are keywords,
+    /// and `Eof` counts as punctuation.
+    pub fn category(&self) -> TokenCategory {
+        use TokenKind::*;
+        match self {
+            Number(..) | Str(_) => TokenCategory::Literal,
+            Identifier(_) => TokenCategory::Identifier,
+            U64 | U8 | Let | Const | Static | Func | Extern | Typedef | If | Else | Goto
+            | Switch | Case | Default | Break | While | For | Continue | Sizeof | Volatile
+            | Restrict | Return => TokenCategory::Keyword,
+            Plus | Minus | Asterisk | Slash | Percent | Ampersand | Pipe | LogAnd | LogOr
+            | Caret | Shl | Shr | Increment | Decrement | Question | Assignment | PlusAssign
+            | MinusAssign | AsteriskAssign | SlashAssign | PercentAssign | AmpersandAssign
+            | PipeAssign | CaretAssign | ShlAssign | ShrAssign | Equal | NotEqual | Lt | Le
+            | Gt | Ge => TokenCategory::Operator,
+            LParen | RParen | LBrace | RBrace | LBracket | RBracket | Colon | Comma | Ellipsis
+            | Semicolon | Eof => TokenCategory::Punct,
         }
     }
 }
 
 pub type Token = Annotation<TokenKind>;
 
+impl Token {
+    /// Kind of the token.
+    pub fn kind(&self) -> &TokenKind {
+        &self.value
+    }
+
+    /// Location of the token in the source code.
+    pub fn loc(&self) -> Loc {
+        self.loc
+    }
+}
+
 // ---ERROR DEFINITION---
 /// Data type that represents lexical error.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum LexErrorKind {
     InvalidChar(char),
+    InvalidEscape(char),
+    UnterminatedStr,
+    InvalidNumber,
+    FloatNotSupported,
+    HexFloatNotSupported,
+    InvalidSuffix,
+    InputTooLarge,
+    IdentifierTooLong,
     Eof,
 }
 
@@ -73,6 +293,47 @@ impl LexError {
         LexError::new(LexErrorKind::InvalidChar(c), loc)
     }
 
+    /// Unknown escape sequence in a string literal.
+    pub fn invalid_escape(c: char, loc: Loc) -> Self {
+        LexError::new(LexErrorKind::InvalidEscape(c), loc)
+    }
+
+    /// String literal which is not closed by `"`.
+    pub fn unterminated_str(loc: Loc) -> Self {
+        LexError::new(LexErrorKind::UnterminatedStr, loc)
+    }
+
+    /// Number literal with a digit out of its radix, no digits after a prefix,
+    /// or a value which overflows.
+    pub fn invalid_number(loc: Loc) -> Self {
+        LexError::new(LexErrorKind::InvalidNumber, loc)
+    }
+
+    /// Letters after the digits of a number literal which are not an integer suffix.
+    pub fn invalid_suffix(loc: Loc) -> Self {
+        LexError::new(LexErrorKind::InvalidSuffix, loc)
+    }
+
+    /// Floating-point literal, which is not supported yet.
+    pub fn float_not_supported(loc: Loc) -> Self {
+        LexError::new(LexErrorKind::FloatNotSupported, loc)
+    }
+
+    /// Hexadecimal floating-point literal such as `0x1p4`, which is not supported yet.
+    pub fn hex_float_not_supported(loc: Loc) -> Self {
+        LexError::new(LexErrorKind::HexFloatNotSupported, loc)
+    }
+
+    /// Input code or the number of tokens exceeds the limit.
+    pub fn input_too_large(loc: Loc) -> Self {
+        LexError::new(LexErrorKind::InputTooLarge, loc)
+    }
+
+    /// Identifier longer than the limit.
+    pub fn identifier_too_long(loc: Loc) -> Self {
+        LexError::new(LexErrorKind::IdentifierTooLong, loc)
+    }
+
     pub fn eof(loc: Loc) -> Self {
         LexError::new(LexErrorKind::Eof, loc)
     }
@@ -83,7 +344,62 @@ impl fmt::Display for LexError {
         let loc = &self.loc;
         match self.value {
             LexErrorKind::InvalidChar(c) => write!(f, "{}: Invalid character '{}'", loc, c),
+            LexErrorKind::InvalidEscape(c) => {
+                write!(f, "{}: Invalid escape sequence '\\{}'", loc, c)
+            }
+            LexErrorKind::UnterminatedStr => write!(f, "{}: Unterminated string literal", loc),
+            LexErrorKind::InvalidNumber => write!(f, "{}: Invalid number literal", loc),
+            LexErrorKind::InvalidSuffix => write!(f, "{}: Invalid suffix of number literal", loc),
+            LexErrorKind::FloatNotSupported => {
+                write!(f, "{}: Floating-point literal is not supported", loc)
+            }
+            LexErrorKind::HexFloatNotSupported => {
+                write!(
+                    f,
+                    "{}: Hexadecimal floating-point literal is not supported",
+                    loc
+                )
+            }
+            LexErrorKind::InputTooLarge => write!(f, "{}: Input too large", loc),
+            LexErrorKind::IdentifierTooLong => write!(f, "{}: Identifier too long", loc),
             LexErrorKind::Eof => write!(f, "End of file"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::token::{IntSuffix, Token, TokenCategory, TokenKind};
+    use crate::Loc;
+
+    #[test]
+    fn test_token_accessors() {
+        let token = Token::new(TokenKind::Identifier("a".to_string()), Loc(4, 5));
+        assert_eq!(token.kind(), &TokenKind::Identifier("a".to_string()));
+        assert_eq!(token.loc(), Loc(4, 5));
+    }
+
+    #[test]
+    fn test_category() {
+        let cases = [
+            (TokenKind::Shl, TokenCategory::Operator),
+            (TokenKind::PlusAssign, TokenCategory::Operator),
+            (TokenKind::While, TokenCategory::Keyword),
+            (TokenKind::U8, TokenCategory::Keyword),
+            (
+                TokenKind::Number(1, IntSuffix::None),
+                TokenCategory::Literal,
+            ),
+            (TokenKind::Str("s".to_string()), TokenCategory::Literal),
+            (
+                TokenKind::Identifier("a".to_string()),
+                TokenCategory::Identifier,
+            ),
+            (TokenKind::Semicolon, TokenCategory::Punct),
+        ];
+        for (kind, category) in cases {
+            assert_eq!(kind.category(), category, "{}", kind);
+        }
+        assert_eq!(TokenCategory::Punct.to_string(), "punctuation");
+    }
+}
@@ -0,0 +1,53 @@
+//! Helpers for tests on tokens.
+use crate::token::Token;
+
+/// Describe the first difference between two token streams,
+/// or return an empty string if they are the same.
+pub fn token_diff(expected: &[Token], actual: &[Token]) -> String {
+    let describe = |token: Option<&Token>| match token {
+        Some(token) => format!("`{}` at {} ({:?})", token.kind(), token.loc(), token.kind()),
+        None => "nothing".to_string(),
+    };
+    let len = expected.len().max(actual.len());
+    match (0..len).find(|&i| expected.get(i) != actual.get(i)) {
+        Some(i) => format!(
+            "tokens differ at index {}:\n  expected: {}\n    actual: {}",
+            i,
+            describe(expected.get(i)),
+            describe(actual.get(i))
+        ),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::token::testutil::token_diff;
+    use crate::token::{IntSuffix, Token, TokenKind};
+    use crate::Loc;
+
+    #[test]
+    fn test_token_diff() {
+        let expected = vec![
+            token!(Number(1, IntSuffix::None), 0, 1),
+            token!(Plus, 2, 3),
+            token!(Number(2, IntSuffix::None), 4, 5),
+        ];
+        let mut actual = expected.clone();
+        assert_eq!(token_diff(&expected, &actual), "");
+
+        actual[1] = token!(Minus, 2, 3);
+        assert_eq!(
+            token_diff(&expected, &actual),
+            "tokens differ at index 1:\n  expected: `+` at 2-3 (Plus)\n    actual: `-` at 2-3 (Minus)"
+        );
+
+        actual.truncate(2);
+        assert_eq!(
+            token_diff(&actual, &expected),
+            "tokens differ at index 1:\n  expected: `-` at 2-3 (Minus)\n    actual: `+` at 2-3 (Plus)"
+        );
+        assert!(token_diff(&expected[..2], &expected)
+            .ends_with("expected: nothing\n    actual: `2` at 4-5 (Number(2, None))"));
+    }
+}
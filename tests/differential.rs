@@ -0,0 +1,215 @@
+//! Compare the exit status of compiled programs with the result of the interpreter.
+//! Any mismatch is a bug of the backend, since both of them wrap arithmetic the same way.
+//! Skipped if `gcc` is not available to assemble and link the generated code.
+
+extern crate prodio;
+
+use prodio::code::code_gen::Generator;
+use prodio::eval::Interpreter;
+use prodio::ir::OverflowMode;
+use prodio::opt::OptLevel;
+use prodio::parse::parser::Parser;
+use prodio::target::Target;
+use std::fs;
+use std::process::Command;
+
+const SEEDS: [&str; 6] = [
+    "return (1 + 5 * 4) / 3;",
+    "let a: u64 = 7; let b: u64 = a * a - 9; return b % 17 + (a << 2);",
+    "let a: u8 = 300; return a + 1;",
+    "let a: u64 = 0 - 20; return a / 3 + 10 > 0 ? a % 7 + 50 : 99;",
+    "let a: u64 = 5; let b: u64 = a > 3 && a != 4 || a / 0; return b + (a == 5) * 2;",
+    "let a: u64 = 12; a += 3; a *= 2; a -= 7; return a >> 1 ^ 6 | 1;",
+];
+
+/// xorshift64, which keeps generated programs the same on every run.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % bound
+    }
+}
+
+/// Random expression using variables of `vars` and small literals.
+fn gen_expr(rng: &mut Rng, vars: &[&str], depth: usize) -> String {
+    const OPS: [&str; 17] = [
+        "+", "-", "*", "/", "%", "&", "|", "^", "<<", ">>", "==", "!=", "<", "<=", "&&", "||", ">",
+    ];
+    if depth == 0 || rng.next(4) == 0 {
+        return match rng.next(3) {
+            0 if !vars.is_empty() => vars[rng.next(vars.len() as u64) as usize].to_string(),
+            _ => rng.next(20).to_string(),
+        };
+    }
+    match rng.next(8) {
+        0 => format!(
+            "({} ? {} : {})",
+            gen_expr(rng, vars, depth - 1),
+            gen_expr(rng, vars, depth - 1),
+            gen_expr(rng, vars, depth - 1)
+        ),
+        1 => format!("(-{})", gen_expr(rng, vars, depth - 1)),
+        _ => format!(
+            "({} {} {})",
+            gen_expr(rng, vars, depth - 1),
+            OPS[rng.next(OPS.len() as u64) as usize],
+            gen_expr(rng, vars, depth - 1)
+        ),
+    }
+}
+
+fn gen_program(rng: &mut Rng) -> String {
+    format!(
+        "let a: u64 = {}; let b: u8 = {}; let c: u64 = {}; return {};",
+        gen_expr(rng, &[], 2),
+        rng.next(512),
+        gen_expr(rng, &["a", "b"], 2),
+        gen_expr(rng, &["a", "b", "c"], 4)
+    )
+}
+
+/// Result of the interpreter, or `None` if the program divides by zero.
+fn interpret(body: &str) -> Option<i64> {
+    let tokens = prodio::lex_source(body).unwrap();
+    let asts = Parser::new(&tokens).allow_trailing_expr().parse().unwrap();
+    Interpreter::new().eval(&asts).ok().flatten()
+}
+
+/// Exit status of the compiled program whose `main` has `body`.
+fn run(body: &str, opt_level: OptLevel, name: &str) -> i32 {
+    run_source(&format!("func main() {{ {} }}", body), opt_level, name)
+}
+
+/// Exit status of the program compiled from `source`.
+fn run_source(source: &str, opt_level: OptLevel, name: &str) -> i32 {
+    let mut ir_generator = prodio::compile_with(source, opt_level, OverflowMode::Wrapping).unwrap();
+    let target = Target::default();
+    ir_generator.reg_alloc(&target);
+    let mut generator = Generator::with_target(target);
+    generator.code_gen(&ir_generator);
+
+    let dir = std::env::temp_dir();
+    let asm_path = dir.join(format!("{}.s", name));
+    let bin_path = dir.join(name);
+    fs::write(&asm_path, generator.code.join("\n") + "\n").unwrap();
+    let output = Command::new("gcc")
+        .arg("-o")
+        .arg(&bin_path)
+        .arg(&asm_path)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "`{}` cannot be assembled: {}",
+        source,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let status = Command::new(&bin_path).status().unwrap();
+    fs::remove_file(asm_path).unwrap();
+    fs::remove_file(bin_path).unwrap();
+    status.code().unwrap()
+}
+
+#[test]
+fn test_interpreter_agrees_with_binary() {
+    if Command::new("gcc").arg("--version").output().is_err() {
+        eprintln!("gcc is not found. Skipped.");
+        return;
+    }
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+    let programs = SEEDS
+        .iter()
+        .map(|body| body.to_string())
+        .chain((0..30).map(|_| gen_program(&mut rng)));
+
+    let mut mismatches = Vec::new();
+    let mut run_count = 0;
+    for (i, body) in programs.enumerate() {
+        let expected = match interpret(&body) {
+            Some(value) => value as u8 as i32,
+            None => continue,
+        };
+        run_count += 1;
+        for opt_level in [OptLevel::O0, OptLevel::O2] {
+            let name = format!(
+                "prodio_differential_{}_{}_{:?}",
+                std::process::id(),
+                i,
+                opt_level
+            );
+            let actual = run(&body, opt_level, &name);
+            if actual != expected {
+                mismatches.push(format!(
+                    "{:?} `{}`: expected {}, got {}",
+                    opt_level, body, expected, actual
+                ));
+            }
+        }
+    }
+    assert!(mismatches.is_empty(), "{:#?}", mismatches);
+    // Most of the programs must not be skipped by division by zero.
+    assert!(run_count > 20, "Only {} programs are run", run_count);
+}
+
+/// Programs using features the interpreter lacks, such as pointers and calls,
+/// are checked against their known exit status.
+#[test]
+fn test_known_exit_status() {
+    if Command::new("gcc").arg("--version").output().is_err() {
+        eprintln!("gcc is not found. Skipped.");
+        return;
+    }
+    let programs = [
+        // A byte store must leave the neighbouring byte unchanged.
+        (
+            "extern func malloc(n: u64);
+            func main() { let p: u8* = malloc(16); let q: u8* = p + 1; *q = 2; *p = 1; return *q; }",
+            2,
+        ),
+        // Evaluating a later argument must not clobber an earlier one in `rcx` or `rdx`.
+        (
+            "func f(a: u64, b: u64, c: u64, d: u64, e: u64) { return d; }
+            func g(x: u64) { return f(1, 2, 3, 4, x >> 1); }
+            func main() { return g(32); }",
+            4,
+        ),
+        (
+            "func f(a: u64, b: u64, c: u64, d: u64) { return c; }
+            func g(x: u64) { return f(1, 2, 3, x / 5); }
+            func main() { return g(32); }",
+            3,
+        ),
+        // An array is as large as all of its elements and decays into its address.
+        (
+            "func main() { let b: u8[3]; *b = 1; *(b + 1) = 2; *(b + 2) = 3;
+            return *b + *(b + 1) * *(b + 2) + sizeof b; }",
+            10,
+        ),
+        (
+            "func main() { let m: u64[2][3]; *(*(m + 1) + 2) = 7;
+            return *(*(m + 1) + 2) + sizeof m + sizeof *m; }",
+            79,
+        ),
+        (
+            "func f(p: u64[3]) { return *(p + 2) + sizeof p; }
+            func main() { let x: u64 = 9; let a: u64[3]; *(a + 2) = 4; return f(a) + x; }",
+            21,
+        ),
+    ];
+    for (i, (source, expected)) in programs.iter().enumerate() {
+        for opt_level in [OptLevel::O0, OptLevel::O2] {
+            let name = format!("prodio_known_{}_{}_{:?}", std::process::id(), i, opt_level);
+            assert_eq!(
+                run_source(source, opt_level, &name),
+                *expected,
+                "{:?} `{}`",
+                opt_level,
+                source
+            );
+        }
+    }
+}
@@ -0,0 +1,41 @@
+//! Compare IR emitted for each `tests/golden/*.pr` with the golden file `*.ir` next to it.
+//! Run with `UPDATE_GOLDEN=1` to regenerate the golden files after changing a pass.
+
+extern crate prodio;
+
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn test_golden_ir() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut sources: Vec<_> = fs::read_dir(Path::new("tests/golden"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "pr"))
+        .collect();
+    sources.sort();
+    assert!(!sources.is_empty());
+
+    let mut mismatches = Vec::new();
+    for source_path in sources {
+        let source = fs::read_to_string(&source_path).unwrap();
+        let ir = prodio::emit_ir(&source)
+            .unwrap_or_else(|error| panic!("{}: {}", source_path.display(), error));
+        let golden_path = source_path.with_extension("ir");
+        if update {
+            fs::write(&golden_path, &ir).unwrap();
+            continue;
+        }
+        let golden = fs::read_to_string(&golden_path).unwrap_or_default();
+        if ir != golden {
+            eprintln!("------{}------\n{}", golden_path.display(), ir);
+            mismatches.push(golden_path);
+        }
+    }
+    assert!(
+        mismatches.is_empty(),
+        "IR differs from {:?}. Run with UPDATE_GOLDEN=1 to regenerate them.",
+        mismatches
+    );
+}